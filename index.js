@@ -4,18 +4,6 @@ window.addEventListener('load', async () => {
     const infoBlock = document.createElement('div');
     document.body.appendChild(infoBlock);
 
-    let fps = 0;
-
-    let lastTickFps = 0;
-
-    function updateInfo() {
-        lastTickFps = fps;
-        fps = 0;
-        setTimeout(updateInfo, 1000);
-    }
-
-    updateInfo();
-
     await init('./pkg/webgl_engine_bg.wasm');
 
     const scene = new Scene();
@@ -26,8 +14,7 @@ window.addEventListener('load', async () => {
 
         scene.update_state();
         scene.draw();
-        fps += 1;
-        infoBlock.innerHTML = `fps is ${lastTickFps}`;
+        infoBlock.innerHTML = `fps is ${scene.stats.fps.toFixed(1)}`;
     }
 
     draw();
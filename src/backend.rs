@@ -0,0 +1,185 @@
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlImageElement, ImageBitmap};
+
+use crate::capabilities::Capabilities;
+
+/// Opaque handles the engine uses to refer to objects owned by a `Backend`.
+/// The backend decides what a handle maps to, so they can be copied freely.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BufferId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShaderId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProgramId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UniformLocationId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VertexArrayId(pub u32);
+
+/// What reflection reports about an active attribute or uniform
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveInfo {
+    pub name: String,
+    /// Number of elements for arrays, 1 otherwise
+    pub size: i32,
+    /// GL type enum such as `FLOAT_VEC3`
+    pub data_type: u32,
+}
+
+/// Where and in which format `tex_image_2d` stores an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureUpload {
+    pub target: u32,
+    /// Mipmap level, 0 is the full size image
+    pub level: i32,
+    pub internal_format: i32,
+    pub format: u32,
+    pub data_type: u32,
+}
+
+/// Browser images a texture can be uploaded from
+#[derive(Clone, Copy, Debug)]
+pub enum ImageSource<'a> {
+    Image(&'a HtmlImageElement),
+    Bitmap(&'a ImageBitmap),
+}
+
+impl ImageSource<'_> {
+    /// Width and height in pixels, for an image element that's its natural size
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            ImageSource::Image(image) => (image.natural_width(), image.natural_height()),
+            ImageSource::Bitmap(bitmap) => (bitmap.width(), bitmap.height()),
+        }
+    }
+}
+
+/// The subset of the GL API the engine relies on.
+///
+/// Enums such as buffer targets, draw modes and capabilities are passed
+/// as raw GL constants (`WebGlRenderingContext::TRIANGLES` and friends),
+/// the same way they are passed to WebGL itself.
+pub trait Backend {
+    fn capabilities(&self) -> Capabilities;
+
+    fn create_buffer(&self) -> Option<BufferId>;
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>);
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32);
+    /// Allocates `size` bytes of undefined contents, orphaning the previous storage
+    fn buffer_data_size(&self, target: u32, size: i32, usage: u32);
+    /// Overwrites the bytes from `offset`, which must fit into the allocated storage
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]);
+    fn delete_buffer(&self, buffer: BufferId);
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId>;
+    fn shader_source(&self, shader: ShaderId, source: &str);
+    fn compile_shader(&self, shader: ShaderId);
+    fn get_shader_compile_status(&self, shader: ShaderId) -> bool;
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String>;
+    fn delete_shader(&self, shader: ShaderId);
+
+    fn create_program(&self) -> Option<ProgramId>;
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId);
+    fn link_program(&self, program: ProgramId);
+    fn get_program_link_status(&self, program: ProgramId) -> bool;
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String>;
+    fn use_program(&self, program: Option<ProgramId>);
+    fn delete_program(&self, program: ProgramId);
+
+    fn get_active_attrib_count(&self, program: ProgramId) -> u32;
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo>;
+    fn get_active_uniform_count(&self, program: ProgramId) -> u32;
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo>;
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32;
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocationId>;
+    /// `components` goes from 1 to 4, as in `uniform1fv` to `uniform4fv`
+    fn uniform_fv(&self, location: UniformLocationId, components: u32, data: &[f32]);
+    /// `components` goes from 1 to 4, as in `uniform1iv` to `uniform4iv`
+    fn uniform_iv(&self, location: UniformLocationId, components: u32, data: &[i32]);
+    /// Square matrices only, `columns` goes from 2 to 4
+    fn uniform_matrix_fv(
+        &self,
+        location: UniformLocationId,
+        columns: u32,
+        transpose: bool,
+        data: &[f32],
+    );
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        data_type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    );
+    fn enable_vertex_attrib_array(&self, index: u32);
+    fn disable_vertex_attrib_array(&self, index: u32);
+    /// Sets the value used for an attribute while its array is disabled
+    fn vertex_attrib4fv(&self, index: u32, values: &[f32]);
+    /// Requires `Capabilities::instancing`
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32);
+
+    /// Requires `Capabilities::vertex_array_objects`
+    fn create_vertex_array(&self) -> Option<VertexArrayId>;
+    /// Requires `Capabilities::vertex_array_objects`, `None` binds the default one
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>);
+    /// Requires `Capabilities::vertex_array_objects`
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId);
+
+    fn create_texture(&self) -> Option<TextureId>;
+    /// `unit` is the GL enum, `TEXTURE0` and up
+    fn active_texture(&self, unit: u32);
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>);
+    /// `pixels` are tightly packed rows, floats are passed as their bytes
+    fn tex_image_2d(
+        &self,
+        upload: &TextureUpload,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+    ) -> Result<(), JsValue>;
+    fn tex_image_2d_from_source(
+        &self,
+        upload: &TextureUpload,
+        source: ImageSource,
+    ) -> Result<(), JsValue>;
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32);
+    fn generate_mipmap(&self, target: u32);
+    fn pixel_storei(&self, pname: u32, param: i32);
+    fn delete_texture(&self, texture: TextureId);
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn clear_depth(&self, depth: f32);
+    fn enable(&self, cap: u32);
+    fn disable(&self, cap: u32);
+    fn depth_func(&self, func: u32);
+    fn depth_mask(&self, flag: bool);
+    fn blend_func(&self, source: u32, destination: u32);
+    fn cull_face(&self, mode: u32);
+    fn clear(&self, mask: u32);
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32);
+    /// Requires `Capabilities::instancing`
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32);
+    /// Requires `Capabilities::instancing`
+    fn draw_elements_instanced(
+        &self,
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+        instance_count: i32,
+    );
+}
@@ -1,59 +1,400 @@
-use std::fmt::Display;
+use std::ops::Range;
 
-use js_sys::{Error, Float32Array, Uint16Array, WebAssembly};
-use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{WebGlBuffer, WebGlRenderingContext};
+use web_sys::WebGlRenderingContext;
 
+use crate::backend::BufferId;
+use crate::error::Error;
+use crate::gl_object::OwnedBuffer;
+use crate::pod::{Pod, as_bytes};
 use crate::rendering_context::{BufferKind, RenderingContext, get_buffer_type};
 
-pub struct BufferStorage<T: Display + Sized> {
+/// Above this many separate dirty ranges they are merged into one,
+/// a single larger upload is cheaper than many small ones
+const MAX_DIRTY_RANGES: usize = 8;
+
+/// How often the data is expected to change, a hint for the driver
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Uploaded once, drawn many times
+    #[default]
+    Static,
+    /// Changed now and then, drawn many times
+    Dynamic,
+    /// Rewritten for about every draw, like particles
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn gl_usage(self) -> u32 {
+        match self {
+            BufferUsage::Static => WebGlRenderingContext::STATIC_DRAW,
+            BufferUsage::Dynamic => WebGlRenderingContext::DYNAMIC_DRAW,
+            BufferUsage::Stream => WebGlRenderingContext::STREAM_DRAW,
+        }
+    }
+}
+
+/// CPU copy of a buffer's data, uploaded byte for byte.
+/// Element array buffers only take `u8`, `u16` and `u32`.
+///
+/// Changes made through the mutable accessors are remembered as dirty ranges
+/// and sent with `flush`, only the parts that changed
+pub struct BufferStorage<T: Pod> {
     kind: BufferKind,
-    buffer: WebGlBuffer,
+    usage: BufferUsage,
+    buffer: OwnedBuffer,
     data: Vec<T>,
+    /// Elements the graphics card has room for
+    capacity: usize,
+    /// Sorted, neither overlapping nor touching
+    dirty: Vec<Range<usize>>,
 }
 
-impl<T: Display + Sized> BufferStorage<T> {
+impl<T: Pod> BufferStorage<T> {
     pub fn new(ctx: &RenderingContext, kind: BufferKind, data: Vec<T>) -> Result<Self, Error> {
+        Self::with_usage(ctx, kind, data, BufferUsage::Static)
+    }
+
+    pub fn with_usage(
+        ctx: &RenderingContext,
+        kind: BufferKind,
+        data: Vec<T>,
+        usage: BufferUsage,
+    ) -> Result<Self, Error> {
+        if kind == BufferKind::ElementArrayBuffer && T::INDEX_TYPE.is_none() {
+            return Err(Error::new(&format!(
+                "{} can't be used as indices, only u8, u16 and u32 can",
+                std::any::type_name::<T>()
+            )));
+        }
         let buffer = ctx.create_buffer()?;
-        Ok(BufferStorage { kind, buffer, data })
+        Ok(BufferStorage {
+            kind,
+            usage,
+            buffer,
+            data,
+            capacity: 0,
+            dirty: vec![],
+        })
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Elements the graphics card has room for, grows when `flush` needs more
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Marks everything dirty, prefer `range_mut` for partial changes
+    pub fn data_mut(&mut self) -> &mut [T] {
+        self.range_mut(0..self.data.len())
+    }
+
+    /// Marks the range dirty
+    pub fn range_mut(&mut self, range: Range<usize>) -> &mut [T] {
+        self.mark_dirty(range.clone());
+        &mut self.data[range]
+    }
+
+    /// Overwrites the elements from `offset`, growing the data when they run past its end.
+    /// A gap between the old end and `offset` is filled with `values[0]`
+    pub fn write(&mut self, offset: usize, values: &[T]) {
+        if values.is_empty() {
+            return;
+        }
+        let end = offset + values.len();
+        let len = self.data.len();
+        if end > len {
+            self.data.resize(end, values[0]);
+            // the graphics card has never seen the gap either
+            self.mark_dirty(len.min(offset)..end);
+        }
+        self.range_mut(offset..end).copy_from_slice(values);
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        let len = self.data.len();
+        self.write(len, values);
+    }
+
+    /// Keeps the graphics card storage, so growing back is free
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        for range in &mut self.dirty {
+            range.end = range.end.min(len);
+        }
+        self.dirty.retain(|range| !range.is_empty());
+    }
+
+    /// Ranges changed since the last upload
+    pub fn dirty_ranges(&self) -> &[Range<usize>] {
+        &self.dirty
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut merged = range;
+        self.dirty.retain(|dirty| {
+            let touches = dirty.start <= merged.end && merged.start <= dirty.end;
+            if touches {
+                merged = merged.start.min(dirty.start)..merged.end.max(dirty.end);
+            }
+            !touches
+        });
+        let at = self
+            .dirty
+            .partition_point(|dirty| dirty.start < merged.start);
+        self.dirty.insert(at, merged);
+
+        if self.dirty.len() > MAX_DIRTY_RANGES {
+            let end = self.dirty[self.dirty.len() - 1].end;
+            self.dirty.truncate(1);
+            self.dirty[0].end = end;
+        }
+    }
+
+    pub fn buffer_id(&self) -> BufferId {
+        self.buffer.id()
     }
 
     pub fn bind(&self, ctx: &RenderingContext) {
-        ctx.bind_buffer(self.kind, &self.buffer);
+        ctx.bind_buffer(self.kind, self.buffer.id());
+    }
+
+    /// Uploads all the data, replacing whatever the graphics card had
+    pub fn write_to_graphics_card(&mut self, ctx: &RenderingContext) {
+        self.uploading(ctx, |storage| storage.upload_all(ctx));
+    }
+
+    /// Uploads the dirty ranges with `bufferSubData`. Data which outgrew
+    /// the capacity, or was rewritten as a whole, goes to freshly allocated
+    /// storage instead: the old one is orphaned, so the driver doesn't
+    /// have to wait for draws still reading from it
+    pub fn flush(&mut self, ctx: &RenderingContext) {
+        if self.dirty.is_empty() {
+            return;
+        }
+        self.uploading(ctx, |storage| storage.upload_dirty(ctx));
     }
 
-    fn get_typed_array(&self) -> Result<JsValue, Error> {
-        let memory_buffer = wasm_bindgen::memory()
-            .dyn_into::<WebAssembly::Memory>()?
-            .buffer();
+    /// Binding an element array buffer would replace the index buffer of
+    /// a bound vertex array, so those are uploaded with none bound
+    fn uploading(&mut self, ctx: &RenderingContext, upload: impl FnOnce(&mut Self)) {
         match self.kind {
-            BufferKind::ArrayBuffer => {
-                let start = self.data.as_ptr() as u32 / 4;
-                let end = start + self.data.len() as u32;
-                Ok(Float32Array::new(&memory_buffer)
-                    .subarray(start, end)
-                    .into())
-            }
-            BufferKind::ElementArrayBuffer => {
-                let start = self.data.as_ptr() as u32 / 2;
-                let end = start + self.data.len() as u32;
-                Ok(Uint16Array::new(&memory_buffer).subarray(start, end).into())
-            }
+            BufferKind::ElementArrayBuffer => ctx.without_vertex_array(|| upload(self)),
+            BufferKind::ArrayBuffer => upload(self),
         }
     }
 
-    pub fn write_to_graphics_card(&self, ctx: &RenderingContext) -> Result<(), Error> {
-        let typed_array = self.get_typed_array()?;
+    fn upload_all(&mut self, ctx: &RenderingContext) {
+        self.bind(ctx);
+        let buffer_type = get_buffer_type(self.kind);
+        ctx.buffer_data(buffer_type, as_bytes(&self.data), self.usage.gl_usage());
+        self.capacity = self.data.len();
+        self.dirty.clear();
+    }
 
+    fn upload_dirty(&mut self, ctx: &RenderingContext) {
+        let rewritten = self.dirty[0] == (0..self.data.len());
+        if self.data.len() > self.capacity {
+            let capacity = self.data.len().max(self.capacity * 2);
+            self.orphan(ctx, capacity);
+        } else if rewritten {
+            self.orphan(ctx, self.capacity);
+        } else {
+            self.bind(ctx);
+            let buffer_type = get_buffer_type(self.kind);
+            let size = std::mem::size_of::<T>();
+            for range in &self.dirty {
+                let bytes = as_bytes(&self.data[range.clone()]);
+                ctx.buffer_sub_data(buffer_type, (range.start * size) as i32, bytes);
+            }
+        }
+        self.dirty.clear();
+    }
+
+    fn orphan(&mut self, ctx: &RenderingContext, capacity: usize) {
+        if capacity == self.data.len() {
+            self.upload_all(ctx);
+            return;
+        }
         self.bind(ctx);
         let buffer_type = get_buffer_type(self.kind);
-        ctx.gl.buffer_data_with_array_buffer_view(
-            buffer_type,
-            &typed_array.into(),
-            WebGlRenderingContext::STATIC_DRAW,
+        let size = (capacity * std::mem::size_of::<T>()) as i32;
+        ctx.gl
+            .buffer_data_size(buffer_type, size, self.usage.gl_usage());
+        ctx.buffer_sub_data(buffer_type, 0, as_bytes(&self.data));
+        self.capacity = capacity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_backend::{Call, CallLog, RecordingBackend};
+
+    fn storage(data: Vec<f32>) -> (RenderingContext, CallLog, BufferStorage<f32>) {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let ctx = RenderingContext::new(Box::new(backend));
+        let mut storage =
+            BufferStorage::with_usage(&ctx, BufferKind::ArrayBuffer, data, BufferUsage::Dynamic)
+                .unwrap();
+        storage.write_to_graphics_card(&ctx);
+        calls.borrow_mut().clear();
+        (ctx, calls, storage)
+    }
+
+    fn uploads(calls: &CallLog) -> Vec<Call> {
+        let calls = calls.borrow();
+        let uploads = calls.iter().filter(|call| {
+            matches!(
+                call,
+                Call::BufferData { .. } | Call::BufferDataSize { .. } | Call::BufferSubData { .. }
+            )
+        });
+        uploads.cloned().collect()
+    }
+
+    #[test]
+    fn merges_dirty_ranges() {
+        let (_ctx, _calls, mut storage) = storage(vec![0.0; 20]);
+        storage.range_mut(2..4);
+        storage.range_mut(8..10);
+        storage.range_mut(4..5);
+        assert_eq!(storage.dirty_ranges(), [2..5, 8..10]);
+        storage.range_mut(3..9);
+        assert_eq!(storage.dirty_ranges().len(), 1);
+        assert_eq!(storage.dirty_ranges()[0], 2..10);
+
+        for start in (12..20).step_by(2) {
+            storage.range_mut(start..start + 1);
+        }
+        assert_eq!(storage.dirty_ranges().len(), 5);
+        storage.truncate(13);
+        assert_eq!(storage.dirty_ranges(), [2..10, 12..13]);
+    }
+
+    #[test]
+    fn uploads_only_dirty_ranges() {
+        let (ctx, calls, mut storage) = storage(vec![0.0; 8]);
+        storage.flush(&ctx);
+        assert!(uploads(&calls).is_empty());
+
+        storage.range_mut(1..2)[0] = 1.0;
+        storage.write(6, &[2.0, 3.0]);
+        storage.flush(&ctx);
+        let target = WebGlRenderingContext::ARRAY_BUFFER;
+        assert_eq!(
+            uploads(&calls),
+            [
+                Call::BufferSubData {
+                    target,
+                    offset: 4,
+                    data: 1.0f32.to_ne_bytes().to_vec()
+                },
+                Call::BufferSubData {
+                    target,
+                    offset: 24,
+                    data: as_bytes(&[2.0f32, 3.0]).to_vec()
+                },
+            ]
+        );
+        assert!(storage.dirty_ranges().is_empty());
+    }
+
+    #[test]
+    fn uploads_the_gap_when_writing_past_the_end() {
+        let (ctx, calls, mut storage) = storage(vec![0.0; 8]);
+        storage.truncate(2);
+        storage.write(4, &[1.0]);
+        assert_eq!(storage.data(), [0.0, 0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(storage.dirty_ranges().len(), 1);
+        assert_eq!(storage.dirty_ranges()[0], 2..5);
+
+        storage.flush(&ctx);
+        assert_eq!(
+            uploads(&calls),
+            [Call::BufferSubData {
+                target: WebGlRenderingContext::ARRAY_BUFFER,
+                offset: 8,
+                data: as_bytes(&[1.0f32; 3]).to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_the_bound_vertex_array_indices() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let ctx = RenderingContext::new(Box::new(backend));
+        let mut indices =
+            BufferStorage::new(&ctx, BufferKind::ElementArrayBuffer, vec![0u16; 6]).unwrap();
+        indices.write_to_graphics_card(&ctx);
+        let vertex_array = ctx.create_vertex_array().unwrap();
+        ctx.bind_vertex_array(Some(vertex_array.id()));
+        calls.borrow_mut().clear();
+
+        indices.write(2, &[1, 2]);
+        indices.flush(&ctx);
+        let calls = calls.borrow();
+        assert_eq!(calls.first(), Some(&Call::BindVertexArray(None)));
+        assert!(matches!(calls[1], Call::BindBuffer { .. }));
+        assert!(matches!(calls[2], Call::BufferSubData { .. }));
+        assert_eq!(
+            calls.last(),
+            Some(&Call::BindVertexArray(Some(vertex_array.id())))
+        );
+    }
+
+    #[test]
+    fn grows_by_orphaning() {
+        let (ctx, calls, mut storage) = storage(vec![0.0; 4]);
+        storage.extend_from_slice(&[1.0]);
+        storage.flush(&ctx);
+        assert_eq!(storage.capacity(), 8);
+
+        let usage = WebGlRenderingContext::DYNAMIC_DRAW;
+        let target = WebGlRenderingContext::ARRAY_BUFFER;
+        assert_eq!(
+            uploads(&calls),
+            [
+                Call::BufferDataSize {
+                    target,
+                    size: 32,
+                    usage
+                },
+                Call::BufferSubData {
+                    target,
+                    offset: 0,
+                    data: as_bytes(storage.data()).to_vec()
+                },
+            ]
         );
-        ctx.clear_array_buffer();
 
-        Ok(())
+        // fits into the capacity, only the new element is sent
+        calls.borrow_mut().clear();
+        storage.extend_from_slice(&[2.0]);
+        storage.flush(&ctx);
+        assert!(matches!(
+            uploads(&calls)[..],
+            [Call::BufferSubData { offset: 20, .. }]
+        ));
     }
 }
@@ -0,0 +1,123 @@
+use nalgebra::{Isometry3, Matrix4, Orthographic3, Perspective3, Point3, Vector3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective(Perspective3<f32>),
+    Orthographic(Orthographic3<f32>),
+}
+
+impl Projection {
+    pub fn to_homogeneous(&self) -> Matrix4<f32> {
+        match self {
+            Projection::Perspective(perspective) => perspective.to_homogeneous(),
+            Projection::Orthographic(orthographic) => orthographic.to_homogeneous(),
+        }
+    }
+
+    /// Orthographic projections keep their height and center,
+    /// only the width follows the aspect ratio
+    fn set_aspect(&mut self, aspect: f32) {
+        match self {
+            Projection::Perspective(perspective) => perspective.set_aspect(aspect),
+            Projection::Orthographic(orthographic) => {
+                let half_width = (orthographic.top() - orthographic.bottom()) * aspect / 2.0;
+                let center = (orthographic.left() + orthographic.right()) / 2.0;
+                orthographic.set_left_and_right(center - half_width, center + half_width);
+            }
+        }
+    }
+}
+
+/// Where the scene is looked at from. The view-projection matrix
+/// is recomputed whenever the camera changes, so reading it is free
+#[derive(Clone, Debug)]
+pub struct Camera {
+    projection: Projection,
+    eye: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    view: Isometry3<f32>,
+    view_projection: Matrix4<f32>,
+}
+
+impl Camera {
+    pub fn new(projection: Projection) -> Self {
+        let mut camera = Camera {
+            projection,
+            eye: Point3::new(0.0, 0.0, 1.0),
+            target: Point3::origin(),
+            up: Vector3::y(),
+            view: Isometry3::identity(),
+            view_projection: Matrix4::identity(),
+        };
+        camera.update();
+        camera
+    }
+
+    /// `fovy` is the vertical field of view, in radians
+    pub fn perspective(aspect: f32, fovy: f32, znear: f32, zfar: f32) -> Self {
+        Camera::new(Projection::Perspective(Perspective3::new(
+            aspect, fovy, znear, zfar,
+        )))
+    }
+
+    pub fn orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        znear: f32,
+        zfar: f32,
+    ) -> Self {
+        Camera::new(Projection::Orthographic(Orthographic3::new(
+            left, right, bottom, top, znear, zfar,
+        )))
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.update();
+    }
+
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.projection.set_aspect(aspect);
+        self.update();
+    }
+
+    pub fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>, up: Vector3<f32>) {
+        self.eye = eye;
+        self.target = target;
+        self.up = up;
+        self.update();
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.eye
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
+    /// Transforms world coordinates into camera coordinates
+    pub fn view(&self) -> &Isometry3<f32> {
+        &self.view
+    }
+
+    pub fn view_projection(&self) -> &Matrix4<f32> {
+        &self.view_projection
+    }
+
+    fn update(&mut self) {
+        self.view = Isometry3::look_at_rh(&self.eye, &self.target, &self.up);
+        self.view_projection = self.projection.to_homogeneous() * self.view.to_homogeneous();
+    }
+}
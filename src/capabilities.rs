@@ -0,0 +1,78 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlVersion {
+    WebGl1,
+    WebGl2,
+}
+
+impl fmt::Display for GlVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlVersion::WebGl1 => write!(f, "WebGL 1"),
+            GlVersion::WebGl2 => write!(f, "WebGL 2"),
+        }
+    }
+}
+
+/// Features which are core in WebGL2 and optional (or missing) in WebGL1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: GlVersion,
+    /// Core in WebGL2, `OES_vertex_array_object` in WebGL1
+    pub vertex_array_objects: bool,
+    /// Core in WebGL2, `ANGLE_instanced_arrays` in WebGL1
+    pub instancing: bool,
+    /// Core in WebGL2, `OES_element_index_uint` in WebGL1
+    pub element_index_uint: bool,
+    /// WebGL2 only
+    pub uniform_buffers: bool,
+    /// Core in WebGL2, `WEBGL_draw_buffers` in WebGL1
+    pub multiple_render_targets: bool,
+    /// WebGL2 only
+    pub texture_3d: bool,
+    /// Core in WebGL2, `OES_texture_float` in WebGL1
+    pub float_textures: bool,
+    /// Linear filtering of float textures, `OES_texture_float_linear` in both versions
+    pub float_linear: bool,
+    /// Core in WebGL2, `EXT_sRGB` in WebGL1
+    pub srgb_textures: bool,
+}
+
+impl Capabilities {
+    pub fn webgl2() -> Self {
+        Capabilities {
+            version: GlVersion::WebGl2,
+            vertex_array_objects: true,
+            instancing: true,
+            element_index_uint: true,
+            uniform_buffers: true,
+            multiple_render_targets: true,
+            texture_3d: true,
+            float_textures: true,
+            float_linear: false,
+            srgb_textures: true,
+        }
+    }
+
+    /// WebGL1 without any extensions
+    pub fn webgl1() -> Self {
+        Capabilities {
+            version: GlVersion::WebGl1,
+            vertex_array_objects: false,
+            instancing: false,
+            element_index_uint: false,
+            uniform_buffers: false,
+            multiple_render_targets: false,
+            texture_3d: false,
+            float_textures: false,
+            float_linear: false,
+            srgb_textures: false,
+        }
+    }
+
+    /// Shaders starting with `#version 300 es` need a WebGL2 context
+    pub fn glsl_es_300(&self) -> bool {
+        self.version == GlVersion::WebGl2
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 #[macro_export]
@@ -5,8 +6,15 @@ macro_rules! console_log {
     ($($t:tt)*) => ($crate::console::log(&format_args!($($t)*).to_string()))
 }
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
     pub fn log(s: &str);
 }
+
+/// There is no console outside of the browser, e.g. with the headless backend
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log(s: &str) {
+    eprintln!("{s}");
+}
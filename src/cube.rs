@@ -1,194 +1,214 @@
-use nalgebra::{Isometry3, Matrix4, Perspective3, Point3, Unit, UnitQuaternion, Vector3};
+use nalgebra::{UnitQuaternion, Vector3};
 use wasm_bindgen::JsValue;
-use web_sys::WebGlRenderingContext;
 
-use crate::buffer_storage::BufferStorage;
+use crate::buffer_storage::{BufferStorage, BufferUsage};
+use crate::error::Error;
 use crate::geometry::{get_colors, get_cube};
-use crate::program::{AttributeKind, ProgramAttribute, UniformKind};
+use crate::glsl_type;
+use crate::mesh::{InstanceAttribute, Mesh, NORMAL_ATTRIBUTE, POSITION_ATTRIBUTE};
+use crate::pod::Pod;
+use crate::program::Program;
 use crate::rendering_context::{BufferKind, RenderingContext};
 use crate::scene::Scene;
+use crate::scene_graph::{NodeId, SceneGraph, Transform};
+use crate::uniform::UniformValue;
+use crate::vertex_layout::{ComponentType, VertexLayout};
+
+/// Matrices of every instance, one after another, read by a per-instance
+/// matrix attribute. With instancing it is recorded in the mesh's vertex arrays
+struct InstanceMatrices {
+    attribute: &'static str,
+    /// 4 for mat4, 3 for mat3
+    rows: usize,
+    buffer: BufferStorage<f32>,
+}
 
-struct Position {
-    x: f32,
-    y: f32,
-    z: f32,
+/// Where an `InstanceMatrices` attribute sits in the program
+struct MatrixLocation {
+    location: u32,
+    columns: u32,
 }
 
-impl Position {
-    fn new(x: f32, y: f32, z: f32) -> Self {
-        Position { x, y, z }
+impl InstanceMatrices {
+    fn new(
+        ctx: &RenderingContext,
+        attribute: &'static str,
+        rows: usize,
+        count: usize,
+    ) -> Result<Self, Error> {
+        let data = vec![0.0; count * rows * rows];
+        let mut buffer =
+            BufferStorage::with_usage(ctx, BufferKind::ArrayBuffer, data, BufferUsage::Dynamic)?;
+        buffer.write_to_graphics_card(ctx);
+        Ok(InstanceMatrices {
+            attribute,
+            rows,
+            buffer,
+        })
     }
-}
 
-pub struct Cube {
-    colors: Vec<f32>,
-    indices_buffer: BufferStorage<u16>,
-    vertices_buffer: BufferStorage<f32>,
-    colors_buffer: BufferStorage<f32>,
-    projection: Perspective3<f32>,
-    model_view: Isometry3<f32>,
-    position: Position,
-}
+    fn matrix_size(&self) -> usize {
+        self.rows * self.rows
+    }
 
-impl Cube {
-    fn enable_vertex_attrib_array(&self, ctx: &RenderingContext, attribute: &ProgramAttribute) {
-        let options = &attribute.options;
-        ctx.gl.vertex_attrib_pointer_with_i32(
-            attribute.location as u32,
-            options.size,
-            options.data_type,
-            options.normalized,
-            options.stride,
-            options.offset,
-        );
-        ctx.gl.enable_vertex_attrib_array(attribute.location as u32);
+    fn locate(&self, program: &Program) -> Result<MatrixLocation, Error> {
+        let attribute = program.attribute(self.attribute).ok_or_else(|| {
+            Error::new(&format!("Cube shader has no {} attribute", self.attribute))
+        })?;
+        Ok(MatrixLocation {
+            location: attribute.location,
+            columns: glsl_type::attribute_slots(attribute.data_type) as u32,
+        })
     }
 
-    fn enable_attribute(&self, scene: &Scene, kind: AttributeKind) {
-        match kind {
-            AttributeKind::Vertex => {
-                self.vertices_buffer.bind(scene.get_ctx());
-                self.enable_vertex_attrib_array(
-                    scene.get_ctx(),
-                    &scene.get_program().attributes.vertices,
-                );
-            }
-            AttributeKind::Color => {
-                self.colors_buffer.bind(scene.get_ctx());
-                self.enable_vertex_attrib_array(
-                    scene.get_ctx(),
-                    &scene.get_program().attributes.colors,
-                );
-            }
+    /// Columns of the matrix of every instance, read with a divisor of 1
+    fn instance_attribute(&self) -> InstanceAttribute {
+        InstanceAttribute {
+            name: self.attribute.to_owned(),
+            buffer: self.buffer.buffer_id(),
+            components: self.rows as i32,
+            divisor: 1,
         }
     }
 
-    fn set_uniform(&self, scene: &Scene, kind: UniformKind) {
+    /// Passes the matrix of one instance as a constant attribute value
+    fn set_constant(&self, ctx: &RenderingContext, at: &MatrixLocation, instance: usize) {
+        let size = self.matrix_size();
+        let matrix = &self.buffer.data()[instance * size..(instance + 1) * size];
+        for (column, values) in matrix.chunks(self.rows).enumerate() {
+            let mut padded = [0.0; 4];
+            padded[..values.len()].copy_from_slice(values);
+            ctx.gl
+                .vertex_attrib4fv(at.location + column as u32, &padded);
+        }
+    }
+}
+
+enum UniformKind {
+    View,
+    Projection,
+    Material,
+}
+
+/// All the cubes on the scene share a single mesh,
+/// only their model matrices differ
+pub struct CubeField {
+    mesh: Mesh,
+    /// The shader derives the normal matrices from them
+    models: InstanceMatrices,
+    /// Parent of all the cubes, moves the whole field
+    root: NodeId,
+    instances: Vec<NodeId>,
+}
+
+impl CubeField {
+    fn set_uniform(&self, scene: &Scene, kind: UniformKind) -> Result<(), Error> {
+        let ctx = scene.get_ctx();
+        let program = scene.get_program();
+        let camera = scene.get_camera();
         match kind {
+            UniformKind::View => {
+                let value = UniformValue::from(camera.view().to_homogeneous());
+                program.set_uniform(ctx, "uViewMatrix", &value)
+            }
             UniformKind::Projection => {
-                let data = &self.projection.into_inner().data;
-                scene.get_ctx().gl.uniform_matrix4fv_with_f32_array(
-                    Some(&scene.get_program().uniform_locations.projection),
-                    false,
-                    data,
-                );
+                let value = UniformValue::from(camera.projection().to_homogeneous());
+                program.set_uniform(ctx, "uProjectionMatrix", &value)
             }
-            UniformKind::ModelView => {
-                let data = &self.model_view.to_homogeneous().data;
-                scene.get_ctx().gl.uniform_matrix4fv_with_f32_array(
-                    Some(&scene.get_program().uniform_locations.model_view),
-                    false,
-                    data,
-                );
+            UniformKind::Material => {
+                let specular = UniformValue::Vec3([0.5, 0.5, 0.5]);
+                program.set_uniform(ctx, "uSpecularColor", &specular)?;
+                program.set_uniform(ctx, "uShininess", &UniformValue::Float(32.0))
             }
         }
     }
 
-    pub fn set_initial_state(&mut self) {
-        let field_of_view = 45.0 * std::f32::consts::PI / 180.0; // in radians
-        let width = 640.0;
-        let height = 480.0;
-        let aspect = width / height;
-        let z_near = 0.1;
-        let z_far = 500.0;
-
-        self.projection = Perspective3::new(aspect, field_of_view, z_near, z_far);
-
-        // Our object is translated along the x axis.
-        let model = Isometry3::<f32>::new(Vector3::new(0.0, 0.0, 0.0), nalgebra::zero());
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
 
-        // Our camera looks toward the point (1.0, 0.0, 0.0).
-        // It is located at (0.0, 0.0, 1.0).
-        let eye = Point3::<f32>::new(0.0, 0.0, 450.0);
+    /// Cubes still in the graph
+    pub fn instance_count(&self) -> usize {
+        self.instances.len()
+    }
 
-        let Position { x, y, z } = self.position;
-        let target = Point3::<f32>::new(x, y, z);
-        let view = Isometry3::<f32>::look_at_rh(&eye, &target, &Vector3::y());
+    /// Spins every cube around its own center.
+    /// Cubes removed from the graph are forgotten
+    pub fn update_state(&mut self, graph: &mut SceneGraph, angle: f32) -> Result<(), Error> {
+        self.instances.retain(|id| graph.contains(*id));
 
-        // The combination of the model with the view is still an isometry.
-        self.model_view = view * model;
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), angle)
+            * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle);
+        for id in &self.instances {
+            graph.update_transform(*id, |transform| transform.rotation *= rotation)?;
+        }
+        Ok(())
     }
 
-    pub fn update_state(&mut self, angle: f32) {
-        let mut matrix = Isometry3::identity();
+    /// Copies the world matrices into the instance buffer,
+    /// and sends them to the graphics card when drawing with instancing
+    pub fn write_instances(&mut self, ctx: &RenderingContext, graph: &SceneGraph) {
+        self.instances.retain(|id| graph.contains(*id));
 
-        let axis = Unit::new_normalize(Vector3::new(0.0, 0.0, 1.0));
-        let rotation = UnitQuaternion::from_axis_angle(&axis, angle);
-        matrix.append_rotation_mut(&rotation);
-
-        let axis = Unit::new_normalize(Vector3::new(0.0, 1.0, 0.0));
-        let rotation = UnitQuaternion::from_axis_angle(&axis, angle);
-        matrix.append_rotation_mut(&rotation);
+        let models = self.models.buffer.data_mut().chunks_mut(16);
+        for (id, model) in self.instances.iter().zip(models) {
+            if let Some(world) = graph.world_matrix(*id) {
+                model.copy_from_slice(world.as_slice());
+            }
+        }
+        if ctx.capabilities().instancing {
+            self.models.buffer.flush(ctx);
+        }
+    }
 
-        self.model_view *= matrix;
+    /// One draw call for every cube, the matrices come
+    /// from the instance attribute of the bound mesh
+    fn draw_instanced(&self, ctx: &RenderingContext) {
+        self.mesh.draw_instanced(ctx, self.instances.len() as i32);
     }
 
-    fn draw_elements(&self, scene: &Scene) {
-        let vertex_count = 36;
-        let data_type = WebGlRenderingContext::UNSIGNED_SHORT;
-        let offset = 0;
-        scene.get_ctx().gl.draw_elements_with_i32(
-            WebGlRenderingContext::TRIANGLES,
-            vertex_count,
-            data_type,
-            offset,
-        );
+    /// Fallback when instancing is not available: the matrix is passed
+    /// as a constant attribute value, and every cube gets its own draw call
+    fn draw_one_by_one(&self, ctx: &RenderingContext, at: &MatrixLocation) {
+        for column in 0..at.columns {
+            ctx.disable_vertex_attrib_array(at.location + column);
+        }
+        for instance in 0..self.instances.len() {
+            self.models.set_constant(ctx, at, instance);
+            self.mesh.draw(ctx);
+        }
     }
 
-    pub fn draw(&self, scene: &Scene) {
-        self.enable_attribute(scene, AttributeKind::Vertex);
-        self.enable_attribute(scene, AttributeKind::Color);
+    pub fn draw(&self, scene: &Scene) -> Result<(), Error> {
+        let ctx = scene.get_ctx();
+        let program = scene.get_program();
 
-        self.indices_buffer.bind(scene.get_ctx());
         scene.use_program();
-
-        self.set_uniform(scene, UniformKind::Projection);
-        self.set_uniform(scene, UniformKind::ModelView);
-
-        self.draw_elements(scene);
-        scene.get_ctx().clear_array_buffer();
+        self.mesh.bind(ctx, program)?;
+
+        self.set_uniform(scene, UniformKind::View)?;
+        self.set_uniform(scene, UniformKind::Projection)?;
+        self.set_uniform(scene, UniformKind::Material)?;
+        let camera = scene.get_camera();
+        scene.get_lights().upload(ctx, program, camera.view())?;
+
+        let location = self.models.locate(program)?;
+        if ctx.capabilities().instancing {
+            self.draw_instanced(ctx);
+        } else {
+            self.draw_one_by_one(ctx, &location);
+        }
+        self.mesh.unbind(ctx, program);
+        Ok(())
     }
 }
 
-fn get_one_cube_geometry(ctx: &RenderingContext, position: Position) -> Result<Cube, JsValue> {
-    let geometry = get_cube();
-    let colors = get_colors();
-
-    let vertices_buffer =
-        BufferStorage::new(ctx, BufferKind::ArrayBuffer, geometry.vertices.clone())?;
-    vertices_buffer.write_to_graphics_card(ctx)?;
-
-    let colors_buffer = BufferStorage::new(ctx, BufferKind::ArrayBuffer, colors.clone())?;
-    colors_buffer.write_to_graphics_card(ctx)?;
-
-    let indices_buffer = BufferStorage::new(
-        ctx,
-        BufferKind::ElementArrayBuffer,
-        geometry.indices.clone(),
-    )?;
-    indices_buffer.write_to_graphics_card(ctx)?;
-
-    let projection = Perspective3::from_matrix_unchecked(Matrix4::zeros());
-    let model_view = Isometry3::identity();
-
-    Ok(Cube {
-        colors,
-        indices_buffer,
-        vertices_buffer,
-        colors_buffer,
-        projection,
-        model_view,
-        position,
-    })
-}
-
-fn make_position(x: i32, y: i32, z: i32) -> Position {
-    Position::new(6.0 * x as f32, 6.0 * y as f32, 6.0 * z as f32)
+fn make_position(x: i32, y: i32, z: i32) -> Vector3<f32> {
+    Vector3::new(6.0 * x as f32, 6.0 * y as f32, 6.0 * z as f32)
 }
 
-pub fn get_geometries(ctx: &RenderingContext) -> Result<Vec<Cube>, JsValue> {
+fn get_positions(field_size: i32) -> Vec<Vector3<f32>> {
     let mut positions = vec![];
-    let field_size = 30;
     let z = 0;
     for i in 0..field_size {
         for j in 0..field_size {
@@ -211,11 +231,61 @@ pub fn get_geometries(ctx: &RenderingContext) -> Result<Vec<Cube>, JsValue> {
             }
         }
     }
+    positions
+}
+
+/// Position, normal and color of a cube vertex, interleaved in one buffer
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CubeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    color: [u8; 4],
+}
+
+unsafe impl Pod for CubeVertex {}
+
+pub fn get_cube_mesh(ctx: &RenderingContext) -> Result<Mesh, Error> {
+    let cube = get_cube();
+    let colors = get_colors();
+    let vertices: Vec<_> = (0..cube.vertex_count())
+        .map(|vertex| CubeVertex {
+            position: [0, 1, 2].map(|axis| cube.vertices[vertex * 3 + axis]),
+            normal: [0, 1, 2].map(|axis| cube.normals[vertex * 3 + axis]),
+            color: [0, 1, 2, 3].map(|channel| (colors[vertex * 4 + channel] * 255.0) as u8),
+        })
+        .collect();
+    let layout = VertexLayout::new()
+        .floats(POSITION_ATTRIBUTE, 3)
+        .floats(NORMAL_ATTRIBUTE, 3)
+        .packed("aVertexColor", 4, ComponentType::U8, true);
+    Mesh::from_vertices(ctx, cube.mode, layout, &vertices, &cube.indices)
+}
 
-    let mut results = vec![];
-    for Position { x, y, z } in positions.into_iter() {
-        results.push(get_one_cube_geometry(ctx, Position::new(x, y, z))?);
+/// Adds the cubes to the graph, under a common root node
+pub fn get_cube_field(
+    ctx: &RenderingContext,
+    graph: &mut SceneGraph,
+) -> Result<CubeField, JsValue> {
+    let mut mesh = get_cube_mesh(ctx)?;
+
+    let root = graph.add(Transform::identity(), None)?;
+    let instances = get_positions(30)
+        .into_iter()
+        .map(|position| graph.add(Transform::from_translation(position), Some(root)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let models = InstanceMatrices::new(ctx, "aModelMatrix", 4, instances.len())?;
+    if ctx.capabilities().instancing {
+        mesh.add_instance_attribute(ctx, models.instance_attribute())?;
     }
 
-    Ok(results)
+    let mut cubes = CubeField {
+        mesh,
+        models,
+        root,
+        instances,
+    };
+    cubes.write_instances(ctx, graph);
+    Ok(cubes)
 }
@@ -1,6 +1,11 @@
-use js_sys::Error;
 use wasm_bindgen::JsCast;
-use web_sys::{Document, HtmlCanvasElement, HtmlElement, WebGlRenderingContext, Window};
+use web_sys::{
+    Document, Element, HtmlCanvasElement, HtmlElement, WebGl2RenderingContext,
+    WebGlContextAttributes, WebGlRenderingContext, Window,
+};
+
+use crate::error::Error;
+use crate::webgl_backend::GlContext;
 
 pub fn get_window() -> Result<Window, Error> {
     web_sys::window().ok_or_else(|| Error::new("Can't get the window"))
@@ -18,6 +23,12 @@ pub fn get_body(document: &Document) -> Result<HtmlElement, Error> {
         .ok_or_else(|| Error::new("Can't get the body"))
 }
 
+pub fn get_element_by_id(document: &Document, id: &str) -> Result<Element, Error> {
+    document
+        .get_element_by_id(id)
+        .ok_or_else(|| Error::new(&format!("Can't find element #{id}")))
+}
+
 pub fn create_canvas(document: &Document) -> Result<HtmlCanvasElement, Error> {
     document
         .create_element("canvas")?
@@ -25,10 +36,21 @@ pub fn create_canvas(document: &Document) -> Result<HtmlCanvasElement, Error> {
         .map_err(|_| Error::new("Can't cast created element to HtmlCanvasElement"))
 }
 
-pub fn get_context(canvas: &HtmlCanvasElement) -> Result<WebGlRenderingContext, Error> {
+/// Tries WebGL2 first and falls back to WebGL1
+pub fn get_context(
+    canvas: &HtmlCanvasElement,
+    attributes: &WebGlContextAttributes,
+) -> Result<GlContext, Error> {
+    if let Some(context) = canvas.get_context_with_context_options("webgl2", attributes)? {
+        return context
+            .dyn_into::<WebGl2RenderingContext>()
+            .map(GlContext::WebGl2)
+            .map_err(|_| Error::new("Can't cast rendering context to WebGl2RenderingContext"));
+    }
     canvas
-        .get_context("webgl")?
+        .get_context_with_context_options("webgl", attributes)?
         .ok_or_else(|| Error::new("Can't get rendering context"))?
         .dyn_into::<WebGlRenderingContext>()
+        .map(GlContext::WebGl1)
         .map_err(|_| Error::new("Can't cast rendering context to WebGlRenderingContext"))
 }
@@ -0,0 +1,48 @@
+use std::fmt;
+
+use wasm_bindgen::{JsCast, JsValue};
+
+/// What the engine returns when something fails. Unlike `js_sys::Error`
+/// it can be created and inspected without a browser, it only turns
+/// into a JS `Error` when it is handed to JavaScript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    pub fn new(message: &str) -> Self {
+        Error {
+            message: message.to_owned(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> Self {
+        js_sys::Error::new(&error.message).into()
+    }
+}
+
+/// For failed browser calls, keeps the message of a thrown `Error`
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        let message = match value.dyn_ref::<js_sys::Error>() {
+            Some(error) => error.message().into(),
+            None => value.as_string().unwrap_or_else(|| format!("{value:?}")),
+        };
+        Error { message }
+    }
+}
@@ -0,0 +1,38 @@
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use web_sys::{Event, EventTarget};
+
+/// A DOM event listener which is removed when dropped
+pub struct EventListener {
+    target: EventTarget,
+    event_type: &'static str,
+    callback: Closure<dyn FnMut(Event)>,
+}
+
+impl EventListener {
+    pub fn new(
+        target: &EventTarget,
+        event_type: &'static str,
+        callback: impl FnMut(Event) + 'static,
+    ) -> Result<Self, JsValue> {
+        let callback = Closure::wrap(Box::new(callback) as Box<dyn FnMut(Event)>);
+        target.add_event_listener_with_callback(event_type, callback.as_ref().unchecked_ref())?;
+        Ok(EventListener {
+            target: target.clone(),
+            event_type,
+            callback,
+        })
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        let callback = self.callback.as_ref().unchecked_ref();
+        if let Err(error) = self
+            .target
+            .remove_event_listener_with_callback(self.event_type, callback)
+        {
+            console_log!("Can't remove {} listener: {:?}", self.event_type, error);
+        }
+    }
+}
@@ -1,14 +1,168 @@
+use nalgebra::Vector3;
+use web_sys::WebGlRenderingContext;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveMode {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+}
+
+impl PrimitiveMode {
+    pub fn gl_mode(self) -> u32 {
+        match self {
+            PrimitiveMode::Points => WebGlRenderingContext::POINTS,
+            PrimitiveMode::Lines => WebGlRenderingContext::LINES,
+            PrimitiveMode::LineStrip => WebGlRenderingContext::LINE_STRIP,
+            PrimitiveMode::LineLoop => WebGlRenderingContext::LINE_LOOP,
+            PrimitiveMode::Triangles => WebGlRenderingContext::TRIANGLES,
+            PrimitiveMode::TriangleStrip => WebGlRenderingContext::TRIANGLE_STRIP,
+            PrimitiveMode::TriangleFan => WebGlRenderingContext::TRIANGLE_FAN,
+        }
+    }
+
+    /// Indices per primitive in lists. Strips, loops and fans share
+    /// vertices between primitives, so they have no fixed size
+    pub fn primitive_size(self) -> Option<usize> {
+        match self {
+            PrimitiveMode::Points => Some(1),
+            PrimitiveMode::Lines => Some(2),
+            PrimitiveMode::Triangles => Some(3),
+            _ => None,
+        }
+    }
+}
+
+/// Vertex positions, three floats each, and optionally indices into them.
+/// Without indices the vertices are drawn in order
+#[derive(Clone)]
 pub struct Geometry {
-    pub indices: Vec<u16>,
+    pub mode: PrimitiveMode,
+    /// Stored as `u32`, the mesh uploads them with the narrowest type that fits
+    pub indices: Vec<u32>,
     pub vertices: Vec<f32>,
+    /// Unit normal of every vertex, three floats each. Empty when unlit
+    pub normals: Vec<f32>,
+    /// Texture coordinates of every vertex, two floats each.
+    /// Empty when untextured
+    pub uvs: Vec<f32>,
+    /// Tangent of every vertex along the U texture direction, four floats each.
+    /// `w` is 1 or -1, the bitangent is `cross(normal, tangent) * w`.
+    /// Empty when not needed for normal mapping
+    pub tangents: Vec<f32>,
 }
 
 impl Geometry {
-    pub fn new(indices: Vec<u16>, vertices: Vec<f32>) -> Self {
-        Geometry { indices, vertices }
+    pub fn new(indices: Vec<u32>, vertices: Vec<f32>) -> Self {
+        Geometry {
+            mode: PrimitiveMode::Triangles,
+            indices,
+            vertices,
+            normals: vec![],
+            uvs: vec![],
+            tangents: vec![],
+        }
+    }
+
+    pub fn with_mode(mut self, mode: PrimitiveMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_normals(mut self, normals: Vec<f32>) -> Self {
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<f32>) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Vec<f32>) -> Self {
+        self.tangents = tangents;
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    /// Fills `tangents` from the positions, normals and UVs of a triangle list.
+    /// Vertices whose UVs don't vary get any tangent perpendicular to the normal
+    pub fn compute_tangents(&mut self) {
+        let count = self.vertex_count();
+        if self.mode != PrimitiveMode::Triangles
+            || self.normals.len() != count * 3
+            || self.uvs.len() != count * 2
+        {
+            return;
+        }
+        let position = |index: usize| Vector3::from_column_slice(&self.vertices[index * 3..][..3]);
+        let uv = |index: usize| [self.uvs[index * 2], self.uvs[index * 2 + 1]];
+
+        let mut u_directions = vec![Vector3::zeros(); count];
+        let mut v_directions = vec![Vector3::zeros(); count];
+        let triangles: Vec<[usize; 3]> = match self.indices.is_empty() {
+            true => (0..count / 3)
+                .map(|i| [i * 3, i * 3 + 1, i * 3 + 2])
+                .collect(),
+            false => self
+                .indices
+                .chunks_exact(3)
+                .map(|triangle| [0, 1, 2].map(|corner| triangle[corner] as usize))
+                .collect(),
+        };
+        for [a, b, c] in triangles {
+            let edge1 = position(b) - position(a);
+            let edge2 = position(c) - position(a);
+            let [du1, dv1] = [uv(b)[0] - uv(a)[0], uv(b)[1] - uv(a)[1]];
+            let [du2, dv2] = [uv(c)[0] - uv(a)[0], uv(c)[1] - uv(a)[1]];
+            let determinant = du1 * dv2 - du2 * dv1;
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+            let u_direction = (edge1 * dv2 - edge2 * dv1) / determinant;
+            let v_direction = (edge2 * du1 - edge1 * du2) / determinant;
+            for vertex in [a, b, c] {
+                u_directions[vertex] += u_direction;
+                v_directions[vertex] += v_direction;
+            }
+        }
+
+        let mut tangents = Vec::with_capacity(count * 4);
+        for vertex in 0..count {
+            let normal = Vector3::from_column_slice(&self.normals[vertex * 3..][..3]);
+            let u_direction = u_directions[vertex];
+            // Gram-Schmidt, keeping only the part perpendicular to the normal
+            let tangent = (u_direction - normal * normal.dot(&u_direction))
+                .try_normalize(1.0e-6)
+                .unwrap_or_else(|| any_perpendicular(&normal));
+            let handedness = match normal.cross(&tangent).dot(&v_directions[vertex]) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            tangents.extend([tangent.x, tangent.y, tangent.z, handedness]);
+        }
+        self.tangents = tangents;
     }
 }
 
+fn any_perpendicular(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = match normal.x.abs() < 0.9 {
+        true => Vector3::x(),
+        false => Vector3::y(),
+    };
+    normal
+        .cross(&axis)
+        .try_normalize(1.0e-6)
+        .unwrap_or_else(Vector3::x)
+}
+
 pub fn get_cube() -> Geometry {
     #[rustfmt::skip]
     let indices = [
@@ -57,7 +211,37 @@ pub fn get_cube() -> Geometry {
         -1.0,  1.0,  1.0,
         -1.0,  1.0, -1.0,
     ];
+    #[rustfmt::skip]
+    let face_normals = [
+        [0.0,  0.0,  1.0],     // Front face
+        [0.0,  0.0, -1.0],     // Back face
+        [0.0,  1.0,  0.0],     // Top face
+        [0.0, -1.0,  0.0],     // Bottom face
+        [1.0,  0.0,  0.0],     // Right face
+        [-1.0, 0.0,  0.0],     // Left face
+    ];
+
+    let mut normals = vec![];
+    for normal in face_normals.iter() {
+        // Faces are flat, all four vertices share the normal
+        for _ in 0..4 {
+            normals.extend(normal);
+        }
+    }
+
+    // Every face shows the whole texture
+    #[rustfmt::skip]
+    let face_uvs = [
+        0.0, 0.0,
+        1.0, 0.0,
+        1.0, 1.0,
+        0.0, 1.0,
+    ];
+    let uvs = face_uvs.repeat(6);
+
     Geometry::new(indices.to_vec(), vertices.to_vec())
+        .with_normals(normals)
+        .with_uvs(uvs)
 }
 
 pub fn get_colors() -> Vec<f32> {
@@ -0,0 +1,238 @@
+#[cfg(debug_assertions)]
+use std::cell::RefCell;
+#[cfg(debug_assertions)]
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::backend::{Backend, BufferId, ProgramId, ShaderId, TextureId, VertexArrayId};
+use crate::gl_state::GlState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GlObjectId {
+    Buffer(BufferId),
+    Shader(ShaderId),
+    Program(ProgramId),
+    Texture(TextureId),
+    VertexArray(VertexArrayId),
+}
+
+impl fmt::Display for GlObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlObjectId::Buffer(BufferId(id)) => write!(f, "buffer #{id}"),
+            GlObjectId::Shader(ShaderId(id)) => write!(f, "shader #{id}"),
+            GlObjectId::Program(ProgramId(id)) => write!(f, "program #{id}"),
+            GlObjectId::Texture(TextureId(id)) => write!(f, "texture #{id}"),
+            GlObjectId::VertexArray(VertexArrayId(id)) => write!(f, "vertex array #{id}"),
+        }
+    }
+}
+
+impl From<BufferId> for GlObjectId {
+    fn from(id: BufferId) -> Self {
+        GlObjectId::Buffer(id)
+    }
+}
+
+impl From<ShaderId> for GlObjectId {
+    fn from(id: ShaderId) -> Self {
+        GlObjectId::Shader(id)
+    }
+}
+
+impl From<ProgramId> for GlObjectId {
+    fn from(id: ProgramId) -> Self {
+        GlObjectId::Program(id)
+    }
+}
+
+impl From<TextureId> for GlObjectId {
+    fn from(id: TextureId) -> Self {
+        GlObjectId::Texture(id)
+    }
+}
+
+impl From<VertexArrayId> for GlObjectId {
+    fn from(id: VertexArrayId) -> Self {
+        GlObjectId::VertexArray(id)
+    }
+}
+
+/// Deletes GL objects on behalf of their owners, clearing the bindings
+/// the state cache has of them, and in debug builds keeps track
+/// of the ones still alive
+pub struct ObjectTracker {
+    gl: Rc<dyn Backend>,
+    state: Rc<GlState>,
+    #[cfg(debug_assertions)]
+    alive: RefCell<BTreeSet<GlObjectId>>,
+}
+
+impl ObjectTracker {
+    pub fn new(gl: Rc<dyn Backend>, state: Rc<GlState>) -> Self {
+        ObjectTracker {
+            gl,
+            state,
+            #[cfg(debug_assertions)]
+            alive: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    fn track(&self, _id: GlObjectId) {
+        #[cfg(debug_assertions)]
+        self.alive.borrow_mut().insert(_id);
+    }
+
+    fn delete(&self, id: GlObjectId) {
+        match id {
+            GlObjectId::Buffer(buffer) => {
+                self.gl.delete_buffer(buffer);
+                self.state.forget_buffer(buffer);
+            }
+            GlObjectId::Shader(shader) => self.gl.delete_shader(shader),
+            GlObjectId::Program(program) => {
+                self.gl.delete_program(program);
+                self.state.forget_program(program);
+            }
+            GlObjectId::Texture(texture) => {
+                self.gl.delete_texture(texture);
+                self.state.forget_texture(texture);
+            }
+            GlObjectId::VertexArray(vertex_array) => {
+                self.gl.delete_vertex_array(vertex_array);
+                self.state.forget_vertex_array(vertex_array);
+            }
+        }
+        #[cfg(debug_assertions)]
+        self.alive.borrow_mut().remove(&id);
+    }
+
+    /// Objects which have been created but not dropped yet.
+    /// Always empty in release builds
+    pub fn alive(&self) -> Vec<GlObjectId> {
+        #[cfg(debug_assertions)]
+        return self.alive.borrow().iter().copied().collect();
+        #[cfg(not(debug_assertions))]
+        return vec![];
+    }
+}
+
+/// Owns a GL object and deletes it when dropped
+pub struct Owned<Id: Copy + Into<GlObjectId>> {
+    id: Id,
+    tracker: Rc<ObjectTracker>,
+}
+
+impl<Id: Copy + Into<GlObjectId>> Owned<Id> {
+    pub fn new(tracker: &Rc<ObjectTracker>, id: Id) -> Self {
+        tracker.track(id.into());
+        Owned {
+            id,
+            tracker: Rc::clone(tracker),
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+impl<Id: Copy + Into<GlObjectId>> Drop for Owned<Id> {
+    fn drop(&mut self) {
+        self.tracker.delete(self.id.into());
+    }
+}
+
+pub type OwnedBuffer = Owned<BufferId>;
+pub type OwnedShader = Owned<ShaderId>;
+pub type OwnedProgram = Owned<ProgramId>;
+pub type OwnedTexture = Owned<TextureId>;
+pub type OwnedVertexArray = Owned<VertexArrayId>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_backend::{Call, RecordingBackend};
+    use crate::rendering_context::RenderingContext;
+
+    const TEXTURE_2D: u32 = 0x0DE1;
+
+    #[test]
+    fn deletes_when_dropped() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let tracker = Rc::new(ObjectTracker::new(
+            Rc::new(backend),
+            Rc::new(GlState::new()),
+        ));
+
+        let buffer = OwnedBuffer::new(&tracker, BufferId(1));
+        let texture = OwnedTexture::new(&tracker, TextureId(2));
+        if cfg!(debug_assertions) {
+            assert_eq!(
+                tracker.alive(),
+                [
+                    GlObjectId::Buffer(BufferId(1)),
+                    GlObjectId::Texture(TextureId(2))
+                ]
+            );
+        }
+
+        drop(buffer);
+        assert_eq!(*calls.borrow(), [Call::DeleteBuffer(BufferId(1))]);
+        if cfg!(debug_assertions) {
+            assert_eq!(tracker.alive(), [GlObjectId::Texture(TextureId(2))]);
+        }
+        drop(texture);
+        assert!(calls.borrow().contains(&Call::DeleteTexture(TextureId(2))));
+        assert!(tracker.alive().is_empty());
+    }
+
+    #[test]
+    fn never_binds_deleted_textures() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let ctx = RenderingContext::new(Box::new(backend));
+        let dropped = ctx.create_texture().unwrap();
+        let deleted = dropped.id();
+        ctx.active_texture(0);
+        ctx.bind_texture(TEXTURE_2D, Some(deleted));
+        drop(dropped);
+
+        let texture = ctx.create_texture().unwrap();
+        ctx.with_texture(TEXTURE_2D, texture.id(), || {});
+        ctx.bind_texture(TEXTURE_2D, None);
+
+        let calls = calls.borrow();
+        let deleted_at = calls
+            .iter()
+            .position(|call| *call == Call::DeleteTexture(deleted))
+            .unwrap();
+        assert!(!calls[deleted_at..].contains(&Call::BindTexture {
+            target: TEXTURE_2D,
+            texture: Some(deleted),
+        }));
+        // the unit is restored to empty, so unbinding again is skipped
+        assert_eq!(
+            calls.last(),
+            Some(&Call::BindTexture {
+                target: TEXTURE_2D,
+                texture: None,
+            })
+        );
+    }
+
+    #[test]
+    fn linked_program_keeps_no_shaders() {
+        let ctx = RenderingContext::new(Box::new(RecordingBackend::new()));
+        let program = ctx
+            .create_program("void main() {}", "void main() {}")
+            .unwrap();
+        if cfg!(debug_assertions) {
+            assert_eq!(ctx.alive_objects(), [GlObjectId::Program(program.id())]);
+        }
+        drop(program);
+        assert!(ctx.alive_objects().is_empty());
+    }
+}
@@ -0,0 +1,401 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::backend::{BufferId, ProgramId, TextureId, VertexArrayId};
+use crate::rendering_context::BufferKind;
+
+/// State calls of one kind, see `StateCacheStats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateCalls {
+    pub programs: u32,
+    pub buffers: u32,
+    pub vertex_arrays: u32,
+    /// Attribute arrays enabled or disabled, divisors and pointers
+    pub attributes: u32,
+    /// Active unit changes and texture binds
+    pub textures: u32,
+    /// `enable` and `disable`
+    pub capabilities: u32,
+    /// Depth, blend and cull settings, the viewport and clear values
+    pub parameters: u32,
+}
+
+impl StateCalls {
+    pub fn total(&self) -> u32 {
+        self.programs
+            + self.buffers
+            + self.vertex_arrays
+            + self.attributes
+            + self.textures
+            + self.capabilities
+            + self.parameters
+    }
+
+    fn get_mut(&mut self, kind: StateKind) -> &mut u32 {
+        match kind {
+            StateKind::Program => &mut self.programs,
+            StateKind::Buffer => &mut self.buffers,
+            StateKind::VertexArray => &mut self.vertex_arrays,
+            StateKind::Attribute => &mut self.attributes,
+            StateKind::Texture => &mut self.textures,
+            StateKind::Capability => &mut self.capabilities,
+            StateKind::Parameter => &mut self.parameters,
+        }
+    }
+}
+
+/// State calls passed on to the backend, and those skipped
+/// because they would not have changed anything
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateCacheStats {
+    pub issued: StateCalls,
+    pub skipped: StateCalls,
+}
+
+#[derive(Clone, Copy)]
+enum StateKind {
+    Program,
+    Buffer,
+    VertexArray,
+    Attribute,
+    Texture,
+    Capability,
+    Parameter,
+}
+
+/// Shadow copy of the GL state set through `RenderingContext`.
+/// Every setter records the new value and returns whether it differs
+/// from the last one, nothing is known until first set
+#[derive(Default)]
+pub struct GlState {
+    program: Cell<Option<Option<ProgramId>>>,
+    array_buffer: Cell<Option<Option<BufferId>>>,
+    /// Part of the bound vertex array, forgotten when that changes
+    element_buffer: Cell<Option<Option<BufferId>>>,
+    vertex_array: Cell<Option<Option<VertexArrayId>>>,
+    /// Whether the array of every location is enabled, part of the bound vertex array
+    attribute_arrays: RefCell<HashMap<u32, bool>>,
+    /// By location, part of the bound vertex array
+    attribute_divisors: RefCell<HashMap<u32, u32>>,
+    /// Counting from 0, not the `TEXTURE0` enum
+    active_texture: Cell<Option<u32>>,
+    /// By unit and target
+    textures: RefCell<HashMap<(u32, u32), Option<TextureId>>>,
+    capabilities: RefCell<HashMap<u32, bool>>,
+    depth_func: Cell<Option<u32>>,
+    depth_mask: Cell<Option<bool>>,
+    blend_func: Cell<Option<(u32, u32)>>,
+    cull_face: Cell<Option<u32>>,
+    viewport: Cell<Option<[i32; 4]>>,
+    clear_color: Cell<Option<[f32; 4]>>,
+    clear_depth: Cell<Option<f32>>,
+    stats: Cell<StateCacheStats>,
+}
+
+impl GlState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> StateCacheStats {
+        self.stats.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.set(StateCacheStats::default());
+    }
+
+    /// Forgets every value, for when the GL state changed behind the context's back
+    pub fn forget(&self) {
+        self.program.set(None);
+        self.array_buffer.set(None);
+        self.element_buffer.set(None);
+        self.vertex_array.set(None);
+        self.forget_attributes();
+        self.active_texture.set(None);
+        self.textures.borrow_mut().clear();
+        self.capabilities.borrow_mut().clear();
+        self.depth_func.set(None);
+        self.depth_mask.set(None);
+        self.blend_func.set(None);
+        self.cull_face.set(None);
+        self.viewport.set(None);
+        self.clear_color.set(None);
+        self.clear_depth.set(None);
+    }
+
+    pub fn set_program(&self, program: Option<ProgramId>) -> bool {
+        self.update(&self.program, program, StateKind::Program)
+    }
+
+    pub fn set_buffer(&self, kind: BufferKind, buffer: Option<BufferId>) -> bool {
+        let cached = match kind {
+            BufferKind::ArrayBuffer => &self.array_buffer,
+            BufferKind::ElementArrayBuffer => &self.element_buffer,
+        };
+        self.update(cached, buffer, StateKind::Buffer)
+    }
+
+    /// `None` while unknown
+    pub fn vertex_array(&self) -> Option<Option<VertexArrayId>> {
+        self.vertex_array.get()
+    }
+
+    pub fn set_vertex_array(&self, vertex_array: Option<VertexArrayId>) -> bool {
+        let changed = self.update(&self.vertex_array, vertex_array, StateKind::VertexArray);
+        if changed {
+            self.element_buffer.set(None);
+            self.forget_attributes();
+        }
+        changed
+    }
+
+    pub fn set_attribute_array(&self, location: u32, enabled: bool) -> bool {
+        let previous = self.attribute_arrays.borrow_mut().insert(location, enabled);
+        self.count(StateKind::Attribute, previous != Some(enabled))
+    }
+
+    pub fn set_attribute_divisor(&self, location: u32, divisor: u32) -> bool {
+        let previous = self
+            .attribute_divisors
+            .borrow_mut()
+            .insert(location, divisor);
+        self.count(StateKind::Attribute, previous != Some(divisor))
+    }
+
+    /// Pointers aren't cached, they are only counted as issued
+    pub fn count_attribute_pointer(&self) {
+        self.count(StateKind::Attribute, true);
+    }
+
+    /// GL unbinds a deleted buffer from the current bindings
+    pub fn forget_buffer(&self, buffer: BufferId) {
+        for cached in [&self.array_buffer, &self.element_buffer] {
+            if cached.get() == Some(Some(buffer)) {
+                cached.set(Some(None));
+            }
+        }
+    }
+
+    /// GL unbinds a deleted texture from every unit
+    pub fn forget_texture(&self, texture: TextureId) {
+        for bound in self.textures.borrow_mut().values_mut() {
+            if *bound == Some(texture) {
+                *bound = None;
+            }
+        }
+    }
+
+    /// Deleting the bound vertex array goes back to the default one,
+    /// whose element buffer and attributes aren't known
+    pub fn forget_vertex_array(&self, vertex_array: VertexArrayId) {
+        if self.vertex_array.get() == Some(Some(vertex_array)) {
+            self.vertex_array.set(Some(None));
+            self.element_buffer.set(None);
+            self.forget_attributes();
+        }
+    }
+
+    /// A deleted program stays in use until another one is,
+    /// the next `set_program` is passed on whatever it is
+    pub fn forget_program(&self, program: ProgramId) {
+        if self.program.get() == Some(Some(program)) {
+            self.program.set(None);
+        }
+    }
+
+    fn forget_attributes(&self) {
+        self.attribute_arrays.borrow_mut().clear();
+        self.attribute_divisors.borrow_mut().clear();
+    }
+
+    pub fn set_active_texture(&self, unit: u32) -> bool {
+        self.update(&self.active_texture, unit, StateKind::Texture)
+    }
+
+    /// Binds to the active unit, always passed on while that is unknown
+    pub fn set_texture(&self, target: u32, texture: Option<TextureId>) -> bool {
+        let Some(unit) = self.active_texture.get() else {
+            self.count(StateKind::Texture, true);
+            return true;
+        };
+        let previous = self.textures.borrow_mut().insert((unit, target), texture);
+        self.count(StateKind::Texture, previous != Some(texture))
+    }
+
+    /// Texture bound to `target` on the active unit, `None` while either is unknown
+    pub fn texture(&self, target: u32) -> Option<Option<TextureId>> {
+        let unit = self.active_texture.get()?;
+        self.textures.borrow().get(&(unit, target)).copied()
+    }
+
+    pub fn set_capability(&self, cap: u32, enabled: bool) -> bool {
+        let previous = self.capabilities.borrow_mut().insert(cap, enabled);
+        self.count(StateKind::Capability, previous != Some(enabled))
+    }
+
+    pub fn set_depth_func(&self, func: u32) -> bool {
+        self.update(&self.depth_func, func, StateKind::Parameter)
+    }
+
+    pub fn set_depth_mask(&self, flag: bool) -> bool {
+        self.update(&self.depth_mask, flag, StateKind::Parameter)
+    }
+
+    pub fn set_blend_func(&self, source: u32, destination: u32) -> bool {
+        self.update(
+            &self.blend_func,
+            (source, destination),
+            StateKind::Parameter,
+        )
+    }
+
+    pub fn set_cull_face(&self, mode: u32) -> bool {
+        self.update(&self.cull_face, mode, StateKind::Parameter)
+    }
+
+    pub fn set_viewport(&self, viewport: [i32; 4]) -> bool {
+        self.update(&self.viewport, viewport, StateKind::Parameter)
+    }
+
+    pub fn set_clear_color(&self, color: [f32; 4]) -> bool {
+        self.update(&self.clear_color, color, StateKind::Parameter)
+    }
+
+    pub fn set_clear_depth(&self, depth: f32) -> bool {
+        self.update(&self.clear_depth, depth, StateKind::Parameter)
+    }
+
+    fn update<T: Copy + PartialEq>(
+        &self,
+        cached: &Cell<Option<T>>,
+        value: T,
+        kind: StateKind,
+    ) -> bool {
+        let changed = cached.replace(Some(value)) != Some(value);
+        self.count(kind, changed)
+    }
+
+    fn count(&self, kind: StateKind, changed: bool) -> bool {
+        let mut stats = self.stats.get();
+        let calls = match changed {
+            true => &mut stats.issued,
+            false => &mut stats.skipped,
+        };
+        *calls.get_mut(kind) += 1;
+        self.stats.set(stats);
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH_TEST: u32 = 0x0B71;
+    const TEXTURE_2D: u32 = 0x0DE1;
+
+    #[test]
+    fn skips_unchanged_values() {
+        let state = GlState::new();
+        assert!(state.set_program(Some(ProgramId(1))));
+        assert!(!state.set_program(Some(ProgramId(1))));
+        assert!(state.set_program(None));
+
+        assert!(state.set_capability(DEPTH_TEST, true));
+        assert!(!state.set_capability(DEPTH_TEST, true));
+        assert!(state.set_capability(DEPTH_TEST, false));
+
+        assert!(state.set_viewport([0, 0, 640, 480]));
+        assert!(!state.set_viewport([0, 0, 640, 480]));
+
+        let stats = state.stats();
+        assert_eq!(stats.issued.programs, 2);
+        assert_eq!(stats.skipped.programs, 1);
+        assert_eq!(stats.skipped.total(), 3);
+        assert_eq!(stats.issued.total(), 5);
+
+        state.forget();
+        assert!(state.set_program(None));
+        state.reset_stats();
+        assert_eq!(state.stats(), StateCacheStats::default());
+    }
+
+    #[test]
+    fn tracks_textures_per_unit() {
+        let state = GlState::new();
+        // unknown unit, nothing can be skipped
+        assert!(state.set_texture(TEXTURE_2D, Some(TextureId(1))));
+        assert!(state.set_texture(TEXTURE_2D, Some(TextureId(1))));
+
+        assert!(state.set_active_texture(0));
+        assert!(state.set_texture(TEXTURE_2D, Some(TextureId(1))));
+        assert!(state.set_active_texture(1));
+        assert!(state.set_texture(TEXTURE_2D, Some(TextureId(2))));
+        assert!(state.set_active_texture(0));
+        assert!(!state.set_texture(TEXTURE_2D, Some(TextureId(1))));
+        assert_eq!(state.texture(TEXTURE_2D), Some(Some(TextureId(1))));
+        state.forget();
+        assert_eq!(state.texture(TEXTURE_2D), None);
+    }
+
+    #[test]
+    fn forgets_element_buffer_with_vertex_array() {
+        let state = GlState::new();
+        assert!(state.set_vertex_array(Some(VertexArrayId(1))));
+        assert!(state.set_buffer(BufferKind::ElementArrayBuffer, Some(BufferId(3))));
+        assert!(state.set_buffer(BufferKind::ArrayBuffer, Some(BufferId(4))));
+
+        assert!(state.set_vertex_array(None));
+        assert!(state.set_buffer(BufferKind::ElementArrayBuffer, Some(BufferId(3))));
+        // array buffer binding is global
+        assert!(!state.set_buffer(BufferKind::ArrayBuffer, Some(BufferId(4))));
+    }
+
+    #[test]
+    fn forgets_deleted_objects() {
+        let state = GlState::new();
+        state.set_active_texture(2);
+        state.set_texture(TEXTURE_2D, Some(TextureId(1)));
+        state.forget_texture(TextureId(1));
+        assert_eq!(state.texture(TEXTURE_2D), Some(None));
+
+        state.set_vertex_array(Some(VertexArrayId(1)));
+        state.set_buffer(BufferKind::ElementArrayBuffer, Some(BufferId(3)));
+        state.set_attribute_array(0, true);
+        state.forget_vertex_array(VertexArrayId(1));
+        assert!(!state.set_vertex_array(None));
+        assert!(state.set_buffer(BufferKind::ElementArrayBuffer, Some(BufferId(3))));
+        assert!(state.set_attribute_array(0, true));
+
+        state.set_buffer(BufferKind::ArrayBuffer, Some(BufferId(4)));
+        state.forget_buffer(BufferId(4));
+        assert!(!state.set_buffer(BufferKind::ArrayBuffer, None));
+
+        state.set_program(Some(ProgramId(5)));
+        state.forget_program(ProgramId(5));
+        assert!(state.set_program(None));
+    }
+
+    #[test]
+    fn forgets_attributes_with_vertex_array() {
+        let state = GlState::new();
+        assert!(state.set_attribute_array(0, true));
+        assert!(!state.set_attribute_array(0, true));
+        assert!(state.set_attribute_divisor(2, 1));
+        assert!(!state.set_attribute_divisor(2, 1));
+        assert!(state.set_attribute_array(0, false));
+
+        // enabled arrays and divisors belong to the vertex array
+        assert!(state.set_vertex_array(Some(VertexArrayId(1))));
+        assert!(state.set_attribute_array(0, false));
+        assert!(state.set_attribute_divisor(2, 1));
+        assert!(!state.set_vertex_array(Some(VertexArrayId(1))));
+        assert!(!state.set_attribute_divisor(2, 1));
+
+        state.count_attribute_pointer();
+        let stats = state.stats();
+        assert_eq!(stats.issued.attributes, 6);
+        assert_eq!(stats.skipped.attributes, 3);
+    }
+}
@@ -0,0 +1,104 @@
+use web_sys::{WebGl2RenderingContext as Gl2, WebGlRenderingContext as Gl};
+
+/// GLSL type names along with the GL enums reflection reports for them
+const TYPES: &[(&str, u32)] = &[
+    ("float", Gl::FLOAT),
+    ("vec2", Gl::FLOAT_VEC2),
+    ("vec3", Gl::FLOAT_VEC3),
+    ("vec4", Gl::FLOAT_VEC4),
+    ("int", Gl::INT),
+    ("ivec2", Gl::INT_VEC2),
+    ("ivec3", Gl::INT_VEC3),
+    ("ivec4", Gl::INT_VEC4),
+    ("uint", Gl2::UNSIGNED_INT),
+    ("uvec2", Gl2::UNSIGNED_INT_VEC2),
+    ("uvec3", Gl2::UNSIGNED_INT_VEC3),
+    ("uvec4", Gl2::UNSIGNED_INT_VEC4),
+    ("bool", Gl::BOOL),
+    ("bvec2", Gl::BOOL_VEC2),
+    ("bvec3", Gl::BOOL_VEC3),
+    ("bvec4", Gl::BOOL_VEC4),
+    ("mat2", Gl::FLOAT_MAT2),
+    ("mat3", Gl::FLOAT_MAT3),
+    ("mat4", Gl::FLOAT_MAT4),
+    ("mat2x3", Gl2::FLOAT_MAT2X3),
+    ("mat2x4", Gl2::FLOAT_MAT2X4),
+    ("mat3x2", Gl2::FLOAT_MAT3X2),
+    ("mat3x4", Gl2::FLOAT_MAT3X4),
+    ("mat4x2", Gl2::FLOAT_MAT4X2),
+    ("mat4x3", Gl2::FLOAT_MAT4X3),
+    ("sampler2D", Gl::SAMPLER_2D),
+    ("samplerCube", Gl::SAMPLER_CUBE),
+    ("sampler3D", Gl2::SAMPLER_3D),
+    ("sampler2DArray", Gl2::SAMPLER_2D_ARRAY),
+    ("sampler2DShadow", Gl2::SAMPLER_2D_SHADOW),
+    ("sampler2DArrayShadow", Gl2::SAMPLER_2D_ARRAY_SHADOW),
+    ("samplerCubeShadow", Gl2::SAMPLER_CUBE_SHADOW),
+    ("isampler2D", Gl2::INT_SAMPLER_2D),
+    ("isampler3D", Gl2::INT_SAMPLER_3D),
+    ("isamplerCube", Gl2::INT_SAMPLER_CUBE),
+    ("isampler2DArray", Gl2::INT_SAMPLER_2D_ARRAY),
+    ("usampler2D", Gl2::UNSIGNED_INT_SAMPLER_2D),
+    ("usampler3D", Gl2::UNSIGNED_INT_SAMPLER_3D),
+    ("usamplerCube", Gl2::UNSIGNED_INT_SAMPLER_CUBE),
+    ("usampler2DArray", Gl2::UNSIGNED_INT_SAMPLER_2D_ARRAY),
+];
+
+pub fn from_name(name: &str) -> Option<u32> {
+    TYPES
+        .iter()
+        .find(|(type_name, _)| *type_name == name)
+        .map(|(_, data_type)| *data_type)
+}
+
+pub fn name(data_type: u32) -> &'static str {
+    TYPES
+        .iter()
+        .find(|(_, known)| *known == data_type)
+        .map_or("unknown type", |(type_name, _)| type_name)
+}
+
+/// How many attribute locations a value of this type takes,
+/// matrices take one per column
+pub fn attribute_slots(data_type: u32) -> i32 {
+    match data_type {
+        Gl::FLOAT_MAT2 | Gl2::FLOAT_MAT2X3 | Gl2::FLOAT_MAT2X4 => 2,
+        Gl::FLOAT_MAT3 | Gl2::FLOAT_MAT3X2 | Gl2::FLOAT_MAT3X4 => 3,
+        Gl::FLOAT_MAT4 | Gl2::FLOAT_MAT4X2 | Gl2::FLOAT_MAT4X3 => 4,
+        _ => 1,
+    }
+}
+
+/// Components of float scalars and vectors, the only
+/// types `vertexAttribPointer` can feed a single location
+pub fn float_components(data_type: u32) -> Option<i32> {
+    match data_type {
+        Gl::FLOAT => Some(1),
+        Gl::FLOAT_VEC2 => Some(2),
+        Gl::FLOAT_VEC3 => Some(3),
+        Gl::FLOAT_VEC4 => Some(4),
+        _ => None,
+    }
+}
+
+/// Whether `UniformValue` has a variant for uniforms of this type,
+/// `uint`, `uvec*` and the non-square matrices have none
+pub fn is_settable_uniform(data_type: u32) -> bool {
+    !matches!(
+        data_type,
+        Gl2::UNSIGNED_INT
+            | Gl2::UNSIGNED_INT_VEC2
+            | Gl2::UNSIGNED_INT_VEC3
+            | Gl2::UNSIGNED_INT_VEC4
+            | Gl2::FLOAT_MAT2X3
+            | Gl2::FLOAT_MAT2X4
+            | Gl2::FLOAT_MAT3X2
+            | Gl2::FLOAT_MAT3X4
+            | Gl2::FLOAT_MAT4X2
+            | Gl2::FLOAT_MAT4X3
+    )
+}
+
+pub fn is_sampler(data_type: u32) -> bool {
+    name(data_type).contains("sampler")
+}
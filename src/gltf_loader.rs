@@ -0,0 +1,1064 @@
+use std::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use gltf::accessor::sparse::IndexType;
+use gltf::accessor::{DataType, Dimensions};
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use gltf::{Accessor, Gltf};
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+
+use crate::error::Error;
+use crate::geometry::{Geometry, PrimitiveMode};
+use crate::mesh::Mesh;
+use crate::rendering_context::RenderingContext;
+use crate::scene_graph::{NodeId, SceneGraph, Transform};
+use crate::texture::{Filter, SamplerOptions, Wrap};
+
+/// Why a glTF file could not be loaded, like `image_decoder::DecodeError`
+/// it keeps the cause
+#[derive(Debug)]
+pub enum GltfError {
+    /// Broken JSON or GLB container, or an invalid document
+    Parse(gltf::Error),
+    /// The document is fine, but its data is missing or out of bounds
+    Invalid(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Parse(error) => write!(f, "Can't parse glTF: {error}"),
+            GltfError::Invalid(message) => write!(f, "Invalid glTF: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<GltfError> for Error {
+    fn from(error: GltfError) -> Self {
+        Error::new(&error.to_string())
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(error: gltf::Error) -> Self {
+        GltfError::Parse(error)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> GltfError {
+    GltfError::Invalid(message.into())
+}
+
+/// Everything loaded from a `.gltf` or `.glb` file.
+/// Objects refer to each other by their index in the file
+pub struct GltfAsset {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<GltfImage>,
+    pub nodes: Vec<GltfNode>,
+    pub scenes: Vec<GltfScene>,
+    pub default_scene: Option<usize>,
+    pub animations: Vec<Animation>,
+}
+
+pub struct GltfMesh {
+    pub name: String,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// Part of a mesh drawn with a single material
+pub struct GltfPrimitive {
+    pub geometry: Geometry,
+    /// `COLOR_0` as four floats per vertex, empty when missing
+    pub colors: Vec<f32>,
+    /// Index into `GltfAsset::materials`, the default material when `None`
+    pub material: Option<usize>,
+}
+
+impl GltfPrimitive {
+    /// Uploads the geometry, vertex colors are bound to `aVertexColor`
+    pub fn create_mesh(&self, ctx: &RenderingContext) -> Result<Mesh, Error> {
+        let mut mesh = Mesh::new(ctx, self.geometry.clone())?;
+        if !self.colors.is_empty() {
+            mesh.add_attribute(ctx, "aVertexColor", self.colors.clone(), 4)?;
+        }
+        Ok(mesh)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fully transparent below `PbrMaterial::alpha_cutoff`, opaque otherwise
+    Mask,
+    Blend,
+}
+
+/// A texture used by a material, and the UV set it's sampled with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureRef {
+    /// Index into `GltfAsset::textures`
+    pub texture: usize,
+    /// 0 for `TEXCOORD_0`
+    pub tex_coord: u32,
+}
+
+impl TextureRef {
+    fn new(info: &gltf::texture::Info) -> Self {
+        TextureRef {
+            texture: info.texture().index(),
+            tex_coord: info.tex_coord(),
+        }
+    }
+}
+
+/// Metallic-roughness material parameters. Factors multiply the
+/// texture values, or are used as they are without a texture
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    pub name: String,
+    /// Linear RGBA
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in the green channel, metalness in the blue one
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+/// An image along with the way it's sampled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GltfTexture {
+    /// Index into `GltfAsset::images`
+    pub image: usize,
+    pub sampler: SamplerOptions,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageData {
+    /// PNG or JPEG file contents, from a buffer view or a data URI.
+    /// They can be decoded with `image_decoder::decode`
+    Bytes(Vec<u8>),
+    /// External file, relative to the glTF file
+    Uri(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GltfImage {
+    pub name: String,
+    pub mime_type: Option<String>,
+    pub data: ImageData,
+}
+
+pub struct GltfNode {
+    pub name: String,
+    pub transform: Transform,
+    /// Index into `GltfAsset::meshes`
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+pub struct GltfScene {
+    pub name: String,
+    /// Root nodes of the scene
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationProperty {
+    Translation,
+    /// Quaternions, as x, y, z, w
+    Rotation,
+    Scale,
+    /// Morph target weights, which are not applied by `Animation::apply`
+    Weights,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Every keyframe has an in-tangent, a value and an out-tangent
+    CubicSpline,
+}
+
+/// Keyframes of a single property of a single node
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationChannel {
+    /// Index into `GltfAsset::nodes`
+    pub node: usize,
+    pub property: AnimationProperty,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds, increasing, at least one
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<AnimationChannel>,
+}
+
+/// Parses a `.gltf` or `.glb` file. Buffers which are neither embedded
+/// in a GLB nor data URIs are asked from `load_uri`, by their URI.
+/// External images are left for the caller to load, see `ImageData::Uri`
+pub fn parse_gltf(
+    bytes: &[u8],
+    mut load_uri: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<GltfAsset, GltfError> {
+    let gltf = Gltf::from_slice(bytes)?;
+    let buffers = load_buffers(&gltf, &mut load_uri)?;
+
+    let meshes = gltf
+        .meshes()
+        .map(|mesh| load_mesh(&mesh, &buffers))
+        .collect::<Result<_, _>>()?;
+    let images = gltf
+        .images()
+        .map(|image| load_image(&image, &buffers))
+        .collect::<Result<_, _>>()?;
+    let animations = gltf
+        .animations()
+        .map(|animation| load_animation(&animation, &buffers))
+        .collect::<Result<_, _>>()?;
+
+    Ok(GltfAsset {
+        meshes,
+        materials: gltf
+            .materials()
+            .map(|material| load_material(&material))
+            .collect(),
+        textures: gltf
+            .textures()
+            .map(|texture| load_texture(&texture))
+            .collect(),
+        images,
+        nodes: gltf.nodes().map(|node| load_node(&node)).collect(),
+        scenes: gltf
+            .scenes()
+            .map(|scene| GltfScene {
+                name: scene.name().unwrap_or_default().to_string(),
+                nodes: scene.nodes().map(|node| node.index()).collect(),
+            })
+            .collect(),
+        default_scene: gltf.default_scene().map(|scene| scene.index()),
+        animations,
+    })
+}
+
+/// `None` when the URI doesn't hold the data itself
+fn decode_data_uri(uri: &str) -> Result<Option<Vec<u8>>, GltfError> {
+    let Some(rest) = uri.strip_prefix("data:") else {
+        return Ok(None);
+    };
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| invalid("Data URI without a comma"))?;
+    if !header.ends_with(";base64") {
+        return Err(invalid(format!(
+            "Only base64 data URIs are supported, got '{header}'"
+        )));
+    }
+    let data = BASE64
+        .decode(payload)
+        .map_err(|error| invalid(format!("Bad base64 in a data URI: {error}")))?;
+    Ok(Some(data))
+}
+
+fn load_buffers(
+    gltf: &Gltf,
+    load_uri: &mut impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, GltfError> {
+    let mut buffers = vec![];
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or_else(|| invalid("The GLB binary chunk is missing"))?,
+            gltf::buffer::Source::Uri(uri) => match decode_data_uri(uri)? {
+                Some(data) => data,
+                None => load_uri(uri)
+                    .ok_or_else(|| invalid(format!("Buffer '{uri}' was not provided")))?,
+            },
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid(format!(
+                "Buffer {} has {} bytes, expected {}",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            )));
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+fn view_bytes<'a>(
+    view: &gltf::buffer::View,
+    buffers: &'a [Vec<u8>],
+) -> Result<&'a [u8], GltfError> {
+    let buffer = &buffers[view.buffer().index()];
+    buffer
+        .get(view.offset()..view.offset() + view.length())
+        .ok_or_else(|| invalid(format!("Buffer view {} is out of bounds", view.index())))
+}
+
+fn component_size(data_type: DataType) -> usize {
+    match data_type {
+        DataType::I8 | DataType::U8 => 1,
+        DataType::I16 | DataType::U16 => 2,
+        DataType::U32 | DataType::F32 => 4,
+    }
+}
+
+/// Normalized integers are mapped to [0, 1], or [-1, 1] when signed
+fn read_component(bytes: &[u8], data_type: DataType, normalized: bool) -> f64 {
+    let (value, max) = match data_type {
+        DataType::I8 => (bytes[0] as i8 as f64, i8::MAX as f64),
+        DataType::U8 => (bytes[0] as f64, u8::MAX as f64),
+        DataType::I16 => (
+            i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            i16::MAX as f64,
+        ),
+        DataType::U16 => (
+            u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            u16::MAX as f64,
+        ),
+        DataType::U32 => {
+            let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            return value as f64;
+        }
+        DataType::F32 => {
+            let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            return value as f64;
+        }
+    };
+    match normalized {
+        true => (value / max).max(-1.0),
+        false => value,
+    }
+}
+
+/// How the components of one element are laid out
+struct ElementLayout {
+    data_type: DataType,
+    normalized: bool,
+    columns: usize,
+    rows: usize,
+    /// Matrix columns start at multiples of 4 bytes
+    column_stride: usize,
+}
+
+impl ElementLayout {
+    fn new(accessor: &Accessor) -> Self {
+        let data_type = accessor.data_type();
+        let size = component_size(data_type);
+        let (columns, rows) = match accessor.dimensions() {
+            Dimensions::Mat2 => (2, 2),
+            Dimensions::Mat3 => (3, 3),
+            Dimensions::Mat4 => (4, 4),
+            dimensions => (1, dimensions.multiplicity()),
+        };
+        let column_stride = match columns {
+            1 => rows * size,
+            _ => (rows * size).next_multiple_of(4),
+        };
+        ElementLayout {
+            data_type,
+            normalized: accessor.normalized(),
+            columns,
+            rows,
+            column_stride,
+        }
+    }
+
+    fn components(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn size(&self) -> usize {
+        self.columns * self.column_stride
+    }
+
+    fn read(&self, bytes: &[u8], start: usize, into: &mut [f64]) -> Result<(), GltfError> {
+        let size = component_size(self.data_type);
+        let end = start + self.size();
+        let element = bytes
+            .get(start..end)
+            .ok_or_else(|| invalid("Accessor reads past the end of its buffer view"))?;
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                let offset = column * self.column_stride + row * size;
+                into[column * self.rows + row] =
+                    read_component(&element[offset..], self.data_type, self.normalized);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads every component of every element, handling strides,
+/// normalized integers and sparse substitution.
+/// `f64` keeps 32-bit integers exact
+fn read_accessor(accessor: &Accessor, buffers: &[Vec<u8>]) -> Result<Vec<f64>, GltfError> {
+    let layout = ElementLayout::new(accessor);
+    let components = layout.components();
+    let mut values = vec![0.0; accessor.count() * components];
+
+    // without a buffer view the accessor is all zeros
+    if let Some(view) = accessor.view() {
+        let bytes = view_bytes(&view, buffers)?;
+        let stride = view.stride().unwrap_or(layout.size());
+        for (index, element) in values.chunks_mut(components).enumerate() {
+            layout.read(bytes, accessor.offset() + index * stride, element)?;
+        }
+    }
+
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_bytes = view_bytes(&indices.view(), buffers)?;
+        let (index_type, index_size) = match indices.index_type() {
+            IndexType::U8 => (DataType::U8, 1),
+            IndexType::U16 => (DataType::U16, 2),
+            IndexType::U32 => (DataType::U32, 4),
+        };
+        let value_bytes = view_bytes(&sparse.values().view(), buffers)?;
+        for item in 0..sparse.count() {
+            let start = indices.offset() + item * index_size;
+            let index_bytes = index_bytes
+                .get(start..start + index_size)
+                .ok_or_else(|| invalid("Sparse indices are out of bounds"))?;
+            let index = read_component(index_bytes, index_type, false) as usize;
+            let element = values
+                .get_mut(index * components..(index + 1) * components)
+                .ok_or_else(|| invalid(format!("Sparse index {index} is out of bounds")))?;
+            let start = sparse.values().offset() + item * layout.size();
+            layout.read(value_bytes, start, element)?;
+        }
+    }
+    Ok(values)
+}
+
+fn read_floats(accessor: &Accessor, buffers: &[Vec<u8>]) -> Result<Vec<f32>, GltfError> {
+    let values = read_accessor(accessor, buffers)?;
+    Ok(values.into_iter().map(|value| value as f32).collect())
+}
+
+fn check_dimensions(
+    accessor: &Accessor,
+    what: &str,
+    allowed: &[Dimensions],
+) -> Result<(), GltfError> {
+    if !allowed.contains(&accessor.dimensions()) {
+        return Err(invalid(format!(
+            "{what} can't be {:?}, accessor {}",
+            accessor.dimensions(),
+            accessor.index()
+        )));
+    }
+    Ok(())
+}
+
+fn primitive_mode(mode: gltf::mesh::Mode) -> PrimitiveMode {
+    match mode {
+        gltf::mesh::Mode::Points => PrimitiveMode::Points,
+        gltf::mesh::Mode::Lines => PrimitiveMode::Lines,
+        gltf::mesh::Mode::LineLoop => PrimitiveMode::LineLoop,
+        gltf::mesh::Mode::LineStrip => PrimitiveMode::LineStrip,
+        gltf::mesh::Mode::Triangles => PrimitiveMode::Triangles,
+        gltf::mesh::Mode::TriangleStrip => PrimitiveMode::TriangleStrip,
+        gltf::mesh::Mode::TriangleFan => PrimitiveMode::TriangleFan,
+    }
+}
+
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[Vec<u8>],
+) -> Result<GltfPrimitive, GltfError> {
+    use gltf::Semantic;
+
+    let positions = primitive
+        .get(&Semantic::Positions)
+        .ok_or_else(|| invalid(format!("Primitive {} has no positions", primitive.index())))?;
+    check_dimensions(&positions, "Positions", &[Dimensions::Vec3])?;
+    let vertices = read_floats(&positions, buffers)?;
+
+    let indices = match primitive.indices() {
+        Some(accessor) => {
+            let indices = read_accessor(&accessor, buffers)?;
+            indices.into_iter().map(|index| index as u32).collect()
+        }
+        None => vec![],
+    };
+
+    let normals = match primitive.get(&Semantic::Normals) {
+        Some(accessor) => {
+            check_dimensions(&accessor, "Normals", &[Dimensions::Vec3])?;
+            read_floats(&accessor, buffers)?
+        }
+        None => vec![],
+    };
+    let uvs = match primitive.get(&Semantic::TexCoords(0)) {
+        Some(accessor) => {
+            check_dimensions(&accessor, "Texture coordinates", &[Dimensions::Vec2])?;
+            read_floats(&accessor, buffers)?
+        }
+        None => vec![],
+    };
+    let tangents = match primitive.get(&Semantic::Tangents) {
+        Some(accessor) => {
+            check_dimensions(&accessor, "Tangents", &[Dimensions::Vec4])?;
+            read_floats(&accessor, buffers)?
+        }
+        None => vec![],
+    };
+    let colors = match primitive.get(&Semantic::Colors(0)) {
+        Some(accessor) => {
+            check_dimensions(&accessor, "Colors", &[Dimensions::Vec3, Dimensions::Vec4])?;
+            let colors = read_floats(&accessor, buffers)?;
+            match accessor.dimensions() {
+                Dimensions::Vec3 => colors
+                    .chunks_exact(3)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 1.0])
+                    .collect(),
+                _ => colors,
+            }
+        }
+        None => vec![],
+    };
+
+    let geometry = Geometry::new(indices, vertices)
+        .with_mode(primitive_mode(primitive.mode()))
+        .with_normals(normals)
+        .with_uvs(uvs)
+        .with_tangents(tangents);
+    Ok(GltfPrimitive {
+        geometry,
+        colors,
+        material: primitive.material().index(),
+    })
+}
+
+fn load_mesh(mesh: &gltf::Mesh, buffers: &[Vec<u8>]) -> Result<GltfMesh, GltfError> {
+    let primitives = mesh
+        .primitives()
+        .map(|primitive| load_primitive(&primitive, buffers))
+        .collect::<Result<_, _>>()?;
+    Ok(GltfMesh {
+        name: mesh.name().unwrap_or_default().to_string(),
+        primitives,
+    })
+}
+
+fn load_material(material: &gltf::Material) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+    PbrMaterial {
+        name: material.name().unwrap_or_default().to_string(),
+        base_color: pbr.base_color_factor(),
+        base_color_texture: pbr.base_color_texture().as_ref().map(TextureRef::new),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .as_ref()
+            .map(TextureRef::new),
+        normal_texture: normal.as_ref().map(|normal| TextureRef {
+            texture: normal.texture().index(),
+            tex_coord: normal.tex_coord(),
+        }),
+        normal_scale: normal.as_ref().map_or(1.0, |normal| normal.scale()),
+        occlusion_texture: occlusion.as_ref().map(|occlusion| TextureRef {
+            texture: occlusion.texture().index(),
+            tex_coord: occlusion.tex_coord(),
+        }),
+        occlusion_strength: occlusion
+            .as_ref()
+            .map_or(1.0, |occlusion| occlusion.strength()),
+        emissive: material.emissive_factor(),
+        emissive_texture: material.emissive_texture().as_ref().map(TextureRef::new),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+fn wrap(mode: WrappingMode) -> Wrap {
+    match mode {
+        WrappingMode::ClampToEdge => Wrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        WrappingMode::Repeat => Wrap::Repeat,
+    }
+}
+
+/// Filters the file leaves out keep the engine defaults
+fn load_texture(texture: &gltf::Texture) -> GltfTexture {
+    let sampler = texture.sampler();
+    let mut options = SamplerOptions {
+        wrap_s: wrap(sampler.wrap_s()),
+        wrap_t: wrap(sampler.wrap_t()),
+        ..SamplerOptions::default()
+    };
+    if let Some(filter) = sampler.mag_filter() {
+        options.mag_filter = match filter {
+            MagFilter::Nearest => Filter::Nearest,
+            MagFilter::Linear => Filter::Linear,
+        };
+    }
+    if let Some(filter) = sampler.min_filter() {
+        use Filter::{Linear, Nearest};
+        (options.min_filter, options.mipmap_filter) = match filter {
+            MinFilter::Nearest => (Nearest, None),
+            MinFilter::Linear => (Linear, None),
+            MinFilter::NearestMipmapNearest => (Nearest, Some(Nearest)),
+            MinFilter::LinearMipmapNearest => (Linear, Some(Nearest)),
+            MinFilter::NearestMipmapLinear => (Nearest, Some(Linear)),
+            MinFilter::LinearMipmapLinear => (Linear, Some(Linear)),
+        };
+    }
+    GltfTexture {
+        image: texture.source().index(),
+        sampler: options,
+    }
+}
+
+fn load_image(image: &gltf::Image, buffers: &[Vec<u8>]) -> Result<GltfImage, GltfError> {
+    let (mime_type, data) = match image.source() {
+        gltf::image::Source::View { view, mime_type } => {
+            let bytes = view_bytes(&view, buffers)?.to_vec();
+            (Some(mime_type), ImageData::Bytes(bytes))
+        }
+        gltf::image::Source::Uri { uri, mime_type } => match decode_data_uri(uri)? {
+            Some(bytes) => (mime_type, ImageData::Bytes(bytes)),
+            None => (mime_type, ImageData::Uri(uri.to_string())),
+        },
+    };
+    Ok(GltfImage {
+        name: image.name().unwrap_or_default().to_string(),
+        mime_type: mime_type.map(str::to_string),
+        data,
+    })
+}
+
+fn load_node(node: &gltf::Node) -> GltfNode {
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    let transform = Transform {
+        translation: Vector3::from(translation),
+        rotation: UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)),
+        scale: Vector3::from(scale),
+    };
+    GltfNode {
+        name: node.name().unwrap_or_default().to_string(),
+        transform,
+        mesh: node.mesh().map(|mesh| mesh.index()),
+        children: node.children().map(|child| child.index()).collect(),
+    }
+}
+
+fn load_animation(
+    animation: &gltf::Animation,
+    buffers: &[Vec<u8>],
+) -> Result<Animation, GltfError> {
+    use gltf::animation::{Interpolation as GltfInterpolation, Property};
+
+    let mut channels = vec![];
+    for channel in animation.channels() {
+        let sampler = channel.sampler();
+        let times = read_floats(&sampler.input(), buffers)?;
+        let values = read_floats(&sampler.output(), buffers)?;
+        let interpolation = match sampler.interpolation() {
+            GltfInterpolation::Step => Interpolation::Step,
+            GltfInterpolation::Linear => Interpolation::Linear,
+            GltfInterpolation::CubicSpline => Interpolation::CubicSpline,
+        };
+        let property = match channel.target().property() {
+            Property::Translation => AnimationProperty::Translation,
+            Property::Rotation => AnimationProperty::Rotation,
+            Property::Scale => AnimationProperty::Scale,
+            Property::MorphTargetWeights => AnimationProperty::Weights,
+        };
+        let keyframe_values = match interpolation {
+            Interpolation::CubicSpline => times.len() * 3,
+            _ => times.len(),
+        };
+        if keyframe_values == 0 || values.len() % keyframe_values != 0 {
+            return Err(invalid(format!(
+                "Animation channel {} has {} values for {} keyframes",
+                channel.index(),
+                values.len(),
+                times.len()
+            )));
+        }
+        channels.push(AnimationChannel {
+            node: channel.target().node().index(),
+            property,
+            interpolation,
+            times,
+            values,
+        });
+    }
+    Ok(Animation {
+        name: animation.name().unwrap_or_default().to_string(),
+        channels,
+    })
+}
+
+impl GltfAsset {
+    /// Adds the nodes of a scene to the graph, under `parent`.
+    /// Returns the graph node of every glTF node, `None` for the nodes
+    /// which are not part of the scene
+    pub fn add_to_graph(
+        &self,
+        graph: &mut SceneGraph,
+        scene: usize,
+        parent: Option<NodeId>,
+    ) -> Result<Vec<Option<NodeId>>, Error> {
+        let scene = self
+            .scenes
+            .get(scene)
+            .ok_or_else(|| Error::new(&format!("There is no scene {scene}")))?;
+        let mut added = vec![None; self.nodes.len()];
+        let mut stack: Vec<_> = scene.nodes.iter().map(|&node| (node, parent)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = &self.nodes[index];
+            let id = graph.add(node.transform, parent)?;
+            added[index] = Some(id);
+            stack.extend(node.children.iter().map(|&child| (child, Some(id))));
+        }
+        Ok(added)
+    }
+}
+
+impl AnimationChannel {
+    /// Floats in a single value, 4 for rotations
+    pub fn components(&self) -> usize {
+        let keyframe_values = match self.interpolation {
+            Interpolation::CubicSpline => self.times.len() * 3,
+            _ => self.times.len(),
+        };
+        self.values.len() / keyframe_values
+    }
+
+    fn value(&self, keyframe: usize) -> &[f32] {
+        let components = self.components();
+        let index = match self.interpolation {
+            Interpolation::CubicSpline => keyframe * 3 + 1,
+            _ => keyframe,
+        };
+        &self.values[index * components..(index + 1) * components]
+    }
+
+    /// Cubic spline tangents: 0 for the in-tangent, 2 for the out-tangent
+    fn tangent(&self, keyframe: usize, which: usize) -> &[f32] {
+        let components = self.components();
+        let index = keyframe * 3 + which;
+        &self.values[index * components..(index + 1) * components]
+    }
+
+    /// The value at `time`, holding the first and last keyframes
+    /// outside of the animated range
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&keyframe| keyframe <= time);
+        if next == 0 {
+            return self.value(0).to_vec();
+        }
+        if next > last {
+            return self.value(last).to_vec();
+        }
+        let previous = next - 1;
+        let duration = self.times[next] - self.times[previous];
+        let t = (time - self.times[previous]) / duration;
+
+        let rotation = self.property == AnimationProperty::Rotation;
+        match self.interpolation {
+            Interpolation::Step => self.value(previous).to_vec(),
+            Interpolation::Linear if rotation => {
+                slerp(self.value(previous), self.value(next), t).to_vec()
+            }
+            Interpolation::Linear => {
+                let (from, to) = (self.value(previous), self.value(next));
+                from.iter().zip(to).map(|(a, b)| a + (b - a) * t).collect()
+            }
+            Interpolation::CubicSpline => {
+                let (t2, t3) = (t * t, t * t * t);
+                let from = self.value(previous);
+                let from_tangent = self.tangent(previous, 2);
+                let to = self.value(next);
+                let to_tangent = self.tangent(next, 0);
+                let mut result: Vec<f32> = (0..from.len())
+                    .map(|i| {
+                        (2.0 * t3 - 3.0 * t2 + 1.0) * from[i]
+                            + (t3 - 2.0 * t2 + t) * duration * from_tangent[i]
+                            + (-2.0 * t3 + 3.0 * t2) * to[i]
+                            + (t3 - t2) * duration * to_tangent[i]
+                    })
+                    .collect();
+                if rotation {
+                    let length = result.iter().map(|value| value * value).sum::<f32>().sqrt();
+                    result.iter_mut().for_each(|value| *value /= length);
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Spherical interpolation of x, y, z, w quaternions, along the shorter arc
+fn slerp(from: &[f32], to: &[f32], t: f32) -> [f32; 4] {
+    let from = Quaternion::new(from[3], from[0], from[1], from[2]);
+    let to = Quaternion::new(to[3], to[0], to[1], to[2]);
+    let from = UnitQuaternion::from_quaternion(from);
+    let mut to = UnitQuaternion::from_quaternion(to);
+    if from.coords.dot(&to.coords) < 0.0 {
+        to = UnitQuaternion::new_unchecked(-to.into_inner());
+    }
+    let result = from.try_slerp(&to, t, 1.0e-6).unwrap_or(from);
+    let coords = result.coords;
+    [coords.x, coords.y, coords.z, coords.w]
+}
+
+impl Animation {
+    /// The time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max)
+    }
+
+    /// Sets the animated transforms at `time`. `nodes` maps glTF nodes
+    /// to the graph, as returned by `GltfAsset::add_to_graph`
+    pub fn apply(
+        &self,
+        time: f32,
+        graph: &mut SceneGraph,
+        nodes: &[Option<NodeId>],
+    ) -> Result<(), Error> {
+        for channel in &self.channels {
+            let Some(id) = nodes.get(channel.node).copied().flatten() else {
+                continue;
+            };
+            let value = channel.sample(time);
+            match channel.property {
+                AnimationProperty::Translation => graph.update_transform(id, |transform| {
+                    transform.translation = Vector3::new(value[0], value[1], value[2])
+                })?,
+                AnimationProperty::Rotation => graph.update_transform(id, |transform| {
+                    let rotation = Quaternion::new(value[3], value[0], value[1], value[2]);
+                    transform.rotation = UnitQuaternion::from_quaternion(rotation)
+                })?,
+                AnimationProperty::Scale => graph.update_transform(id, |transform| {
+                    transform.scale = Vector3::new(value[0], value[1], value[2])
+                })?,
+                AnimationProperty::Weights => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_uri(bytes: &[u8]) -> String {
+        let encoded = BASE64.encode(bytes);
+        format!("data:application/octet-stream;base64,{encoded}")
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    /// A triangle with u16 indices and normalized u8 texture coordinates
+    fn triangle_json(buffer_uri: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": 50, "uri": "{buffer_uri}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }},
+                    {{ "buffer": 0, "byteOffset": 44, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }},
+                    {{ "bufferView": 2, "componentType": 5121, "count": 3, "type": "VEC2",
+                       "normalized": true }}
+                ],
+                "meshes": [{{ "name": "triangle", "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 2 }},
+                    "indices": 1
+                }}] }}],
+                "nodes": [
+                    {{ "name": "root", "children": [1], "translation": [1, 2, 3] }},
+                    {{ "mesh": 0, "scale": [2, 2, 2] }}
+                ],
+                "scenes": [{{ "nodes": [0] }}],
+                "scene": 0
+            }}"#
+        )
+    }
+
+    fn triangle_buffer() -> Vec<u8> {
+        let mut buffer = floats(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        buffer.extend([0, 0, 1, 0, 2, 0, 0, 0]);
+        buffer.extend([0, 0, 255, 0, 0, 255]);
+        buffer
+    }
+
+    #[test]
+    fn loads_data_uri_buffers() {
+        let json = triangle_json(&data_uri(&triangle_buffer()));
+        let asset = parse_gltf(json.as_bytes(), |_| None).unwrap();
+        let primitive = &asset.meshes[0].primitives[0];
+        assert_eq!(asset.meshes[0].name, "triangle");
+        assert_eq!(primitive.geometry.vertex_count(), 3);
+        assert_eq!(primitive.geometry.indices, [0, 1, 2]);
+        assert_eq!(primitive.geometry.uvs, [0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert!(primitive.geometry.normals.is_empty());
+        assert_eq!(asset.default_scene, Some(0));
+    }
+
+    #[test]
+    fn asks_for_external_buffers() {
+        let json = triangle_json("triangle.bin");
+        let mut requested = vec![];
+        let asset = parse_gltf(json.as_bytes(), |uri| {
+            requested.push(uri.to_string());
+            Some(triangle_buffer())
+        })
+        .unwrap();
+        assert_eq!(requested, ["triangle.bin"]);
+        assert_eq!(asset.meshes[0].primitives[0].geometry.vertices[3], 1.0);
+
+        let missing = parse_gltf(json.as_bytes(), |_| None);
+        assert!(matches!(missing, Err(GltfError::Invalid(_))));
+    }
+
+    #[test]
+    fn loads_glb() {
+        let json = triangle_json("").replace(r#", "uri": """#, "");
+        let mut json = json.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut binary = triangle_buffer();
+        binary.resize(binary.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + binary.len();
+        let mut glb = b"glTF".to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((length as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        glb.extend((binary.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(&binary);
+
+        let asset = parse_gltf(&glb, |_| None).unwrap();
+        assert_eq!(asset.meshes[0].primitives[0].geometry.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn builds_the_node_tree() {
+        let json = triangle_json(&data_uri(&triangle_buffer()));
+        let asset = parse_gltf(json.as_bytes(), |_| None).unwrap();
+        let mut graph = SceneGraph::new();
+        let nodes = asset.add_to_graph(&mut graph, 0, None).unwrap();
+        let (root, child) = (nodes[0].unwrap(), nodes[1].unwrap());
+        assert_eq!(graph.parent(child), Some(root));
+        assert_eq!(asset.nodes[1].mesh, Some(0));
+        let world = graph.world_matrix(child).unwrap();
+        assert_eq!(world[(0, 0)], 2.0);
+        assert_eq!(world[(2, 3)], 3.0);
+    }
+
+    #[test]
+    fn applies_sparse_accessors() {
+        let mut buffer = floats(&[1.0, 2.0, 3.0, 4.0]);
+        buffer.extend([2, 0, 0, 0]);
+        buffer.extend(floats(&[30.0]));
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": {}, "uri": "{}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 16 }},
+                    {{ "buffer": 0, "byteOffset": 16, "byteLength": 2 }},
+                    {{ "buffer": 0, "byteOffset": 20, "byteLength": 4 }}
+                ],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+                    "sparse": {{
+                        "count": 1,
+                        "indices": {{ "bufferView": 1, "componentType": 5123 }},
+                        "values": {{ "bufferView": 2 }}
+                    }}
+                }}]
+            }}"#,
+            buffer.len(),
+            data_uri(&buffer)
+        );
+        let gltf = Gltf::from_slice(json.as_bytes()).unwrap();
+        let buffers = load_buffers(&gltf, &mut |_| None).unwrap();
+        let accessor = gltf.accessors().next().unwrap();
+        let values = read_floats(&accessor, &buffers).unwrap();
+        assert_eq!(values, [1.0, 2.0, 30.0, 4.0]);
+    }
+
+    #[test]
+    fn samples_animations() {
+        let channel = AnimationChannel {
+            node: 0,
+            property: AnimationProperty::Translation,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0, 2.0],
+            values: vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 2.0, 4.0, 0.0],
+        };
+        assert_eq!(channel.components(), 3);
+        assert_eq!(channel.sample(-1.0), [0.0, 0.0, 0.0]);
+        assert_eq!(channel.sample(0.5), [1.0, 0.0, 0.0]);
+        assert_eq!(channel.sample(1.5), [2.0, 2.0, 0.0]);
+        assert_eq!(channel.sample(5.0), [2.0, 4.0, 0.0]);
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let rotation = AnimationChannel {
+            property: AnimationProperty::Rotation,
+            times: vec![0.0, 1.0],
+            // identity, then half a turn around y
+            values: vec![0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0],
+            ..channel
+        };
+        let [x, y, z, w] = rotation.sample(0.5)[..] else {
+            panic!("rotations have four components");
+        };
+        assert!(x.abs() < 1e-6 && z.abs() < 1e-6);
+        assert!((y - half).abs() < 1e-6 && (w - half).abs() < 1e-6);
+    }
+}
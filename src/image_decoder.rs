@@ -0,0 +1,289 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::texture::TextureFormat;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+
+/// Things to do with the pixels after decoding
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Multiplies the color by alpha, for blending with `ONE, ONE_MINUS_SRC_ALPHA`
+    pub premultiply_alpha: bool,
+    /// Puts the last row first. Images are stored top row first,
+    /// while texture coordinates start from the bottom
+    pub flip_y: bool,
+    /// The colors are sRGB encoded, so the texture is `TextureFormat::Srgba8`
+    /// and the shaders read them linear. Leave it off for data like normal maps
+    pub srgb: bool,
+}
+
+/// Why an image could not be decoded, keeps the cause so it can be matched on
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Neither PNG nor JPEG
+    UnknownFormat,
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    /// The file is fine, but its pixel layout is not handled
+    Unsupported(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnknownFormat => write!(f, "Only PNG and JPEG images can be decoded"),
+            DecodeError::Png(error) => write!(f, "Can't decode PNG: {error}"),
+            DecodeError::Jpeg(error) => write!(f, "Can't decode JPEG: {error}"),
+            DecodeError::Unsupported(what) => write!(f, "Unsupported image: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Error::new(&error.to_string())
+    }
+}
+
+impl From<png::DecodingError> for DecodeError {
+    fn from(error: png::DecodingError) -> Self {
+        DecodeError::Png(error)
+    }
+}
+
+impl From<jpeg_decoder::Error> for DecodeError {
+    fn from(error: jpeg_decoder::Error) -> Self {
+        DecodeError::Jpeg(error)
+    }
+}
+
+/// Pixels ready for `Texture2D::from_decoded`,
+/// always four bytes per pixel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// `Rgba8`, or `Srgba8` when decoded with `DecodeOptions::srgb`
+    pub format: TextureFormat,
+    pub pixels: Vec<u8>,
+}
+
+/// Decodes a PNG or baseline JPEG file, telling them apart by the signature
+pub fn decode(bytes: &[u8], options: DecodeOptions) -> Result<DecodedImage, DecodeError> {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        decode_png(bytes, options)
+    } else if bytes.starts_with(JPEG_SIGNATURE) {
+        decode_jpeg(bytes, options)
+    } else {
+        Err(DecodeError::UnknownFormat)
+    }
+}
+
+/// Palette, grayscale and 16-bit images are converted to 8-bit RGBA
+pub fn decode_png(bytes: &[u8], options: DecodeOptions) -> Result<DecodedImage, DecodeError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => expand(&buffer, 3, |rgb| [rgb[0], rgb[1], rgb[2], 255]),
+        png::ColorType::GrayscaleAlpha => expand(&buffer, 2, |la| [la[0], la[0], la[0], la[1]]),
+        png::ColorType::Grayscale => expand(&buffer, 1, |l| [l[0], l[0], l[0], 255]),
+        png::ColorType::Indexed => {
+            return Err(DecodeError::Unsupported(
+                "PNG palette was not expanded".into(),
+            ));
+        }
+    };
+    Ok(finish(info.width, info.height, pixels, options))
+}
+
+/// Grayscale and RGB baseline or progressive JPEGs. CMYK files are rejected
+pub fn decode_jpeg(bytes: &[u8], options: DecodeOptions) -> Result<DecodedImage, DecodeError> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    let buffer = decoder.decode()?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| DecodeError::Unsupported("JPEG without a frame header".into()))?;
+
+    let pixels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => expand(&buffer, 3, |rgb| [rgb[0], rgb[1], rgb[2], 255]),
+        jpeg_decoder::PixelFormat::L8 => expand(&buffer, 1, |l| [l[0], l[0], l[0], 255]),
+        format => {
+            return Err(DecodeError::Unsupported(format!(
+                "JPEG pixel format {format:?}"
+            )));
+        }
+    };
+    Ok(finish(
+        info.width as u32,
+        info.height as u32,
+        pixels,
+        options,
+    ))
+}
+
+fn expand(pixels: &[u8], channels: usize, to_rgba: impl Fn(&[u8]) -> [u8; 4]) -> Vec<u8> {
+    pixels.chunks_exact(channels).flat_map(to_rgba).collect()
+}
+
+fn finish(width: u32, height: u32, mut pixels: Vec<u8>, options: DecodeOptions) -> DecodedImage {
+    if options.premultiply_alpha {
+        premultiply(&mut pixels, options.srgb);
+    }
+    if options.flip_y {
+        flip_rows(&mut pixels, width as usize * 4);
+    }
+    let format = match options.srgb {
+        true => TextureFormat::Srgba8,
+        false => TextureFormat::Rgba8,
+    };
+    DecodedImage {
+        width,
+        height,
+        format,
+        pixels,
+    }
+}
+
+/// sRGB colors are multiplied in linear space, since that's
+/// how the shaders will see them
+fn premultiply(pixels: &mut [u8], srgb: bool) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as f32 / 255.0;
+        for channel in &mut pixel[..3] {
+            let value = *channel as f32 / 255.0;
+            let premultiplied = match srgb {
+                true => linear_to_srgb(srgb_to_linear(value) * alpha),
+                false => value * alpha,
+            };
+            *channel = (premultiplied * 255.0).round() as u8;
+        }
+    }
+}
+
+fn flip_rows(pixels: &mut [u8], row_size: usize) {
+    let rows = pixels.len() / row_size;
+    for row in 0..rows / 2 {
+        let (top, bottom) = pixels.split_at_mut((rows - row - 1) * row_size);
+        top[row * row_size..(row + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(width: u32, height: u32, color: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_rgba_png() {
+        let data = [255, 0, 0, 255, 0, 255, 0, 128];
+        let bytes = encode_png(2, 1, png::ColorType::Rgba, &data);
+        let image = decode(&bytes, DecodeOptions::default()).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.format, TextureFormat::Rgba8);
+        assert_eq!(image.pixels, data);
+    }
+
+    #[test]
+    fn expands_grayscale_png() {
+        let bytes = encode_png(2, 1, png::ColorType::Grayscale, &[10, 20]);
+        let image = decode(&bytes, DecodeOptions::default()).unwrap();
+        assert_eq!(image.pixels, [10, 10, 10, 255, 20, 20, 20, 255]);
+    }
+
+    #[test]
+    fn flips_rows() {
+        let data = [1, 1, 1, 255, 2, 2, 2, 255, 3, 3, 3, 255];
+        let bytes = encode_png(1, 3, png::ColorType::Rgba, &data);
+        let options = DecodeOptions {
+            flip_y: true,
+            ..DecodeOptions::default()
+        };
+        let image = decode(&bytes, options).unwrap();
+        assert_eq!(image.pixels, [3, 3, 3, 255, 2, 2, 2, 255, 1, 1, 1, 255]);
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        let data = [200, 100, 0, 128, 255, 255, 255, 0];
+        let bytes = encode_png(2, 1, png::ColorType::Rgba, &data);
+        let options = DecodeOptions {
+            premultiply_alpha: true,
+            ..DecodeOptions::default()
+        };
+        let image = decode(&bytes, options).unwrap();
+        assert_eq!(image.pixels, [100, 50, 0, 128, 0, 0, 0, 0]);
+
+        let srgb = DecodeOptions {
+            srgb: true,
+            ..options
+        };
+        let image = decode(&bytes, srgb).unwrap();
+        assert_eq!(image.format, TextureFormat::Srgba8);
+        // half of the linear intensity is brighter than half of the sRGB value
+        assert!(image.pixels[0] > 100);
+        assert_eq!(&image.pixels[3..], [128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decodes_jpeg() {
+        let data = [200u8, 100, 50].repeat(16 * 16);
+        let mut bytes = vec![];
+        let encoder = jpeg_encoder::Encoder::new(&mut bytes, 100);
+        encoder
+            .encode(&data, 16, 16, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+
+        let image = decode(&bytes, DecodeOptions::default()).unwrap();
+        assert_eq!((image.width, image.height), (16, 16));
+        for pixel in image.pixels.chunks(4) {
+            for (decoded, original) in pixel.iter().zip(&data[..3]) {
+                assert!(decoded.abs_diff(*original) <= 3, "{pixel:?}");
+            }
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let error = decode(b"GIF89a", DecodeOptions::default()).unwrap_err();
+        assert!(matches!(error, DecodeError::UnknownFormat));
+        let error = decode(PNG_SIGNATURE, DecodeOptions::default()).unwrap_err();
+        assert!(matches!(error, DecodeError::Png(_)));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::BufferId;
+use crate::buffer_storage::BufferStorage;
+use crate::error::Error;
+use crate::pod::Pod;
+use crate::rendering_context::{BufferKind, RenderingContext};
+
+/// How many vertices 16-bit indices can address
+pub const MAX_U16_VERTICES: usize = 1 << 16;
+
+/// Width of every index in an element array buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+    U8,
+    U16,
+    /// Requires `Capabilities::element_index_uint`
+    U32,
+}
+
+impl IndexType {
+    /// The narrowest type that can hold `max_index`
+    pub fn for_max_index(max_index: u32) -> Self {
+        if max_index <= u8::MAX as u32 {
+            IndexType::U8
+        } else if max_index <= u16::MAX as u32 {
+            IndexType::U16
+        } else {
+            IndexType::U32
+        }
+    }
+
+    pub fn gl_type(self) -> u32 {
+        match self {
+            IndexType::U8 => WebGlRenderingContext::UNSIGNED_BYTE,
+            IndexType::U16 => WebGlRenderingContext::UNSIGNED_SHORT,
+            IndexType::U32 => WebGlRenderingContext::UNSIGNED_INT,
+        }
+    }
+
+    /// Bytes per index
+    pub fn size(self) -> usize {
+        match self {
+            IndexType::U8 => 1,
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        }
+    }
+}
+
+enum IndexStorage {
+    U8(BufferStorage<u8>),
+    U16(BufferStorage<u16>),
+    U32(BufferStorage<u32>),
+}
+
+/// Indices uploaded with the narrowest type that holds them
+pub struct IndexBuffer {
+    count: i32,
+    storage: IndexStorage,
+}
+
+impl IndexBuffer {
+    /// Fails when the indices need 32 bits but
+    /// `Capabilities::element_index_uint` is missing
+    pub fn new(ctx: &RenderingContext, indices: &[u32]) -> Result<Self, Error> {
+        let max_index = indices.iter().copied().max().unwrap_or(0);
+        let index_type = IndexType::for_max_index(max_index);
+        if index_type == IndexType::U32 && !ctx.capabilities().element_index_uint {
+            return Err(Error::new(&format!(
+                "Index {max_index} needs 32-bit indices, which require OES_element_index_uint"
+            )));
+        }
+
+        let storage = match index_type {
+            IndexType::U8 => IndexStorage::U8(upload(ctx, indices, |index| index as u8)?),
+            IndexType::U16 => IndexStorage::U16(upload(ctx, indices, |index| index as u16)?),
+            IndexType::U32 => IndexStorage::U32(upload(ctx, indices, |index| index)?),
+        };
+        Ok(IndexBuffer {
+            count: indices.len() as i32,
+            storage,
+        })
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        match self.storage {
+            IndexStorage::U8(_) => IndexType::U8,
+            IndexStorage::U16(_) => IndexType::U16,
+            IndexStorage::U32(_) => IndexType::U32,
+        }
+    }
+
+    pub fn count(&self) -> i32 {
+        self.count
+    }
+
+    pub fn buffer_id(&self) -> BufferId {
+        match &self.storage {
+            IndexStorage::U8(buffer) => buffer.buffer_id(),
+            IndexStorage::U16(buffer) => buffer.buffer_id(),
+            IndexStorage::U32(buffer) => buffer.buffer_id(),
+        }
+    }
+
+    /// Records the buffer in the bound vertex array, if there is one.
+    /// Uploads leave a bound vertex array alone
+    pub fn bind(&self, ctx: &RenderingContext) {
+        ctx.bind_buffer(BufferKind::ElementArrayBuffer, self.buffer_id());
+    }
+}
+
+fn upload<T: Pod>(
+    ctx: &RenderingContext,
+    indices: &[u32],
+    convert: impl Fn(u32) -> T,
+) -> Result<BufferStorage<T>, Error> {
+    let data = indices.iter().map(|&index| convert(index)).collect();
+    let mut buffer = BufferStorage::new(ctx, BufferKind::ElementArrayBuffer, data)?;
+    buffer.write_to_graphics_card(ctx);
+    Ok(buffer)
+}
+
+/// Part of a mesh that is small enough for 16-bit indices
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexChunk {
+    /// Original index of every vertex the chunk uses
+    pub vertices: Vec<u32>,
+    /// Indices into `vertices`
+    pub indices: Vec<u32>,
+}
+
+/// Splits a list of primitives, `primitive_size` indices each, into chunks
+/// using at most `max_vertices` vertices. Primitives are never cut in half
+pub fn split_indices(
+    indices: &[u32],
+    primitive_size: usize,
+    max_vertices: usize,
+) -> Vec<IndexChunk> {
+    let mut chunks = vec![];
+    let mut chunk = IndexChunk::default();
+    let mut local: HashMap<u32, u32> = HashMap::new();
+
+    for primitive in indices.chunks(primitive_size) {
+        let mut fresh: Vec<u32> = primitive
+            .iter()
+            .copied()
+            .filter(|index| !local.contains_key(index))
+            .collect();
+        fresh.sort_unstable();
+        fresh.dedup();
+        if chunk.vertices.len() + fresh.len() > max_vertices {
+            chunks.push(std::mem::take(&mut chunk));
+            local.clear();
+        }
+
+        for &index in primitive {
+            let next = chunk.vertices.len() as u32;
+            let local_index = *local.entry(index).or_insert_with(|| {
+                chunk.vertices.push(index);
+                next
+            });
+            chunk.indices.push(local_index);
+        }
+    }
+    if !chunk.indices.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_narrowest_type() {
+        assert_eq!(IndexType::for_max_index(0), IndexType::U8);
+        assert_eq!(IndexType::for_max_index(255), IndexType::U8);
+        assert_eq!(IndexType::for_max_index(256), IndexType::U16);
+        assert_eq!(IndexType::for_max_index(65535), IndexType::U16);
+        assert_eq!(IndexType::for_max_index(65536), IndexType::U32);
+    }
+
+    #[test]
+    fn splits_whole_primitives() {
+        let indices = [0, 1, 2, 2, 1, 3, 10, 11, 12, 3, 10, 0];
+        let chunks = split_indices(&indices, 3, 4);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].vertices, [0, 1, 2, 3]);
+        assert_eq!(chunks[0].indices, [0, 1, 2, 2, 1, 3]);
+        assert_eq!(chunks[1].vertices, [10, 11, 12]);
+        assert_eq!(chunks[2].vertices, [3, 10, 0]);
+        for chunk in &chunks {
+            assert!(chunk.vertices.len() <= 4);
+            assert_eq!(chunk.indices.len() % 3, 0);
+        }
+
+        let restored: Vec<u32> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.indices.iter().map(|&i| chunk.vertices[i as usize]))
+            .collect();
+        assert_eq!(restored, indices);
+    }
+}
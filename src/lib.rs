@@ -1,10 +1,37 @@
-mod array_traits;
-mod buffer_storage;
+pub mod backend;
+pub mod buffer_storage;
+pub mod camera;
+pub mod capabilities;
 #[macro_use]
-mod console;
-mod cube;
-mod dom_helpers;
-mod geometry;
-mod program;
-mod rendering_context;
-mod scene;
+pub mod console;
+pub mod cube;
+pub mod dom_helpers;
+pub mod error;
+pub mod event_listener;
+pub mod geometry;
+pub mod gl_object;
+pub mod gl_state;
+pub mod glsl_type;
+pub mod gltf_loader;
+pub mod image_decoder;
+pub mod index_buffer;
+pub mod light;
+pub mod mesh;
+pub mod obj_loader;
+pub mod orbit_controls;
+pub mod pod;
+pub mod program;
+pub mod recording_backend;
+pub mod render_stats;
+pub mod rendering_context;
+pub mod resize_observer;
+pub mod scene;
+pub mod scene_graph;
+pub mod scene_options;
+pub mod shapes;
+pub mod stats_overlay;
+pub mod texture;
+pub mod uniform;
+pub mod vertex_array;
+pub mod vertex_layout;
+pub mod webgl_backend;
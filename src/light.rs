@@ -0,0 +1,179 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+
+use crate::error::Error;
+use crate::program::Program;
+use crate::rendering_context::RenderingContext;
+use crate::uniform::UniformValue;
+
+/// How many lights of each kind the lit shaders support,
+/// matches the array sizes in `shaders/lit_frag.glsl`
+pub const MAX_LIGHTS: usize = 4;
+
+/// Light coming from far away, like the sun
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// The way the light travels, doesn't need to be normalized
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+}
+
+/// Light shining in every direction from a point,
+/// fading out completely at `range`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: Vector3<f32>,
+    pub range: f32,
+}
+
+/// A point light limited to a cone. It is at full strength within
+/// `inner_angle` of the direction, and fades out towards `outer_angle`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub range: f32,
+    /// Half angle of the cone, in radians
+    pub inner_angle: f32,
+    /// Half angle of the cone, in radians
+    pub outer_angle: f32,
+}
+
+/// All the lights on a scene. Lights are given in world space,
+/// and moved into view space when uploaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lights {
+    pub ambient: Vector3<f32>,
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+    pub spot: Vec<SpotLight>,
+}
+
+impl Default for Lights {
+    /// A dim ambient light and a white light from the top left
+    fn default() -> Self {
+        Lights {
+            ambient: Vector3::new(0.2, 0.2, 0.2),
+            directional: vec![DirectionalLight {
+                direction: Vector3::new(1.0, -1.0, -1.0),
+                color: Vector3::new(0.8, 0.8, 0.8),
+            }],
+            point: vec![],
+            spot: vec![],
+        }
+    }
+}
+
+fn check_count(kind: &str, count: usize) -> Result<(), Error> {
+    if count > MAX_LIGHTS {
+        return Err(Error::new(&format!(
+            "At most {MAX_LIGHTS} {kind} lights are supported, got {count}"
+        )));
+    }
+    Ok(())
+}
+
+fn to_array(vector: Vector3<f32>) -> [f32; 3] {
+    [vector.x, vector.y, vector.z]
+}
+
+/// Empty arrays are skipped, WebGL doesn't accept them
+fn set_array(
+    ctx: &RenderingContext,
+    program: &Program,
+    name: &str,
+    values: Vec<[f32; 3]>,
+) -> Result<(), Error> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    program.set_uniform(ctx, name, &UniformValue::Vec3Array(values))
+}
+
+fn set_floats(
+    ctx: &RenderingContext,
+    program: &Program,
+    name: &str,
+    values: Vec<f32>,
+) -> Result<(), Error> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    program.set_uniform(ctx, name, &UniformValue::FloatArray(values))
+}
+
+impl Lights {
+    pub fn new() -> Self {
+        Lights::default()
+    }
+
+    /// No lights at all, not even the ambient one
+    pub fn none() -> Self {
+        Lights {
+            ambient: Vector3::zeros(),
+            directional: vec![],
+            point: vec![],
+            spot: vec![],
+        }
+    }
+
+    /// Sets the light uniforms of a program using `shaders/lit_frag.glsl`.
+    /// The program should be in use
+    pub fn upload(
+        &self,
+        ctx: &RenderingContext,
+        program: &Program,
+        view: &Isometry3<f32>,
+    ) -> Result<(), Error> {
+        check_count("directional", self.directional.len())?;
+        check_count("point", self.point.len())?;
+        check_count("spot", self.spot.len())?;
+
+        let set = |name: &str, value: UniformValue| program.set_uniform(ctx, name, &value);
+        let count = |lights: usize| UniformValue::Int(lights as i32);
+        let direction = |direction: &Vector3<f32>| to_array(-(view * direction).normalize());
+        let position = |position: &Point3<f32>| to_array((view * position).coords);
+
+        set("uAmbientLight", self.ambient.into())?;
+
+        set("uDirectionalLightCount", count(self.directional.len()))?;
+        let lights = &self.directional;
+        let directions = lights.iter().map(|light| direction(&light.direction));
+        set_array(
+            ctx,
+            program,
+            "uDirectionalLightDirections",
+            directions.collect(),
+        )?;
+        let colors = lights.iter().map(|light| to_array(light.color));
+        set_array(ctx, program, "uDirectionalLightColors", colors.collect())?;
+
+        set("uPointLightCount", count(self.point.len()))?;
+        let lights = &self.point;
+        let positions = lights.iter().map(|light| position(&light.position));
+        set_array(ctx, program, "uPointLightPositions", positions.collect())?;
+        let colors = lights.iter().map(|light| to_array(light.color));
+        set_array(ctx, program, "uPointLightColors", colors.collect())?;
+        let ranges = lights.iter().map(|light| light.range).collect();
+        set_floats(ctx, program, "uPointLightRanges", ranges)?;
+
+        set("uSpotLightCount", count(self.spot.len()))?;
+        let lights = &self.spot;
+        let positions = lights.iter().map(|light| position(&light.position));
+        set_array(ctx, program, "uSpotLightPositions", positions.collect())?;
+        let directions = lights.iter().map(|light| direction(&light.direction));
+        set_array(ctx, program, "uSpotLightDirections", directions.collect())?;
+        let colors = lights.iter().map(|light| to_array(light.color));
+        set_array(ctx, program, "uSpotLightColors", colors.collect())?;
+        // range, then the cosines of the inner and outer angles
+        let parameters = lights.iter().map(|light| {
+            [
+                light.range,
+                light.inner_angle.cos(),
+                light.outer_angle.cos(),
+            ]
+        });
+        set_array(ctx, program, "uSpotLightParameters", parameters.collect())
+    }
+}
@@ -0,0 +1,398 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::{BufferId, ProgramId};
+use crate::error::Error;
+use crate::geometry::{Geometry, PrimitiveMode};
+use crate::glsl_type;
+use crate::index_buffer::{IndexBuffer, IndexType, MAX_U16_VERTICES, split_indices};
+use crate::pod::{Pod, as_bytes};
+use crate::program::Program;
+use crate::rendering_context::RenderingContext;
+use crate::vertex_array::{AttributeBinding, VertexArray};
+use crate::vertex_layout::{VertexBuffer, VertexLayout};
+
+/// Name of the attribute the geometry positions are bound to
+pub const POSITION_ATTRIBUTE: &str = "aVertexPosition";
+/// Name of the attribute the geometry normals are bound to
+pub const NORMAL_ATTRIBUTE: &str = "aVertexNormal";
+/// Name of the attribute the geometry texture coordinates are bound to
+pub const UV_ATTRIBUTE: &str = "aTextureCoord";
+/// Name of the attribute the geometry tangents are bound to
+pub const TANGENT_ATTRIBUTE: &str = "aVertexTangent";
+
+/// An attribute read once every `divisor` instances instead of once per vertex,
+/// from a buffer of tightly packed floats the caller keeps up to date.
+/// Matrices take one location per column
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceAttribute {
+    pub name: String,
+    pub buffer: BufferId,
+    /// Floats per location, the column size of matrices
+    pub components: i32,
+    pub divisor: u32,
+}
+
+impl InstanceAttribute {
+    /// One binding per location, none when the program doesn't read the attribute
+    fn bindings(&self, program: &Program) -> Vec<AttributeBinding> {
+        let Some(active) = program.attribute(&self.name) else {
+            return vec![];
+        };
+        let columns = glsl_type::attribute_slots(active.data_type);
+        let column_size = self.components * 4;
+        (0..columns)
+            .map(|column| AttributeBinding {
+                location: active.location + column as u32,
+                buffer: self.buffer,
+                components: self.components,
+                data_type: WebGlRenderingContext::FLOAT,
+                normalized: false,
+                stride: columns * column_size,
+                offset: column * column_size,
+                divisor: self.divisor,
+            })
+            .collect()
+    }
+}
+
+/// Vertices drawn with a single call. A mesh has more than one
+/// only when its indices don't fit into 16 bits and the context
+/// can't draw 32-bit indices
+struct MeshChunk {
+    /// Vertex of the whole mesh behind every chunk vertex,
+    /// `None` when the mesh is not split
+    vertices: Option<Vec<u32>>,
+    vertex_count: i32,
+    /// Empty when vertices are drawn in order
+    indices: Option<IndexBuffer>,
+    buffers: Vec<VertexBuffer>,
+}
+
+/// Vertices uploaded to the graphics card, in any number of buffers
+/// of named per-vertex attributes
+pub struct Mesh {
+    mode: PrimitiveMode,
+    vertex_count: i32,
+    chunks: Vec<MeshChunk>,
+    /// Shared by every chunk
+    instances: Vec<InstanceAttribute>,
+    /// One vertex array per chunk for every program the mesh was bound to
+    vertex_arrays: RefCell<HashMap<ProgramId, Vec<VertexArray>>>,
+    /// Program of the last `bind`, to switch between chunks while drawing
+    bound: Cell<Option<ProgramId>>,
+}
+
+impl Mesh {
+    /// Interleaves positions, normals, UVs and tangents into a single buffer
+    pub fn new(ctx: &RenderingContext, geometry: Geometry) -> Result<Self, Error> {
+        let (layout, vertices) = interleave(&geometry)?;
+        Self::from_vertices(ctx, geometry.mode, layout, &vertices, &geometry.indices)
+    }
+
+    /// Vertices of any `Pod` type, such as a `#[repr(C)]` struct, described by `layout`.
+    /// Without indices the vertices are drawn in order.
+    ///
+    /// Indices are uploaded as `u8`, `u16` or `u32`, whichever fits. Without
+    /// `Capabilities::element_index_uint` larger meshes are split into chunks
+    /// of at most 65536 vertices, which only works for lists of primitives
+    pub fn from_vertices<T: Pod>(
+        ctx: &RenderingContext,
+        mode: PrimitiveMode,
+        layout: VertexLayout,
+        vertices: &[T],
+        indices: &[u32],
+    ) -> Result<Self, Error> {
+        let vertex_count = layout.vertex_count(vertices)? as i32;
+        let max_index = indices.iter().copied().max().unwrap_or(0);
+        let needs_split = IndexType::for_max_index(max_index) == IndexType::U32
+            && !ctx.capabilities().element_index_uint;
+
+        let chunks = if needs_split {
+            let primitive_size = mode.primitive_size().ok_or_else(|| {
+                Error::new(&format!(
+                    "{mode:?} with more than {MAX_U16_VERTICES} vertices \
+                     can't be split without OES_element_index_uint",
+                ))
+            })?;
+            split_indices(indices, primitive_size, MAX_U16_VERTICES)
+                .into_iter()
+                .map(|chunk| {
+                    Ok(MeshChunk {
+                        vertex_count: chunk.vertices.len() as i32,
+                        indices: Some(IndexBuffer::new(ctx, &chunk.indices)?),
+                        vertices: Some(chunk.vertices),
+                        buffers: vec![],
+                    })
+                })
+                .collect::<Result<_, Error>>()?
+        } else {
+            let indices = match indices.is_empty() {
+                true => None,
+                false => Some(IndexBuffer::new(ctx, indices)?),
+            };
+            vec![MeshChunk {
+                vertices: None,
+                vertex_count,
+                indices,
+                buffers: vec![],
+            }]
+        };
+
+        let mut mesh = Mesh {
+            mode,
+            vertex_count,
+            chunks,
+            instances: vec![],
+            vertex_arrays: RefCell::new(HashMap::new()),
+            bound: Cell::new(None),
+        };
+        mesh.add_vertices(ctx, layout, vertices)?;
+        Ok(mesh)
+    }
+
+    /// Adds a buffer with more attributes of the same vertices. It replaces
+    /// earlier buffers whose attributes it all provides again, while
+    /// replacing just a part of an interleaved buffer is an error
+    pub fn add_vertices<T: Pod>(
+        &mut self,
+        ctx: &RenderingContext,
+        layout: VertexLayout,
+        vertices: &[T],
+    ) -> Result<(), Error> {
+        let vertex_count = layout.vertex_count(vertices)?;
+        if vertex_count != self.vertex_count as usize {
+            return Err(Error::new(&format!(
+                "Vertex buffer has {vertex_count} vertices, the mesh has {}",
+                self.vertex_count
+            )));
+        }
+
+        let mut replaced = vec![];
+        for (index, buffer) in self.chunks[0].buffers.iter().enumerate() {
+            let attributes = buffer.layout().attributes();
+            let provided: Vec<_> = attributes
+                .iter()
+                .filter(|attribute| layout.attribute(&attribute.name).is_some())
+                .collect();
+            if provided.len() == attributes.len() {
+                replaced.push(index);
+            } else if let Some(attribute) = provided.first() {
+                return Err(Error::new(&format!(
+                    "Attribute {} is part of an interleaved buffer, \
+                     which can only be replaced as a whole",
+                    attribute.name
+                )));
+            }
+        }
+
+        // recorded bindings point at the buffers being replaced
+        self.vertex_arrays.get_mut().clear();
+        let bytes = as_bytes(vertices);
+        let stride = layout.stride() as usize;
+        for chunk in &mut self.chunks {
+            for &index in replaced.iter().rev() {
+                chunk.buffers.remove(index);
+            }
+            let buffer = match &chunk.vertices {
+                Some(vertices) => {
+                    let gathered: Vec<u8> = vertices
+                        .iter()
+                        .flat_map(|&vertex| &bytes[vertex as usize * stride..][..stride])
+                        .copied()
+                        .collect();
+                    VertexBuffer::new(ctx, layout.clone(), &gathered)?
+                }
+                None => VertexBuffer::new(ctx, layout.clone(), vertices)?,
+            };
+            chunk.buffers.push(buffer);
+        }
+        Ok(())
+    }
+
+    /// Adds a buffer with a single attribute.
+    /// `size` is the number of floats per vertex, from 1 to 4
+    pub fn add_attribute(
+        &mut self,
+        ctx: &RenderingContext,
+        name: &str,
+        data: Vec<f32>,
+        size: i32,
+    ) -> Result<(), Error> {
+        if data.len() != self.vertex_count as usize * size as usize {
+            return Err(Error::new(&format!(
+                "Attribute {name} has {} floats, expected {} for {} vertices",
+                data.len(),
+                self.vertex_count * size,
+                self.vertex_count,
+            )));
+        }
+        self.add_vertices(ctx, VertexLayout::new().floats(name, size), &data)
+    }
+
+    /// Records a per-instance attribute into the vertex arrays, next to the
+    /// vertex attributes. Requires `Capabilities::instancing`
+    pub fn add_instance_attribute(
+        &mut self,
+        ctx: &RenderingContext,
+        attribute: InstanceAttribute,
+    ) -> Result<(), Error> {
+        if !ctx.capabilities().instancing {
+            return Err(Error::new(&format!(
+                "Instance attribute {} needs instancing",
+                attribute.name
+            )));
+        }
+        if attribute.divisor == 0 {
+            return Err(Error::new(&format!(
+                "Instance attribute {} has a divisor of 0",
+                attribute.name
+            )));
+        }
+        self.vertex_arrays.get_mut().clear();
+        self.instances
+            .retain(|instance| instance.name != attribute.name);
+        self.instances.push(attribute);
+        Ok(())
+    }
+
+    pub fn mode(&self) -> PrimitiveMode {
+        self.mode
+    }
+
+    /// Number of draw calls `draw` makes
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Binds the vertex arrays recorded for the program, recording them on first use
+    /// along with the instance attributes.
+    /// Attributes the program doesn't know about are skipped, ones the shader
+    /// declares with fewer components or as integers are an error
+    pub fn bind(&self, ctx: &RenderingContext, program: &Program) -> Result<(), Error> {
+        let id = program.compiled.id();
+        let mut vertex_arrays = self.vertex_arrays.borrow_mut();
+        let arrays = match vertex_arrays.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let arrays = self
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.vertex_array(ctx, program, &self.instances))
+                    .collect::<Result<_, Error>>()?;
+                entry.insert(arrays)
+            }
+        };
+        arrays[0].bind(ctx);
+        self.bound.set(Some(id));
+        Ok(())
+    }
+
+    /// Unbinds the vertex array, so its attributes don't leak into the next draw
+    pub fn unbind(&self, ctx: &RenderingContext, program: &Program) {
+        let id = program.compiled.id();
+        if let Some(arrays) = self.vertex_arrays.borrow().get(&id) {
+            arrays[0].unbind(ctx);
+        }
+        self.bound.set(None);
+    }
+
+    /// Expects the mesh to be bound
+    pub fn draw(&self, ctx: &RenderingContext) {
+        let mode = self.mode.gl_mode();
+        self.for_each_chunk(ctx, |chunk| match &chunk.indices {
+            Some(indices) => {
+                let data_type = indices.index_type().gl_type();
+                ctx.draw_elements(mode, indices.count(), data_type, 0);
+            }
+            None => ctx.draw_arrays(mode, 0, chunk.vertex_count),
+        });
+    }
+
+    /// Expects the mesh to be bound, requires `Capabilities::instancing`
+    pub fn draw_instanced(&self, ctx: &RenderingContext, instance_count: i32) {
+        let mode = self.mode.gl_mode();
+        self.for_each_chunk(ctx, |chunk| match &chunk.indices {
+            Some(indices) => {
+                let data_type = indices.index_type().gl_type();
+                ctx.draw_elements_instanced(mode, indices.count(), data_type, 0, instance_count);
+            }
+            None => ctx.draw_arrays_instanced(mode, 0, chunk.vertex_count, instance_count),
+        });
+    }
+
+    /// A single chunk is already bound, split meshes
+    /// bind the vertex array of every chunk before drawing it
+    fn for_each_chunk(&self, ctx: &RenderingContext, mut draw: impl FnMut(&MeshChunk)) {
+        if let [chunk] = self.chunks.as_slice() {
+            draw(chunk);
+            return;
+        }
+        let vertex_arrays = self.vertex_arrays.borrow();
+        let arrays = self.bound.get().and_then(|id| vertex_arrays.get(&id));
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if let Some(arrays) = arrays {
+                arrays[index].bind(ctx);
+            }
+            draw(chunk);
+        }
+    }
+}
+
+impl MeshChunk {
+    fn vertex_array(
+        &self,
+        ctx: &RenderingContext,
+        program: &Program,
+        instances: &[InstanceAttribute],
+    ) -> Result<VertexArray, Error> {
+        let mut bindings = vec![];
+        for buffer in &self.buffers {
+            bindings.extend(buffer.bindings(program)?);
+        }
+        for instance in instances {
+            bindings.extend(instance.bindings(program));
+        }
+        let indices = self.indices.as_ref().map(IndexBuffer::buffer_id);
+        VertexArray::new(ctx, bindings, indices)
+    }
+}
+
+/// One float vertex per position, followed by whichever
+/// of normals, UVs and tangents the geometry has
+fn interleave(geometry: &Geometry) -> Result<(VertexLayout, Vec<f32>), Error> {
+    let vertex_count = geometry.vertex_count();
+    let attributes = [
+        (POSITION_ATTRIBUTE, &geometry.vertices, 3),
+        (NORMAL_ATTRIBUTE, &geometry.normals, 3),
+        (UV_ATTRIBUTE, &geometry.uvs, 2),
+        (TANGENT_ATTRIBUTE, &geometry.tangents, 4),
+    ];
+    let attributes: Vec<_> = attributes
+        .into_iter()
+        .filter(|(name, data, _)| *name == POSITION_ATTRIBUTE || !data.is_empty())
+        .collect();
+
+    let mut layout = VertexLayout::new();
+    for &(name, data, size) in &attributes {
+        if data.len() != vertex_count * size {
+            return Err(Error::new(&format!(
+                "Attribute {name} has {} floats, expected {} for {vertex_count} vertices",
+                data.len(),
+                vertex_count * size,
+            )));
+        }
+        layout = layout.floats(name, size as i32);
+    }
+
+    let mut vertices = Vec::with_capacity(layout.stride() as usize / 4 * vertex_count);
+    for vertex in 0..vertex_count {
+        for &(_, data, size) in &attributes {
+            vertices.extend(&data[vertex * size..][..size]);
+        }
+    }
+    Ok((layout, vertices))
+}
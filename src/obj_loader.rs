@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use nalgebra::Vector3;
+
+use crate::error::Error;
+use crate::geometry::Geometry;
+
+/// A problem in an OBJ or MTL file, with the line it was found on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjError {
+    /// Counting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl ObjError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        ObjError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<ObjError> for Error {
+    fn from(error: ObjError) -> Self {
+        Error::new(&error.to_string())
+    }
+}
+
+/// Part of the model sharing a group name and a material
+pub struct ObjMesh {
+    /// From the last `o` or `g` statement, empty when there was none
+    pub name: String,
+    /// Name of the material set with `usemtl`, see `ObjMaterial::find`
+    pub material: Option<String>,
+    /// Triangles with positions and normals, and UVs when the faces have them
+    pub geometry: Geometry,
+}
+
+pub struct ObjModel {
+    /// Files listed by `mtllib`, to be loaded with `parse_mtl`
+    pub material_libraries: Vec<String>,
+    pub meshes: Vec<ObjMesh>,
+}
+
+/// A material from an MTL file. Colors are linear RGB
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub emissive: Vector3<f32>,
+    /// Specular exponent, `Ns`
+    pub shininess: f32,
+    /// 1 is fully opaque, from `d` or `Tr`
+    pub opacity: f32,
+    /// Texture file names, relative to the MTL file
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub opacity_map: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: Vector3::zeros(),
+            diffuse: Vector3::new(0.8, 0.8, 0.8),
+            specular: Vector3::zeros(),
+            emissive: Vector3::zeros(),
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            opacity_map: None,
+        }
+    }
+
+    pub fn find<'a>(materials: &'a [ObjMaterial], name: &str) -> Option<&'a ObjMaterial> {
+        materials.iter().find(|material| material.name == name)
+    }
+}
+
+/// Non-empty statements with their line numbers. Comments are dropped,
+/// and lines ending with `\` are joined with the next one
+fn statements(source: &str) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim_end();
+        let (number, mut text) = pending.take().unwrap_or((index + 1, String::new()));
+        if let Some(start) = line.strip_suffix('\\') {
+            text.push_str(start);
+            text.push(' ');
+            pending = Some((number, text));
+            continue;
+        }
+        text.push_str(line);
+        if !text.trim().is_empty() {
+            result.push((number, text));
+        }
+    }
+    result.extend(pending.filter(|(_, text)| !text.trim().is_empty()));
+    result
+}
+
+fn parse_float(line: usize, value: &str) -> Result<f32, ObjError> {
+    value
+        .parse()
+        .map_err(|_| ObjError::new(line, format!("'{value}' is not a number")))
+}
+
+/// Reads up to `N` numbers, the first `required` of them must be present
+fn parse_floats<const N: usize>(
+    line: usize,
+    values: &[&str],
+    required: usize,
+) -> Result<[f32; N], ObjError> {
+    if values.len() < required {
+        return Err(ObjError::new(
+            line,
+            format!("Expected {required} numbers, got {}", values.len()),
+        ));
+    }
+    let mut result = [0.0; N];
+    for (slot, value) in result.iter_mut().zip(values) {
+        *slot = parse_float(line, value)?;
+    }
+    Ok(result)
+}
+
+fn parse_color(line: usize, values: &[&str]) -> Result<Vector3<f32>, ObjError> {
+    // a single value means gray
+    if values.len() == 1 {
+        let value = parse_float(line, values[0])?;
+        return Ok(Vector3::new(value, value, value));
+    }
+    parse_floats::<3>(line, values, 3).map(Vector3::from)
+}
+
+/// Resolves a 1-based or negative (relative to the end) index
+fn resolve_index(line: usize, value: &str, count: usize, kind: &str) -> Result<usize, ObjError> {
+    let index: i64 = value
+        .parse()
+        .map_err(|_| ObjError::new(line, format!("'{value}' is not a {kind} index")))?;
+    let resolved = match index {
+        0 => None,
+        index if index > 0 => Some(index as usize - 1),
+        index => count.checked_sub(index.unsigned_abs() as usize),
+    };
+    match resolved {
+        Some(index) if index < count => Ok(index),
+        _ => Err(ObjError::new(
+            line,
+            format!("There is no {kind} {index}, only {count} are defined so far"),
+        )),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct Face {
+    mesh: usize,
+    /// 0 when smoothing is off
+    smoothing: u32,
+    corners: Vec<Corner>,
+}
+
+/// Where the normal of a vertex comes from, part of the key vertices are welded by
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalSource {
+    Given(usize),
+    Smooth(u32),
+    /// Index of the face
+    Flat(usize),
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Vector3<f32>>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<Vector3<f32>>,
+    faces: Vec<Face>,
+    /// Group name and material of every mesh
+    meshes: Vec<(String, Option<String>)>,
+    material_libraries: Vec<String>,
+}
+
+impl ObjData {
+    fn parse_corner(&self, line: usize, corner: &str) -> Result<Corner, ObjError> {
+        let mut parts = corner.split('/');
+        let position = parts.next().unwrap_or("");
+        let position = resolve_index(line, position, self.positions.len(), "vertex")?;
+        let uv = match parts.next() {
+            Some("") | None => None,
+            Some(uv) => Some(resolve_index(
+                line,
+                uv,
+                self.uvs.len(),
+                "texture coordinate",
+            )?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(normal) => Some(resolve_index(line, normal, self.normals.len(), "normal")?),
+        };
+        Ok(Corner {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    fn face_normal(&self, face: &Face) -> Vector3<f32> {
+        let points: Vec<_> = face
+            .corners
+            .iter()
+            .map(|corner| self.positions[corner.position])
+            .collect();
+        newell_normal(&points)
+    }
+}
+
+/// Parses an OBJ file into meshes, one for every group and material pair.
+/// Polygons are triangulated, and normals missing from the file are
+/// computed from the smoothing groups: smooth within a group, flat when off.
+/// `s on` is taken as group 1
+pub fn parse_obj(source: &str) -> Result<ObjModel, ObjError> {
+    let mut data = ObjData::default();
+    let mut mesh_indices: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group = String::new();
+    let mut material = None;
+    let mut smoothing = 0;
+
+    for (line, text) in statements(source) {
+        let mut words = text.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let values: Vec<&str> = words.collect();
+        match keyword {
+            "v" => {
+                // extra values are vertex colors, which are ignored
+                let [x, y, z] = parse_floats(line, &values, 3)?;
+                data.positions.push(Vector3::new(x, y, z));
+            }
+            "vt" => data.uvs.push(parse_floats(line, &values, 1)?),
+            "vn" => {
+                let [x, y, z] = parse_floats(line, &values, 3)?;
+                data.normals.push(Vector3::new(x, y, z));
+            }
+            "f" => {
+                if values.len() < 3 {
+                    return Err(ObjError::new(line, "A face needs at least 3 vertices"));
+                }
+                let corners = values
+                    .iter()
+                    .map(|corner| data.parse_corner(line, corner))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let key = (group.clone(), material.clone());
+                let next_mesh = data.meshes.len();
+                let mesh = *mesh_indices.entry(key.clone()).or_insert(next_mesh);
+                if mesh == next_mesh {
+                    data.meshes.push(key);
+                }
+                data.faces.push(Face {
+                    mesh,
+                    smoothing,
+                    corners,
+                });
+            }
+            "o" | "g" => group = values.join(" "),
+            "usemtl" => material = Some(values.join(" ")),
+            "mtllib" => {
+                let libraries = values.iter().map(|library| library.to_string());
+                data.material_libraries.extend(libraries);
+            }
+            "s" => {
+                smoothing = match values.first() {
+                    Some(&"off") | None => 0,
+                    Some(&"on") => 1,
+                    Some(value) => value.parse().map_err(|_| {
+                        ObjError::new(line, format!("'{value}' is not a smoothing group"))
+                    })?,
+                }
+            }
+            // lines, points, curves and render attributes are not supported
+            _ => {}
+        }
+    }
+
+    let meshes = build_meshes(&data);
+    Ok(ObjModel {
+        material_libraries: data.material_libraries,
+        meshes,
+    })
+}
+
+fn build_meshes(data: &ObjData) -> Vec<ObjMesh> {
+    let face_normals: Vec<_> = data
+        .faces
+        .iter()
+        .map(|face| data.face_normal(face))
+        .collect();
+
+    // area weighted, Newell normals are twice as long as the polygon area
+    let mut smooth_normals: HashMap<(usize, u32), Vector3<f32>> = HashMap::new();
+    for (face, normal) in data.faces.iter().zip(&face_normals) {
+        if face.smoothing == 0 {
+            continue;
+        }
+        for corner in face.corners.iter().filter(|corner| corner.normal.is_none()) {
+            *smooth_normals
+                .entry((corner.position, face.smoothing))
+                .or_insert_with(Vector3::zeros) += normal;
+        }
+    }
+
+    let mut builders: Vec<_> = data.meshes.iter().map(|_| MeshBuilder::default()).collect();
+    for (index, face) in data.faces.iter().enumerate() {
+        let builder = &mut builders[face.mesh];
+        let face_normal = face_normals[index];
+
+        let mut corner_indices = vec![];
+        for corner in &face.corners {
+            let (source, normal) = match (corner.normal, face.smoothing) {
+                (Some(normal), _) => (NormalSource::Given(normal), data.normals[normal]),
+                (None, 0) => (NormalSource::Flat(index), face_normal),
+                (None, group) => (
+                    NormalSource::Smooth(group),
+                    smooth_normals[&(corner.position, group)],
+                ),
+            };
+            let vertex = Vertex {
+                position: data.positions[corner.position],
+                uv: corner.uv.map(|uv| data.uvs[uv]),
+                normal: unit_or(normal, face_normal),
+            };
+            let key = (corner.position, corner.uv, source);
+            corner_indices.push(builder.weld(key, vertex));
+        }
+
+        let points: Vec<_> = face
+            .corners
+            .iter()
+            .map(|corner| data.positions[corner.position])
+            .collect();
+        for [a, b, c] in triangulate(&points, &face_normal) {
+            let triangle = [corner_indices[a], corner_indices[b], corner_indices[c]];
+            builder.indices.extend(triangle);
+        }
+    }
+
+    let meshes = data.meshes.iter().zip(builders);
+    let meshes = meshes.map(|((name, material), builder)| ObjMesh {
+        name: name.clone(),
+        material: material.clone(),
+        geometry: builder.into_geometry(),
+    });
+    meshes.collect()
+}
+
+struct Vertex {
+    position: Vector3<f32>,
+    uv: Option<[f32; 2]>,
+    normal: Vector3<f32>,
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    welded: HashMap<(usize, Option<usize>, NormalSource), u32>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Reuses an identical vertex when there is one
+    fn weld(&mut self, key: (usize, Option<usize>, NormalSource), vertex: Vertex) -> u32 {
+        if let Some(index) = self.welded.get(&key) {
+            return *index;
+        }
+        let index = self.vertices.len() as u32;
+        self.vertices.push(vertex);
+        self.welded.insert(key, index);
+        index
+    }
+
+    fn into_geometry(self) -> Geometry {
+        let has_uvs = self.vertices.iter().any(|vertex| vertex.uv.is_some());
+        let mut positions = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        for vertex in &self.vertices {
+            positions.extend(vertex.position.iter());
+            normals.extend(vertex.normal.iter());
+            if has_uvs {
+                uvs.extend(vertex.uv.unwrap_or([0.0, 0.0]));
+            }
+        }
+        Geometry::new(self.indices, positions)
+            .with_normals(normals)
+            .with_uvs(uvs)
+    }
+}
+
+fn unit_or(normal: Vector3<f32>, fallback: Vector3<f32>) -> Vector3<f32> {
+    normal
+        .try_normalize(f32::EPSILON)
+        .or_else(|| fallback.try_normalize(f32::EPSILON))
+        .unwrap_or_else(Vector3::z)
+}
+
+/// Normal of a polygon which might not be flat or convex,
+/// its length is twice the area
+fn newell_normal(points: &[Vector3<f32>]) -> Vector3<f32> {
+    let mut normal = Vector3::zeros();
+    for (index, current) in points.iter().enumerate() {
+        let next = points[(index + 1) % points.len()];
+        normal += current.cross(&next);
+    }
+    normal
+}
+
+/// Splits a polygon into triangles by clipping ears, keeping its winding.
+/// Works for concave polygons, falls back to a fan for degenerate ones
+fn triangulate(points: &[Vector3<f32>], normal: &Vector3<f32>) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // project onto the plane the polygon faces most
+    let axis = normal.iamax();
+    let flat: Vec<[f32; 2]> = points
+        .iter()
+        .map(|point| match axis {
+            0 => [point.y, point.z],
+            1 => [point.z, point.x],
+            _ => [point.x, point.y],
+        })
+        .collect();
+    let cross = |a: usize, b: usize, c: usize| {
+        let [a, b, c] = [flat[a], flat[b], flat[c]];
+        (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+    };
+    let orientation = normal[axis].signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = vec![];
+    let mut attempts = 0;
+    let mut index = 0;
+    while remaining.len() > 3 && attempts < remaining.len() {
+        let count = remaining.len();
+        let previous = remaining[(index + count - 1) % count];
+        let current = remaining[index % count];
+        let next = remaining[(index + 1) % count];
+
+        let convex = cross(previous, current, next) * orientation > 0.0;
+        let contains_other = remaining.iter().any(|&other| {
+            other != previous
+                && other != current
+                && other != next
+                && cross(previous, current, other) * orientation >= 0.0
+                && cross(current, next, other) * orientation >= 0.0
+                && cross(next, previous, other) * orientation >= 0.0
+        });
+        if convex && !contains_other {
+            triangles.push([previous, current, next]);
+            remaining.remove(index % count);
+            attempts = 0;
+        } else {
+            index += 1;
+            attempts += 1;
+        }
+        index %= remaining.len();
+    }
+
+    for pair in remaining[1..].windows(2) {
+        triangles.push([remaining[0], pair[0], pair[1]]);
+    }
+    triangles
+}
+
+/// Parses the materials of an MTL file
+pub fn parse_mtl(source: &str) -> Result<Vec<ObjMaterial>, ObjError> {
+    let mut materials: Vec<ObjMaterial> = vec![];
+    for (line, text) in statements(source) {
+        let mut words = text.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let values: Vec<&str> = words.collect();
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(&values.join(" ")));
+            continue;
+        }
+        let material = materials
+            .last_mut()
+            .ok_or_else(|| ObjError::new(line, format!("'{keyword}' before any newmtl")))?;
+        // texture options come first, the file name is last
+        let map = || values.last().map(|file| file.to_string());
+        match keyword {
+            "Ka" => material.ambient = parse_color(line, &values)?,
+            "Kd" => material.diffuse = parse_color(line, &values)?,
+            "Ks" => material.specular = parse_color(line, &values)?,
+            "Ke" => material.emissive = parse_color(line, &values)?,
+            "Ns" => material.shininess = parse_floats::<1>(line, &values, 1)?[0],
+            "d" => material.opacity = parse_floats::<1>(line, &values, 1)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats::<1>(line, &values, 1)?[0],
+            "map_Kd" => material.diffuse_map = map(),
+            "map_Ks" => material.specular_map = map(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_map = map(),
+            "map_d" => material.opacity_map = map(),
+            // illumination models, transmission and the rest are ignored
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welds_quads_into_triangles() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            f 1/1/1 2/2/1 3/3/1 4/4/1
+        ";
+        let model = parse_obj(source).unwrap();
+        let geometry = &model.meshes[0].geometry;
+        assert_eq!(geometry.vertex_count(), 4);
+        assert_eq!(geometry.indices, [3, 0, 1, 1, 2, 3]);
+        assert_eq!(geometry.uvs.len(), 8);
+        assert_eq!(&geometry.normals[..3], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn triangulates_concave_polygons() {
+        // an L shape, a fan from the first corner would go outside
+        let points = [[0, 0], [2, 0], [2, 1], [1, 1], [1, 2], [0, 2]]
+            .map(|[x, y]| Vector3::new(x as f32, y as f32, 0.0));
+        let normal = newell_normal(&points);
+        let triangles = triangulate(&points, &normal);
+        assert_eq!(triangles.len(), 4);
+        let area: f32 = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                (points[*b] - points[*a])
+                    .cross(&(points[*c] - points[*a]))
+                    .z
+            })
+            .sum();
+        // every triangle keeps the counter clockwise winding
+        assert_eq!(area, 2.0 * 3.0);
+    }
+
+    #[test]
+    fn computes_normals_from_smoothing_groups() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 0 0 1
+            s off
+            f 1 3 2
+            f 1 2 4
+            s 1
+            f 1 3 2
+            f 1 2 4
+        ";
+        let model = parse_obj(source).unwrap();
+        let geometry = &model.meshes[0].geometry;
+        // 3 + 3 flat vertices, then 4 smooth ones
+        assert_eq!(geometry.vertex_count(), 10);
+        assert!(geometry.uvs.is_empty());
+        let shared = &geometry.normals[6 * 3..7 * 3];
+        let expected = 1.0 / 2.0f32.sqrt();
+        assert!((shared[1] + expected).abs() < 1e-6);
+        assert!((shared[2] + expected).abs() < 1e-6);
+
+        // some exporters write `on` for a single group
+        let model = parse_obj(&source.replace("s 1", "s on")).unwrap();
+        assert_eq!(model.meshes[0].geometry.normals, geometry.normals);
+    }
+
+    #[test]
+    fn splits_groups_and_materials() {
+        let source = "
+            mtllib scene.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            g first
+            usemtl red
+            f -3 -2 -1
+            usemtl blue
+            f 1 2 3
+            g second
+            f 1 2 3
+            g first
+            usemtl red
+            f 3 2 1
+        ";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.material_libraries, ["scene.mtl"]);
+        let meshes: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.name.as_str(), mesh.material.as_deref()))
+            .collect();
+        assert_eq!(
+            meshes,
+            [
+                ("first", Some("red")),
+                ("first", Some("blue")),
+                ("second", Some("blue")),
+            ]
+        );
+        assert_eq!(model.meshes[0].geometry.indices.len(), 6);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let error = parse_obj("v 0 0 0\nv 1 0 0\n\nf 1 2 3").err().unwrap();
+        assert_eq!(error.line, 4);
+        let error = parse_obj("v 0 0 \\\n  zero").err().unwrap();
+        assert_eq!(error, ObjError::new(1, "'zero' is not a number"));
+        let error = parse_mtl("newmtl a\nKd 1 1").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn parses_materials() {
+        let source = "
+            newmtl shiny
+            Kd 1 0.5 0
+            Ks 1
+            Ns 64
+            Tr 0.25
+            map_Kd -s 2 2 1 textures/wood.png
+        ";
+        let materials = parse_mtl(source).unwrap();
+        let material = ObjMaterial::find(&materials, "shiny").unwrap();
+        assert_eq!(material.diffuse, Vector3::new(1.0, 0.5, 0.0));
+        assert_eq!(material.specular, Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(material.shininess, 64.0);
+        assert_eq!(material.opacity, 0.75);
+        assert_eq!(material.diffuse_map.as_deref(), Some("textures/wood.png"));
+    }
+}
@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::f32::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+use nalgebra::{Point3, Vector3};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Event, HtmlCanvasElement, MouseEvent, WheelEvent, Window};
+
+use crate::camera::Camera;
+use crate::event_listener::EventListener;
+
+/// Keeps the camera from flipping over the poles
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DragMode {
+    Rotate,
+    Pan,
+}
+
+/// Moves the camera on a sphere around a target point.
+/// Input accumulates into pending deltas, and every `update`
+/// applies a `damping_factor` share of them, so the motion eases out.
+/// Knows nothing about the DOM, see `attach` for the event wiring
+#[derive(Clone, Debug)]
+pub struct OrbitControls {
+    target: Point3<f32>,
+    radius: f32,
+    /// Rotation around the y axis, in radians
+    yaw: f32,
+    /// Elevation above the xz plane, in radians
+    pitch: f32,
+    yaw_delta: f32,
+    pitch_delta: f32,
+    /// Natural log of the radius scale still to apply
+    zoom_delta: f32,
+    pan_delta: Vector3<f32>,
+    drag: Option<(DragMode, f32, f32)>,
+    /// Share of the pending motion applied per update, 1 disables damping
+    pub damping_factor: f32,
+    /// Radians per pixel dragged
+    pub rotate_speed: f32,
+    /// Fraction of the distance to the target per pixel dragged
+    pub pan_speed: f32,
+    /// Zoom per unit of wheel delta
+    pub zoom_speed: f32,
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+impl OrbitControls {
+    pub fn new(eye: Point3<f32>, target: Point3<f32>) -> Self {
+        let mut controls = OrbitControls {
+            target,
+            radius: 1.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            yaw_delta: 0.0,
+            pitch_delta: 0.0,
+            zoom_delta: 0.0,
+            pan_delta: Vector3::zeros(),
+            drag: None,
+            damping_factor: 0.1,
+            rotate_speed: 0.005,
+            pan_speed: 0.0015,
+            zoom_speed: 0.001,
+            min_radius: 0.1,
+            max_radius: f32::INFINITY,
+        };
+        controls.look_at(eye, target);
+        controls
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        OrbitControls::new(camera.eye(), camera.target())
+    }
+
+    /// Jumps to the new position, dropping any pending motion
+    pub fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>) {
+        let offset = eye - target;
+        self.target = target;
+        self.radius = offset.norm();
+        self.yaw = offset.x.atan2(offset.z);
+        self.pitch = if self.radius > 0.0 {
+            (offset.y / self.radius).asin().clamp(-MAX_PITCH, MAX_PITCH)
+        } else {
+            0.0
+        };
+        self.stop();
+    }
+
+    /// Drops the pending motion, the camera stays where it is
+    pub fn stop(&mut self) {
+        self.yaw_delta = 0.0;
+        self.pitch_delta = 0.0;
+        self.zoom_delta = 0.0;
+        self.pan_delta = Vector3::zeros();
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let direction = Vector3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw);
+        self.target + direction * self.radius
+    }
+
+    /// Positive `dx` moves the camera to the left around the target,
+    /// positive `dy` moves it up
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.yaw_delta -= dx * self.rotate_speed;
+        self.pitch_delta += dy * self.rotate_speed;
+    }
+
+    /// Drags the scene along with the pointer, in screen pixels
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let right = Vector3::new(cos_yaw, 0.0, -sin_yaw);
+        let up = Vector3::new(-sin_pitch * sin_yaw, cos_pitch, -sin_pitch * cos_yaw);
+        let scale = self.radius * self.pan_speed;
+        self.pan_delta += (up * dy - right * dx) * scale;
+    }
+
+    /// Positive `delta` moves away from the target, as the wheel does
+    /// when scrolling down
+    pub fn zoom(&mut self, delta: f32) {
+        self.zoom_delta += delta * self.zoom_speed;
+    }
+
+    pub fn begin_drag(&mut self, mode: DragMode, x: f32, y: f32) {
+        self.drag = Some((mode, x, y));
+    }
+
+    /// Rotates or pans by the distance moved since the previous call,
+    /// does nothing unless a drag is in progress
+    pub fn drag_to(&mut self, x: f32, y: f32) {
+        let Some((mode, last_x, last_y)) = self.drag else {
+            return;
+        };
+        let (dx, dy) = (x - last_x, y - last_y);
+        match mode {
+            DragMode::Rotate => self.rotate(dx, dy),
+            DragMode::Pan => self.pan(dx, dy),
+        }
+        self.drag = Some((mode, x, y));
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Applies a share of the pending motion, returns false
+    /// when there was nothing left to apply
+    pub fn update(&mut self) -> bool {
+        let moving = self.yaw_delta.abs() > f32::EPSILON
+            || self.pitch_delta.abs() > f32::EPSILON
+            || self.zoom_delta.abs() > f32::EPSILON
+            || self.pan_delta.norm() > f32::EPSILON * self.radius;
+        if !moving {
+            self.stop();
+            return false;
+        }
+
+        let share = self.damping_factor.clamp(0.0, 1.0);
+        self.yaw += self.yaw_delta * share;
+        self.pitch = (self.pitch + self.pitch_delta * share).clamp(-MAX_PITCH, MAX_PITCH);
+        self.radius =
+            (self.radius * (self.zoom_delta * share).exp()).clamp(self.min_radius, self.max_radius);
+        self.target += self.pan_delta * share;
+
+        let rest = 1.0 - share;
+        self.yaw_delta *= rest;
+        self.pitch_delta *= rest;
+        self.zoom_delta *= rest;
+        self.pan_delta *= rest;
+        true
+    }
+
+    /// Moves the camera to the current position, keeping y up
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.look_at(self.eye(), self.target, Vector3::y());
+    }
+}
+
+/// Drag to rotate, right-drag or shift-drag to pan, wheel to zoom.
+/// Moves are listened for on the window, so dragging can leave the canvas.
+/// The listeners are removed when the returned values are dropped
+pub fn attach(
+    window: &Window,
+    canvas: &HtmlCanvasElement,
+    controls: &Rc<RefCell<OrbitControls>>,
+) -> Result<Vec<EventListener>, JsValue> {
+    let on_mouse_down = {
+        let controls = Rc::clone(controls);
+        move |event: Event| {
+            let event: &MouseEvent = event.unchecked_ref();
+            let mode = match event.button() {
+                0 if event.shift_key() => DragMode::Pan,
+                0 => DragMode::Rotate,
+                2 => DragMode::Pan,
+                _ => return,
+            };
+            event.prevent_default();
+            let (x, y) = (event.client_x() as f32, event.client_y() as f32);
+            controls.borrow_mut().begin_drag(mode, x, y);
+        }
+    };
+    let on_mouse_move = {
+        let controls = Rc::clone(controls);
+        move |event: Event| {
+            let event: &MouseEvent = event.unchecked_ref();
+            let (x, y) = (event.client_x() as f32, event.client_y() as f32);
+            controls.borrow_mut().drag_to(x, y);
+        }
+    };
+    let on_mouse_up = {
+        let controls = Rc::clone(controls);
+        move |_: Event| controls.borrow_mut().end_drag()
+    };
+    let on_wheel = {
+        let controls = Rc::clone(controls);
+        move |event: Event| {
+            let event: &WheelEvent = event.unchecked_ref();
+            event.prevent_default();
+            let delta = match event.delta_mode() {
+                WheelEvent::DOM_DELTA_LINE => event.delta_y() * 16.0,
+                WheelEvent::DOM_DELTA_PAGE => event.delta_y() * 400.0,
+                _ => event.delta_y(),
+            };
+            controls.borrow_mut().zoom(delta as f32);
+        }
+    };
+    // Right-drag would open the context menu otherwise
+    let on_context_menu = |event: Event| event.prevent_default();
+
+    Ok(vec![
+        EventListener::new(canvas, "mousedown", on_mouse_down)?,
+        EventListener::new(window, "mousemove", on_mouse_move)?,
+        EventListener::new(window, "mouseup", on_mouse_up)?,
+        EventListener::new(canvas, "wheel", on_wheel)?,
+        EventListener::new(canvas, "contextmenu", on_context_menu)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).norm() < 1e-3, "{a} != {b}");
+    }
+
+    fn settle(controls: &mut OrbitControls) {
+        while controls.update() {}
+    }
+
+    #[test]
+    fn eye_round_trips() {
+        let eye = Point3::new(3.0, 4.0, 5.0);
+        let target = Point3::new(1.0, 0.0, -1.0);
+        let controls = OrbitControls::new(eye, target);
+        assert_close(controls.eye(), eye);
+        assert_close(controls.target(), target);
+    }
+
+    #[test]
+    fn damping_eases_out_to_the_full_motion() {
+        let mut controls = OrbitControls::new(Point3::new(0.0, 0.0, 10.0), Point3::origin());
+        controls.rotate(-100.0, 0.0);
+        assert!(controls.update());
+        // a tenth of the half radian is applied right away
+        let first_step = controls.eye();
+        assert!(first_step.x > 0.0 && first_step.x < 10.0 * 0.05);
+
+        settle(&mut controls);
+        let angle = 100.0 * controls.rotate_speed;
+        let expected = Point3::new(10.0 * angle.sin(), 0.0, 10.0 * angle.cos());
+        assert_close(controls.eye(), expected);
+        assert!(!controls.update());
+    }
+
+    #[test]
+    fn pitch_is_clamped_at_the_poles() {
+        let mut controls = OrbitControls::new(Point3::new(0.0, 0.0, 10.0), Point3::origin());
+        controls.damping_factor = 1.0;
+        controls.rotate(0.0, 10_000.0);
+        controls.update();
+        let eye = controls.eye();
+        assert!(eye.y < 10.0 && eye.z > 0.0);
+        assert!((controls.radius() - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn zoom_scales_the_radius_within_limits() {
+        let mut controls = OrbitControls::new(Point3::new(0.0, 0.0, 10.0), Point3::origin());
+        controls.max_radius = 15.0;
+        controls.zoom(-1.0 / controls.zoom_speed);
+        settle(&mut controls);
+        assert!((controls.radius() - 10.0 / std::f32::consts::E).abs() < 1e-3);
+
+        controls.zoom(1e6);
+        settle(&mut controls);
+        assert_eq!(controls.radius(), 15.0);
+    }
+
+    #[test]
+    fn pan_moves_target_and_eye_together() {
+        let mut controls = OrbitControls::new(Point3::new(0.0, 0.0, 10.0), Point3::origin());
+        controls.damping_factor = 1.0;
+        controls.begin_drag(DragMode::Pan, 100.0, 100.0);
+        controls.drag_to(90.0, 100.0);
+        controls.end_drag();
+        controls.drag_to(0.0, 0.0);
+        controls.update();
+
+        let shift = 10.0 * 10.0 * controls.pan_speed;
+        assert_close(controls.target(), Point3::new(shift, 0.0, 0.0));
+        assert_close(controls.eye(), Point3::new(shift, 0.0, 10.0));
+    }
+}
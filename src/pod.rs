@@ -0,0 +1,78 @@
+use crate::index_buffer::IndexType;
+
+/// Plain old data, uploaded to buffers byte for byte.
+///
+/// # Safety
+///
+/// The type has to be `#[repr(C)]` or a primitive, without padding bytes,
+/// and valid for any bit pattern. Vertex structs made of `f32` fields
+/// and arrays qualify:
+///
+/// ```
+/// use webgl_engine::pod::Pod;
+///
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct Vertex {
+///     position: [f32; 3],
+///     uv: [f32; 2],
+/// }
+///
+/// unsafe impl Pod for Vertex {}
+/// ```
+pub unsafe trait Pod: Copy + 'static {
+    /// Set for the types an element array buffer can hold
+    const INDEX_TYPE: Option<IndexType> = None;
+}
+
+unsafe impl Pod for u8 {
+    const INDEX_TYPE: Option<IndexType> = Some(IndexType::U8);
+}
+
+unsafe impl Pod for u16 {
+    const INDEX_TYPE: Option<IndexType> = Some(IndexType::U16);
+}
+
+unsafe impl Pod for u32 {
+    const INDEX_TYPE: Option<IndexType> = Some(IndexType::U32);
+}
+
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// The memory of `data`, as the graphics card will see it
+pub fn as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    let len = std::mem::size_of_val(data);
+    // Pod types have no padding, so every byte is initialized
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Vertex {
+        position: [f32; 2],
+        color: [u8; 4],
+    }
+
+    unsafe impl Pod for Vertex {}
+
+    #[test]
+    fn vertex_structs_keep_their_layout() {
+        let vertices = [Vertex {
+            position: [1.0, -2.0],
+            color: [1, 2, 3, 4],
+        }];
+        let bytes = as_bytes(&vertices);
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(bytes[..4], 1.0f32.to_ne_bytes());
+        assert_eq!(bytes[4..8], (-2.0f32).to_ne_bytes());
+        assert_eq!(bytes[8..], [1, 2, 3, 4]);
+    }
+}
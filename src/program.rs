@@ -1,16 +1,14 @@
-use js_sys::Error;
-use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+use std::collections::HashMap;
 
-use crate::rendering_context::RenderingContext;
+use web_sys::WebGlRenderingContext;
 
-pub enum AttributeKind {
-    Vertex,
-    Color,
-}
-pub enum UniformKind {
-    Projection,
-    ModelView,
-}
+use crate::backend::UniformLocationId;
+use crate::error::Error;
+use crate::gl_object::OwnedProgram;
+use crate::glsl_type;
+use crate::rendering_context::RenderingContext;
+use crate::texture::Texture2D;
+use crate::uniform::UniformValue;
 
 pub struct AttributeOptions {
     pub size: i32,
@@ -30,52 +28,226 @@ impl AttributeOptions {
             offset: 0,
         }
     }
+
+    /// A mat3 attribute takes three consecutive locations, one vec3 column each.
+    /// Offset is given for the first column
+    pub fn matrix3_columns() -> Self {
+        AttributeOptions {
+            stride: 9 * 4,
+            ..Self::floats_with_size(3)
+        }
+    }
+
+    /// A mat4 attribute takes four consecutive locations, one vec4 column each.
+    /// Offset is given for the first column
+    pub fn matrix4_columns() -> Self {
+        AttributeOptions {
+            stride: 16 * 4,
+            ..Self::floats_with_size(4)
+        }
+    }
 }
 
-pub struct ProgramAttribute {
-    pub location: i32,
-    pub options: AttributeOptions,
+pub struct ActiveAttribute {
+    pub location: u32,
+    /// GL type enum such as `FLOAT_VEC3`
+    pub data_type: u32,
 }
 
-pub struct ProgramAttributesList {
-    pub vertices: ProgramAttribute,
-    pub colors: ProgramAttribute,
+pub struct ActiveUniform {
+    pub location: UniformLocationId,
+    /// GL type enum such as `FLOAT_MAT4`
+    pub data_type: u32,
+    /// Number of elements for arrays, 1 otherwise
+    pub size: i32,
 }
 
-pub struct ProgramUniformsLocations {
-    pub projection: WebGlUniformLocation,
-    pub model_view: WebGlUniformLocation,
+/// Arrays are reported as `name[0]`, they are stored by their plain name
+fn strip_array_suffix(name: &str) -> &str {
+    name.strip_suffix("[0]").unwrap_or(name)
 }
 
 pub struct Program {
-    pub compiled: WebGlProgram,
-    pub attributes: ProgramAttributesList,
-    pub uniform_locations: ProgramUniformsLocations,
+    pub compiled: OwnedProgram,
+    pub attributes: HashMap<String, ActiveAttribute>,
+    pub uniforms: HashMap<String, ActiveUniform>,
 }
 
 impl Program {
     pub fn new(ctx: &RenderingContext, vert_src: &str, frag_src: &str) -> Result<Self, Error> {
-        let compiled_program = ctx.create_program(vert_src, frag_src)?;
+        let compiled = ctx.create_program(vert_src, frag_src)?;
+        let program = compiled.id();
 
-        let vertices = ProgramAttribute {
-            location: ctx.get_attrib_location(&compiled_program, "aVertexPosition"),
-            options: AttributeOptions::floats_with_size(3),
-        };
-        let colors = ProgramAttribute {
-            location: ctx.get_attrib_location(&compiled_program, "aVertexColor"),
-            options: AttributeOptions::floats_with_size(4),
-        };
+        let mut attributes = HashMap::new();
+        for index in 0..ctx.gl.get_active_attrib_count(program) {
+            let Some(info) = ctx.gl.get_active_attrib(program, index) else {
+                continue;
+            };
+            let location = ctx.get_attrib_location(program, &info.name);
+            if location < 0 {
+                // built-ins such as gl_VertexID have no location
+                continue;
+            }
+            let attribute = ActiveAttribute {
+                location: location as u32,
+                data_type: info.data_type,
+            };
+            attributes.insert(info.name, attribute);
+        }
 
-        let projection = ctx.get_uniform_location(&compiled_program, "uProjectionMatrix")?;
-        let model_view = ctx.get_uniform_location(&compiled_program, "uModelViewMatrix")?;
+        let mut uniforms = HashMap::new();
+        for index in 0..ctx.gl.get_active_uniform_count(program) {
+            let Some(info) = ctx.gl.get_active_uniform(program, index) else {
+                continue;
+            };
+            let uniform = ActiveUniform {
+                location: ctx.get_uniform_location(program, &info.name)?,
+                data_type: info.data_type,
+                size: info.size,
+            };
+            uniforms.insert(strip_array_suffix(&info.name).to_owned(), uniform);
+        }
 
         Ok(Program {
-            compiled: compiled_program,
-            attributes: ProgramAttributesList { vertices, colors },
-            uniform_locations: ProgramUniformsLocations {
-                projection,
-                model_view,
-            },
+            compiled,
+            attributes,
+            uniforms,
         })
     }
+
+    /// `None` if the shader doesn't declare the attribute,
+    /// or the driver optimized it out
+    pub fn attribute(&self, name: &str) -> Option<&ActiveAttribute> {
+        self.attributes.get(name)
+    }
+
+    pub fn uniform(&self, name: &str) -> Result<&ActiveUniform, Error> {
+        self.uniforms
+            .get(name)
+            .ok_or_else(|| Error::new(&format!("No active uniform named {name}")))
+    }
+
+    /// Checks the value against the type declared in the shader,
+    /// then sends it. The program has to be in use
+    pub fn set_uniform(
+        &self,
+        ctx: &RenderingContext,
+        name: &str,
+        value: &UniformValue,
+    ) -> Result<(), Error> {
+        let uniform = self.uniform(name)?;
+
+        if !glsl_type::is_settable_uniform(uniform.data_type) {
+            return Err(Error::new(&format!(
+                "Uniform {name} is declared as {}, an unsupported uniform type",
+                glsl_type::name(uniform.data_type),
+            )));
+        }
+        let type_matches = if value.is_sampler() {
+            glsl_type::is_sampler(uniform.data_type)
+        } else {
+            value.data_type() == uniform.data_type
+        };
+        if !type_matches {
+            return Err(Error::new(&format!(
+                "Uniform {name} is declared as {}, got {}",
+                glsl_type::name(uniform.data_type),
+                glsl_type::name(value.data_type()),
+            )));
+        }
+        if value.is_empty() {
+            return Err(Error::new(&format!("Uniform {name} got an empty array")));
+        }
+        if value.len() > uniform.size as usize {
+            return Err(Error::new(&format!(
+                "Uniform {name} holds {} elements, got {}",
+                uniform.size,
+                value.len(),
+            )));
+        }
+
+        ctx.upload_uniform(value, uniform.location);
+        Ok(())
+    }
+
+    /// Binds the texture to `unit` and points the sampler uniform at it.
+    /// The program should be in use
+    pub fn set_texture(
+        &self,
+        ctx: &RenderingContext,
+        name: &str,
+        texture: &Texture2D,
+        unit: u32,
+    ) -> Result<(), Error> {
+        texture.bind(ctx, unit);
+        self.set_uniform(ctx, name, &UniformValue::Sampler(unit as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_backend::RecordingBackend;
+
+    const VERTEX: &str = "
+        #version 300 es
+        uniform mat4 uModel;
+        uniform vec3 uLights[4];
+        uniform bvec2 uFlags[2];
+        uniform uint uCount;
+        uniform mat2x3 uSkew;
+        void main() {}
+    ";
+    const FRAGMENT: &str = "#version 300 es\nuniform sampler2D uTexture;\nvoid main() {}";
+
+    fn program() -> (RenderingContext, Program) {
+        let ctx = RenderingContext::new(Box::new(RecordingBackend::new()));
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        (ctx, program)
+    }
+
+    fn set_error(name: &str, value: UniformValue) -> String {
+        let (ctx, program) = program();
+        let error = program.set_uniform(&ctx, name, &value).unwrap_err();
+        error.message().to_owned()
+    }
+
+    #[test]
+    fn sets_matching_values() {
+        let (ctx, program) = program();
+        let lights = UniformValue::Vec3Array(vec![[0.0; 3]; 2]);
+        program.set_uniform(&ctx, "uLights", &lights).unwrap();
+        let flags = UniformValue::BVec2Array(vec![[true, false]; 2]);
+        program.set_uniform(&ctx, "uFlags", &flags).unwrap();
+        let unit = UniformValue::Sampler(1);
+        program.set_uniform(&ctx, "uTexture", &unit).unwrap();
+        assert_eq!(ctx.counters().uniform_uploads, 3);
+    }
+
+    #[test]
+    fn rejects_type_mismatch() {
+        let message = set_error("uModel", UniformValue::Mat3([0.0; 9]));
+        assert_eq!(message, "Uniform uModel is declared as mat4, got mat3");
+        let message = set_error("uModel", UniformValue::Sampler(0));
+        assert_eq!(message, "Uniform uModel is declared as mat4, got sampler2D");
+    }
+
+    #[test]
+    fn rejects_oversized_and_empty_arrays() {
+        let message = set_error("uLights", UniformValue::Vec3Array(vec![[0.0; 3]; 5]));
+        assert_eq!(message, "Uniform uLights holds 4 elements, got 5");
+        let message = set_error("uLights", UniformValue::Vec3Array(vec![]));
+        assert_eq!(message, "Uniform uLights got an empty array");
+    }
+
+    #[test]
+    fn rejects_unsupported_types() {
+        let message = set_error("uCount", UniformValue::Int(1));
+        assert_eq!(
+            message,
+            "Uniform uCount is declared as uint, an unsupported uniform type"
+        );
+        let message = set_error("uSkew", UniformValue::Mat2([0.0; 4]));
+        assert!(message.contains("mat2x3"));
+    }
 }
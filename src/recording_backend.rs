@@ -0,0 +1,896 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use web_sys::WebGlRenderingContext;
+
+use wasm_bindgen::JsValue;
+
+use crate::backend::{
+    ActiveInfo, Backend, BufferId, ImageSource, ProgramId, ShaderId, TextureId, TextureUpload,
+    UniformLocationId, VertexArrayId,
+};
+use crate::capabilities::Capabilities;
+use crate::glsl_type;
+
+/// A single call made against the `RecordingBackend`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    CreateBuffer(BufferId),
+    BindBuffer {
+        target: u32,
+        buffer: Option<BufferId>,
+    },
+    BufferData {
+        target: u32,
+        data: Vec<u8>,
+        usage: u32,
+    },
+    BufferDataSize {
+        target: u32,
+        size: i32,
+        usage: u32,
+    },
+    BufferSubData {
+        target: u32,
+        offset: i32,
+        data: Vec<u8>,
+    },
+    DeleteBuffer(BufferId),
+    CreateShader {
+        shader: ShaderId,
+        shader_type: u32,
+    },
+    ShaderSource {
+        shader: ShaderId,
+        source: String,
+    },
+    CompileShader(ShaderId),
+    DeleteShader(ShaderId),
+    CreateProgram(ProgramId),
+    AttachShader {
+        program: ProgramId,
+        shader: ShaderId,
+    },
+    LinkProgram(ProgramId),
+    UseProgram(Option<ProgramId>),
+    DeleteProgram(ProgramId),
+    GetAttribLocation {
+        program: ProgramId,
+        name: String,
+    },
+    GetUniformLocation {
+        program: ProgramId,
+        name: String,
+    },
+    Uniformfv {
+        location: UniformLocationId,
+        components: u32,
+        data: Vec<f32>,
+    },
+    Uniformiv {
+        location: UniformLocationId,
+        components: u32,
+        data: Vec<i32>,
+    },
+    UniformMatrixfv {
+        location: UniformLocationId,
+        columns: u32,
+        transpose: bool,
+        data: Vec<f32>,
+    },
+    VertexAttribPointer {
+        index: u32,
+        size: i32,
+        data_type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    },
+    EnableVertexAttribArray(u32),
+    DisableVertexAttribArray(u32),
+    VertexAttrib4fv {
+        index: u32,
+        values: Vec<f32>,
+    },
+    VertexAttribDivisor {
+        index: u32,
+        divisor: u32,
+    },
+    CreateVertexArray(VertexArrayId),
+    BindVertexArray(Option<VertexArrayId>),
+    DeleteVertexArray(VertexArrayId),
+    CreateTexture(TextureId),
+    ActiveTexture(u32),
+    BindTexture {
+        target: u32,
+        texture: Option<TextureId>,
+    },
+    TexImage2D {
+        upload: TextureUpload,
+        width: i32,
+        height: i32,
+        pixels: Vec<u8>,
+    },
+    /// The source itself is a JS object and isn't kept
+    TexImage2DFromSource {
+        upload: TextureUpload,
+    },
+    TexParameteri {
+        target: u32,
+        pname: u32,
+        param: i32,
+    },
+    GenerateMipmap(u32),
+    PixelStorei {
+        pname: u32,
+        param: i32,
+    },
+    DeleteTexture(TextureId),
+    Viewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    ClearColor([f32; 4]),
+    ClearDepth(f32),
+    Enable(u32),
+    Disable(u32),
+    DepthFunc(u32),
+    DepthMask(bool),
+    BlendFunc {
+        source: u32,
+        destination: u32,
+    },
+    CullFace(u32),
+    Clear(u32),
+    DrawArrays {
+        mode: u32,
+        first: i32,
+        count: i32,
+    },
+    DrawArraysInstanced {
+        mode: u32,
+        first: i32,
+        count: i32,
+        instance_count: i32,
+    },
+    DrawElements {
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+    },
+    DrawElementsInstanced {
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+        instance_count: i32,
+    },
+}
+
+pub type CallLog = Rc<RefCell<Vec<Call>>>;
+
+const PRECISION_QUALIFIERS: &[&str] = &["lowp", "mediump", "highp", "flat", "smooth", "invariant"];
+
+struct Declaration {
+    info: ActiveInfo,
+    location: Option<i32>,
+}
+
+/// Takes `uniform` and `attribute` (`in` in vertex shaders) declarations
+/// out of a single statement, e.g. `uniform highp vec3 uA, uB[2]`
+fn parse_statement(statement: &str, is_vertex: bool) -> Option<(bool, Vec<Declaration>)> {
+    let mut statement = statement.trim();
+    let mut location = None;
+    if let Some(rest) = statement.strip_prefix("layout") {
+        let (layout, rest) = rest.split_once(')')?;
+        location = layout
+            .split(',')
+            .filter_map(|item| item.split_once('='))
+            .find(|(key, _)| {
+                key.trim_matches(|c: char| c == '(' || c.is_whitespace()) == "location"
+            })
+            .and_then(|(_, value)| value.trim().parse().ok());
+        statement = rest;
+    }
+
+    let mut tokens = statement
+        .split_whitespace()
+        .filter(|token| !PRECISION_QUALIFIERS.contains(token));
+    let is_uniform = match tokens.next()? {
+        "uniform" => true,
+        "attribute" if is_vertex => false,
+        "in" if is_vertex => false,
+        _ => return None,
+    };
+    let data_type = glsl_type::from_name(tokens.next()?)?;
+    let names: String = tokens.collect();
+
+    let declarations = names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let (name, size) = match name.split_once('[') {
+                Some((base, size)) => (
+                    // arrays are reported by their first element, as WebGL does
+                    format!("{base}[0]"),
+                    size.trim_end_matches(']').parse().unwrap_or(1),
+                ),
+                None => (name.to_owned(), 1),
+            };
+            Declaration {
+                info: ActiveInfo {
+                    name,
+                    size,
+                    data_type,
+                },
+                location,
+            }
+        })
+        .collect();
+    Some((is_uniform, declarations))
+}
+
+/// Splits the source into statements, dropping comments, preprocessor
+/// lines and anything before an opening or closing brace
+fn parse_declarations(source: &str, is_vertex: bool) -> (Vec<Declaration>, Vec<Declaration>) {
+    let code: Vec<&str> = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+    let code = code.join("\n");
+
+    let mut attributes = vec![];
+    let mut uniforms = vec![];
+    for statement in code.split(';') {
+        let statement = statement.rsplit(['{', '}']).next().unwrap_or_default();
+        if let Some((is_uniform, declarations)) = parse_statement(statement, is_vertex) {
+            if is_uniform {
+                uniforms.extend(declarations);
+            } else {
+                attributes.extend(declarations);
+            }
+        }
+    }
+    (attributes, uniforms)
+}
+
+struct LinkedProgram {
+    /// Along with their locations
+    attributes: Vec<(ActiveInfo, i32)>,
+    uniforms: Vec<ActiveInfo>,
+}
+
+impl LinkedProgram {
+    fn new(shaders: &[(u32, String)]) -> Self {
+        let mut attributes = vec![];
+        let mut uniforms: Vec<ActiveInfo> = vec![];
+        for (shader_type, source) in shaders {
+            let is_vertex = *shader_type == WebGlRenderingContext::VERTEX_SHADER;
+            let (shader_attributes, shader_uniforms) = parse_declarations(source, is_vertex);
+            attributes.extend(shader_attributes);
+            for uniform in shader_uniforms {
+                // both stages may declare the same uniform
+                if !uniforms.iter().any(|known| known.name == uniform.info.name) {
+                    uniforms.push(uniform.info);
+                }
+            }
+        }
+
+        // matrices take a location per column, explicit ones included
+        let slots = |info: &ActiveInfo| glsl_type::attribute_slots(info.data_type);
+        let mut used: Vec<i32> = attributes
+            .iter()
+            .filter_map(|a| {
+                a.location
+                    .map(|location| location..location + slots(&a.info))
+            })
+            .flatten()
+            .collect();
+        let attributes = attributes
+            .into_iter()
+            .map(|Declaration { info, location }| {
+                let location = location.unwrap_or_else(|| {
+                    // the first run of free locations wide enough
+                    let columns = slots(&info);
+                    let free =
+                        |start: &i32| (*start..start + columns).all(|slot| !used.contains(&slot));
+                    let location = (0..).find(free).unwrap_or_default();
+                    used.extend(location..location + columns);
+                    location
+                });
+                (info, location)
+            })
+            .collect();
+
+        LinkedProgram {
+            attributes,
+            uniforms,
+        }
+    }
+}
+
+/// In-memory backend which records every call instead of rendering,
+/// so the engine can run and be inspected without a browser.
+///
+/// Shaders always compile and programs always link. Attributes and uniforms
+/// are found by reading declarations from the shader sources, so unlike
+/// a real driver it reports them even when they are unused.
+/// It reports full WebGL2 capabilities unless told otherwise.
+pub struct RecordingBackend {
+    capabilities: Capabilities,
+    calls: CallLog,
+    next_id: Cell<u32>,
+    /// Type and source of every shader
+    shaders: RefCell<HashMap<ShaderId, (u32, String)>>,
+    attached: RefCell<HashMap<ProgramId, Vec<ShaderId>>>,
+    linked: RefCell<HashMap<ProgramId, LinkedProgram>>,
+    uniform_locations: RefCell<HashMap<(ProgramId, String), UniformLocationId>>,
+}
+
+impl Default for RecordingBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::with_capabilities(Capabilities::webgl2())
+    }
+
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        RecordingBackend {
+            capabilities,
+            calls: Rc::new(RefCell::new(vec![])),
+            next_id: Cell::new(1),
+            shaders: RefCell::new(HashMap::new()),
+            attached: RefCell::new(HashMap::new()),
+            linked: RefCell::new(HashMap::new()),
+            uniform_locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Shared handle to the recorded calls, which stays valid
+    /// after the backend is moved into a `RenderingContext`
+    pub fn calls(&self) -> CallLog {
+        Rc::clone(&self.calls)
+    }
+
+    fn record(&self, call: Call) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn next_id(&self) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+impl Backend for RecordingBackend {
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        let buffer = BufferId(self.next_id());
+        self.record(Call::CreateBuffer(buffer));
+        Some(buffer)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        self.record(Call::BindBuffer { target, buffer });
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.record(Call::BufferData {
+            target,
+            data: data.to_vec(),
+            usage,
+        });
+    }
+
+    fn buffer_data_size(&self, target: u32, size: i32, usage: u32) {
+        self.record(Call::BufferDataSize {
+            target,
+            size,
+            usage,
+        });
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        self.record(Call::BufferSubData {
+            target,
+            offset,
+            data: data.to_vec(),
+        });
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        self.record(Call::DeleteBuffer(buffer));
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        let shader = ShaderId(self.next_id());
+        self.shaders
+            .borrow_mut()
+            .insert(shader, (shader_type, String::new()));
+        self.record(Call::CreateShader {
+            shader,
+            shader_type,
+        });
+        Some(shader)
+    }
+
+    fn shader_source(&self, shader: ShaderId, source: &str) {
+        if let Some((_, shader_source)) = self.shaders.borrow_mut().get_mut(&shader) {
+            *shader_source = source.to_owned();
+        }
+        self.record(Call::ShaderSource {
+            shader,
+            source: source.to_owned(),
+        });
+    }
+
+    fn compile_shader(&self, shader: ShaderId) {
+        self.record(Call::CompileShader(shader));
+    }
+
+    fn get_shader_compile_status(&self, _shader: ShaderId) -> bool {
+        true
+    }
+
+    fn get_shader_info_log(&self, _shader: ShaderId) -> Option<String> {
+        None
+    }
+
+    fn delete_shader(&self, shader: ShaderId) {
+        self.record(Call::DeleteShader(shader));
+        self.shaders.borrow_mut().remove(&shader);
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        let program = ProgramId(self.next_id());
+        self.record(Call::CreateProgram(program));
+        Some(program)
+    }
+
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId) {
+        self.record(Call::AttachShader { program, shader });
+        self.attached
+            .borrow_mut()
+            .entry(program)
+            .or_default()
+            .push(shader);
+    }
+
+    fn link_program(&self, program: ProgramId) {
+        self.record(Call::LinkProgram(program));
+        let shaders: Vec<(u32, String)> = {
+            let sources = self.shaders.borrow();
+            let attached = self.attached.borrow();
+            attached
+                .get(&program)
+                .into_iter()
+                .flatten()
+                .filter_map(|shader| sources.get(shader).cloned())
+                .collect()
+        };
+        self.linked
+            .borrow_mut()
+            .insert(program, LinkedProgram::new(&shaders));
+    }
+
+    fn get_program_link_status(&self, _program: ProgramId) -> bool {
+        true
+    }
+
+    fn get_program_info_log(&self, _program: ProgramId) -> Option<String> {
+        None
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        self.record(Call::UseProgram(program));
+    }
+
+    fn delete_program(&self, program: ProgramId) {
+        self.record(Call::DeleteProgram(program));
+        self.attached.borrow_mut().remove(&program);
+        self.linked.borrow_mut().remove(&program);
+        self.uniform_locations
+            .borrow_mut()
+            .retain(|(p, _), _| *p != program);
+    }
+
+    fn get_active_attrib_count(&self, program: ProgramId) -> u32 {
+        self.linked
+            .borrow()
+            .get(&program)
+            .map_or(0, |linked| linked.attributes.len() as u32)
+    }
+
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let linked = self.linked.borrow();
+        let (info, _) = linked.get(&program)?.attributes.get(index as usize)?;
+        Some(info.clone())
+    }
+
+    fn get_active_uniform_count(&self, program: ProgramId) -> u32 {
+        self.linked
+            .borrow()
+            .get(&program)
+            .map_or(0, |linked| linked.uniforms.len() as u32)
+    }
+
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let linked = self.linked.borrow();
+        linked.get(&program)?.uniforms.get(index as usize).cloned()
+    }
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
+        self.record(Call::GetAttribLocation {
+            program,
+            name: name.to_owned(),
+        });
+        self.linked
+            .borrow()
+            .get(&program)
+            .and_then(|linked| linked.attributes.iter().find(|(info, _)| info.name == name))
+            .map_or(-1, |(_, location)| *location)
+    }
+
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocationId> {
+        self.record(Call::GetUniformLocation {
+            program,
+            name: name.to_owned(),
+        });
+        let base_name = name.split('[').next().unwrap_or_default();
+        let is_active = self.linked.borrow().get(&program).is_some_and(|linked| {
+            linked
+                .uniforms
+                .iter()
+                .any(|info| info.name.split('[').next() == Some(base_name))
+        });
+        if !is_active {
+            return None;
+        }
+        let key = (program, name.to_owned());
+        if let Some(location) = self.uniform_locations.borrow().get(&key) {
+            return Some(*location);
+        }
+        let location = UniformLocationId(self.next_id());
+        self.uniform_locations.borrow_mut().insert(key, location);
+        Some(location)
+    }
+
+    fn uniform_fv(&self, location: UniformLocationId, components: u32, data: &[f32]) {
+        self.record(Call::Uniformfv {
+            location,
+            components,
+            data: data.to_vec(),
+        });
+    }
+
+    fn uniform_iv(&self, location: UniformLocationId, components: u32, data: &[i32]) {
+        self.record(Call::Uniformiv {
+            location,
+            components,
+            data: data.to_vec(),
+        });
+    }
+
+    fn uniform_matrix_fv(
+        &self,
+        location: UniformLocationId,
+        columns: u32,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.record(Call::UniformMatrixfv {
+            location,
+            columns,
+            transpose,
+            data: data.to_vec(),
+        });
+    }
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        data_type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.record(Call::VertexAttribPointer {
+            index,
+            size,
+            data_type,
+            normalized,
+            stride,
+            offset,
+        });
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.record(Call::EnableVertexAttribArray(index));
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        self.record(Call::DisableVertexAttribArray(index));
+    }
+
+    fn vertex_attrib4fv(&self, index: u32, values: &[f32]) {
+        self.record(Call::VertexAttrib4fv {
+            index,
+            values: values.to_vec(),
+        });
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.record(Call::VertexAttribDivisor { index, divisor });
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let vertex_array = VertexArrayId(self.next_id());
+        self.record(Call::CreateVertexArray(vertex_array));
+        Some(vertex_array)
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        self.record(Call::BindVertexArray(vertex_array));
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        self.record(Call::DeleteVertexArray(vertex_array));
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        let texture = TextureId(self.next_id());
+        self.record(Call::CreateTexture(texture));
+        Some(texture)
+    }
+
+    fn active_texture(&self, unit: u32) {
+        self.record(Call::ActiveTexture(unit));
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        self.record(Call::BindTexture { target, texture });
+    }
+
+    fn tex_image_2d(
+        &self,
+        upload: &TextureUpload,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+    ) -> Result<(), JsValue> {
+        self.record(Call::TexImage2D {
+            upload: *upload,
+            width,
+            height,
+            pixels: pixels.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn tex_image_2d_from_source(
+        &self,
+        upload: &TextureUpload,
+        _source: ImageSource,
+    ) -> Result<(), JsValue> {
+        self.record(Call::TexImage2DFromSource { upload: *upload });
+        Ok(())
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.record(Call::TexParameteri {
+            target,
+            pname,
+            param,
+        });
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        self.record(Call::GenerateMipmap(target));
+    }
+
+    fn pixel_storei(&self, pname: u32, param: i32) {
+        self.record(Call::PixelStorei { pname, param });
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        self.record(Call::DeleteTexture(texture));
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(Call::Viewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.record(Call::ClearColor([red, green, blue, alpha]));
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        self.record(Call::ClearDepth(depth));
+    }
+
+    fn enable(&self, cap: u32) {
+        self.record(Call::Enable(cap));
+    }
+
+    fn disable(&self, cap: u32) {
+        self.record(Call::Disable(cap));
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.record(Call::DepthFunc(func));
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.record(Call::DepthMask(flag));
+    }
+
+    fn blend_func(&self, source: u32, destination: u32) {
+        self.record(Call::BlendFunc {
+            source,
+            destination,
+        });
+    }
+
+    fn cull_face(&self, mode: u32) {
+        self.record(Call::CullFace(mode));
+    }
+
+    fn clear(&self, mask: u32) {
+        self.record(Call::Clear(mask));
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.record(Call::DrawArrays { mode, first, count });
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        self.record(Call::DrawArraysInstanced {
+            mode,
+            first,
+            count,
+            instance_count,
+        });
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        self.record(Call::DrawElements {
+            mode,
+            count,
+            data_type,
+            offset,
+        });
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.record(Call::DrawElementsInstanced {
+            mode,
+            count,
+            data_type,
+            offset,
+            instance_count,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web_sys::WebGlRenderingContext as Gl;
+
+    fn names(declarations: &[Declaration]) -> Vec<&str> {
+        declarations
+            .iter()
+            .map(|declaration| declaration.info.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_declarations() {
+        let source = "
+            #version 300 es
+            precision highp float;
+            // uniform float uCommented;
+            layout(location = 3) in vec3 aNormal;
+            in highp vec4 aPosition;
+            uniform mat4 uModel, uLights[4];
+            out vec3 vNormal;
+            void main() { float unused; vNormal = aNormal; }
+        ";
+        let (attributes, uniforms) = parse_declarations(source, true);
+        assert_eq!(names(&attributes), ["aNormal", "aPosition"]);
+        assert_eq!(attributes[0].location, Some(3));
+        assert_eq!(attributes[1].location, None);
+        assert_eq!(attributes[1].info.data_type, Gl::FLOAT_VEC4);
+
+        assert_eq!(names(&uniforms), ["uModel", "uLights[0]"]);
+        assert_eq!(uniforms[1].info.size, 4);
+        assert_eq!(uniforms[1].info.data_type, Gl::FLOAT_MAT4);
+
+        // fragment shader inputs are varyings, not attributes
+        let (attributes, _) = parse_declarations("in vec3 vNormal;", false);
+        assert!(attributes.is_empty());
+    }
+
+    #[test]
+    fn assigns_free_attribute_locations() {
+        let vertex = "
+            attribute vec4 aPosition;
+            attribute mat4 aModel;
+            layout(location = 1) in vec3 aNormal;
+            attribute vec2 aUv;
+            uniform mat4 uView;
+        ";
+        let fragment = "uniform mat4 uView; uniform sampler2D uTexture;";
+        let program = LinkedProgram::new(&[
+            (Gl::VERTEX_SHADER, vertex.to_owned()),
+            (Gl::FRAGMENT_SHADER, fragment.to_owned()),
+        ]);
+
+        let locations: Vec<(&str, i32)> = program
+            .attributes
+            .iter()
+            .map(|(info, location)| (info.name.as_str(), *location))
+            .collect();
+        // the matrix takes four slots around the explicit location 1
+        assert_eq!(
+            locations,
+            [("aPosition", 0), ("aModel", 2), ("aNormal", 1), ("aUv", 6)]
+        );
+        let uniforms: Vec<&str> = program.uniforms.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(uniforms, ["uView", "uTexture"]);
+    }
+
+    #[test]
+    fn keeps_matrix_columns_apart() {
+        let vertex = "
+            layout(location = 0) in mat3 aNormalMatrix;
+            in vec4 aPosition;
+            in mat4 aModel;
+            layout(location = 5) in vec2 aUv;
+        ";
+        let program = LinkedProgram::new(&[(Gl::VERTEX_SHADER, vertex.to_owned())]);
+
+        let locations: Vec<(&str, i32)> = program
+            .attributes
+            .iter()
+            .map(|(info, location)| (info.name.as_str(), *location))
+            .collect();
+        // aPosition skips the explicit matrix columns, aModel
+        // needs four free slots in a row and 4..8 holds aUv
+        assert_eq!(
+            locations,
+            [
+                ("aNormalMatrix", 0),
+                ("aPosition", 3),
+                ("aModel", 6),
+                ("aUv", 5)
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+use web_sys::WebGlRenderingContext as Gl;
+
+use crate::gl_state::StateCacheStats;
+
+/// Frames the FPS is averaged over
+const FPS_FRAMES: usize = 60;
+
+/// Work sent to the backend through `RenderingContext`, counted until taken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawCounters {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub vertices: u32,
+    pub uniform_uploads: u32,
+    /// Buffer data and texture pixels
+    pub bytes_uploaded: u32,
+}
+
+impl DrawCounters {
+    pub fn add_draw(&mut self, mode: u32, count: i32, instance_count: i32) {
+        let count = count.max(0) as u32;
+        let instances = instance_count.max(0) as u32;
+        self.draw_calls += 1;
+        self.vertices += count * instances;
+        self.triangles += triangle_count(mode, count) * instances;
+    }
+}
+
+/// Triangles drawn from `count` vertices, 0 for points and lines
+pub fn triangle_count(mode: u32, count: u32) -> u32 {
+    match mode {
+        Gl::TRIANGLES => count / 3,
+        Gl::TRIANGLE_STRIP | Gl::TRIANGLE_FAN => count.saturating_sub(2),
+        _ => 0,
+    }
+}
+
+/// Frames per second averaged over the last `FPS_FRAMES` frames
+#[derive(Debug)]
+pub struct FpsCounter {
+    /// Start of every frame in milliseconds, oldest first
+    frames: VecDeque<f64>,
+}
+
+impl FpsCounter {
+    pub fn new() -> Self {
+        FpsCounter {
+            frames: VecDeque::with_capacity(FPS_FRAMES + 1),
+        }
+    }
+
+    pub fn tick(&mut self, now: f64) {
+        if self.frames.len() > FPS_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(now);
+    }
+
+    /// 0 until two frames with different times were seen
+    pub fn fps(&self) -> f64 {
+        let (Some(first), Some(last)) = (self.frames.front(), self.frames.back()) else {
+            return 0.0;
+        };
+        let elapsed = last - first;
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        (self.frames.len() - 1) as f64 * 1000.0 / elapsed
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the last frame cost, from the end of one `Scene::draw` to the end of the next.
+/// Times are in milliseconds and stay 0 for headless scenes
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    /// Vertices processed, every instance counts
+    pub vertices: u32,
+    pub program_switches: u32,
+    pub buffer_binds: u32,
+    pub uniform_uploads: u32,
+    pub bytes_uploaded: u32,
+    /// State calls the context found redundant
+    pub skipped_state_calls: u32,
+    pub update_ms: f64,
+    pub draw_ms: f64,
+    pub fps: f64,
+}
+
+impl FrameStats {
+    pub fn new(counters: DrawCounters, state: StateCacheStats) -> Self {
+        FrameStats {
+            draw_calls: counters.draw_calls,
+            triangles: counters.triangles,
+            vertices: counters.vertices,
+            program_switches: state.issued.programs,
+            buffer_binds: state.issued.buffers,
+            uniform_uploads: counters.uniform_uploads,
+            bytes_uploaded: counters.bytes_uploaded,
+            skipped_state_calls: state.skipped.total(),
+            ..FrameStats::default()
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl FrameStats {
+    /// One stat per line, as the overlay shows them
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.1} fps\n\
+             update {:.2} ms, draw {:.2} ms\n\
+             {} draw calls, {} triangles, {} vertices\n\
+             {} program switches, {} buffer binds, {} skipped\n\
+             {} uniforms, {} bytes uploaded",
+            self.fps,
+            self.update_ms,
+            self.draw_ms,
+            self.draw_calls,
+            self.triangles,
+            self.vertices,
+            self.program_switches,
+            self.buffer_binds,
+            self.skipped_state_calls,
+            self.uniform_uploads,
+            self.bytes_uploaded,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_instanced_triangles() {
+        let mut counters = DrawCounters::default();
+        counters.add_draw(Gl::TRIANGLES, 36, 10);
+        counters.add_draw(Gl::TRIANGLE_STRIP, 4, 1);
+        counters.add_draw(Gl::LINES, 8, 1);
+        assert_eq!(counters.draw_calls, 3);
+        assert_eq!(counters.triangles, 122);
+        assert_eq!(counters.vertices, 372);
+    }
+
+    #[test]
+    fn averages_fps_over_recent_frames() {
+        let mut fps = FpsCounter::new();
+        assert_eq!(fps.fps(), 0.0);
+        fps.tick(0.0);
+        assert_eq!(fps.fps(), 0.0);
+
+        // a slow start falls out of the window
+        fps.tick(500.0);
+        for frame in 1..=FPS_FRAMES {
+            fps.tick(500.0 + frame as f64 * 20.0);
+        }
+        assert!((fps.fps() - 50.0).abs() < 1e-9);
+    }
+}
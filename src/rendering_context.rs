@@ -1,13 +1,26 @@
-use js_sys::Error;
-use web_sys::{
-    WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlUniformLocation,
+use std::cell::Cell;
+use std::rc::Rc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::{
+    Backend, BufferId, ProgramId, TextureId, TextureUpload, UniformLocationId, VertexArrayId,
 };
+use crate::capabilities::{Capabilities, GlVersion};
+use crate::error::Error;
+use crate::gl_object::{
+    GlObjectId, ObjectTracker, OwnedBuffer, OwnedProgram, OwnedShader, OwnedTexture,
+    OwnedVertexArray,
+};
+use crate::gl_state::{GlState, StateCacheStats};
+use crate::render_stats::DrawCounters;
+use crate::uniform::UniformValue;
 
 enum ShaderKind {
     Vertex,
     Fragment,
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BufferKind {
     ArrayBuffer,
     ElementArrayBuffer,
@@ -27,26 +40,92 @@ pub fn get_buffer_type(kind: BufferKind) -> u32 {
     }
 }
 
+fn is_glsl_es_300(source: &str) -> bool {
+    source.trim_start().starts_with("#version 300 es")
+}
+
+/// State changes should go through the context rather than `gl`,
+/// it remembers what is set and skips calls that change nothing
 pub struct RenderingContext {
-    pub gl: WebGlRenderingContext,
+    pub gl: Rc<dyn Backend>,
+    capabilities: Capabilities,
+    objects: Rc<ObjectTracker>,
+    /// Shared with `objects`, which clears the bindings of deleted objects
+    state: Rc<GlState>,
+    counters: Cell<DrawCounters>,
 }
 
 impl RenderingContext {
-    pub fn new(gl: WebGlRenderingContext) -> Self {
-        RenderingContext { gl }
+    pub fn new(gl: Box<dyn Backend>) -> Self {
+        let gl: Rc<dyn Backend> = Rc::from(gl);
+        let capabilities = gl.capabilities();
+        let state = Rc::new(GlState::new());
+        let objects = Rc::new(ObjectTracker::new(Rc::clone(&gl), Rc::clone(&state)));
+        RenderingContext {
+            gl,
+            capabilities,
+            objects,
+            state,
+            counters: Cell::new(DrawCounters::default()),
+        }
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub fn version(&self) -> GlVersion {
+        self.capabilities.version
+    }
+
+    /// Calls passed on to the backend and skipped since the last reset
+    pub fn state_stats(&self) -> StateCacheStats {
+        self.state.stats()
+    }
+
+    pub fn reset_state_stats(&self) {
+        self.state.reset_stats();
     }
 
-    pub fn bind_buffer(&self, kind: BufferKind, buffer: &WebGlBuffer) {
-        let buffer_type = get_buffer_type(kind);
-        self.gl.bind_buffer(buffer_type, Some(buffer));
+    /// GL objects created through the context and not dropped yet,
+    /// always empty in release builds
+    pub fn alive_objects(&self) -> Vec<GlObjectId> {
+        self.objects.alive()
+    }
+
+    /// Draws and uploads since the counters were last taken
+    pub fn counters(&self) -> DrawCounters {
+        self.counters.get()
+    }
+
+    pub fn take_counters(&self) -> DrawCounters {
+        self.counters.take()
+    }
+
+    fn count(&self, update: impl FnOnce(&mut DrawCounters)) {
+        let mut counters = self.counters.get();
+        update(&mut counters);
+        self.counters.set(counters);
+    }
+
+    /// Needed after changing state through `gl` directly
+    pub fn forget_state(&self) {
+        self.state.forget();
+    }
+
+    pub fn bind_buffer(&self, kind: BufferKind, buffer: BufferId) {
+        if self.state.set_buffer(kind, Some(buffer)) {
+            self.gl.bind_buffer(get_buffer_type(kind), Some(buffer));
+        }
     }
 
     pub fn clear_buffer(&self, kind: BufferKind) {
-        let buffer_type = get_buffer_type(kind);
-        self.gl.bind_buffer(buffer_type, None);
+        if self.state.set_buffer(kind, None) {
+            self.gl.bind_buffer(get_buffer_type(kind), None);
+        }
     }
 
-    pub fn bind_array_buffer(&self, buffer: &WebGlBuffer) {
+    pub fn bind_array_buffer(&self, buffer: BufferId) {
         self.bind_buffer(BufferKind::ArrayBuffer, buffer);
     }
 
@@ -54,94 +133,322 @@ impl RenderingContext {
         self.clear_buffer(BufferKind::ArrayBuffer);
     }
 
-    pub fn create_buffer(&self) -> Result<WebGlBuffer, Error> {
+    /// Requires `Capabilities::vertex_array_objects`
+    pub fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        if self.state.set_vertex_array(vertex_array) {
+            self.gl.bind_vertex_array(vertex_array);
+        }
+    }
+
+    /// Reads from the buffer bound to `ARRAY_BUFFER`. Always passed on
+    pub fn vertex_attrib_pointer(
+        &self,
+        location: u32,
+        size: i32,
+        data_type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.state.count_attribute_pointer();
         self.gl
+            .vertex_attrib_pointer(location, size, data_type, normalized, stride, offset);
+    }
+
+    pub fn enable_vertex_attrib_array(&self, location: u32) {
+        if self.state.set_attribute_array(location, true) {
+            self.gl.enable_vertex_attrib_array(location);
+        }
+    }
+
+    pub fn disable_vertex_attrib_array(&self, location: u32) {
+        if self.state.set_attribute_array(location, false) {
+            self.gl.disable_vertex_attrib_array(location);
+        }
+    }
+
+    /// Requires `Capabilities::instancing`
+    pub fn vertex_attrib_divisor(&self, location: u32, divisor: u32) {
+        if self.state.set_attribute_divisor(location, divisor) {
+            self.gl.vertex_attrib_divisor(location, divisor);
+        }
+    }
+
+    /// Runs `f` with the default vertex array bound, then binds back the one
+    /// that was, so element buffers bound inside don't end up recorded in it
+    pub fn without_vertex_array<R>(&self, f: impl FnOnce() -> R) -> R {
+        let Some(Some(vertex_array)) = self.state.vertex_array() else {
+            return f();
+        };
+        self.bind_vertex_array(None);
+        let result = f();
+        self.bind_vertex_array(Some(vertex_array));
+        result
+    }
+
+    /// `unit` counts from 0
+    pub fn active_texture(&self, unit: u32) {
+        if self.state.set_active_texture(unit) {
+            self.gl
+                .active_texture(WebGlRenderingContext::TEXTURE0 + unit);
+        }
+    }
+
+    /// Binds to the active texture unit
+    pub fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        if self.state.set_texture(target, texture) {
+            self.gl.bind_texture(target, texture);
+        }
+    }
+
+    /// Binds `texture` to the active unit while `f` runs, then puts back what
+    /// was bound there before. Unbinds instead if that binding isn't known.
+    /// A texture deleted since it was bound is not restored: deleting it
+    /// clears it from the cache, just as GL unbinds it
+    pub fn with_texture<R>(&self, target: u32, texture: TextureId, f: impl FnOnce() -> R) -> R {
+        let previous = self.state.texture(target).flatten();
+        self.bind_texture(target, Some(texture));
+        let result = f();
+        self.bind_texture(target, previous);
+        result
+    }
+
+    pub fn enable(&self, cap: u32) {
+        if self.state.set_capability(cap, true) {
+            self.gl.enable(cap);
+        }
+    }
+
+    pub fn disable(&self, cap: u32) {
+        if self.state.set_capability(cap, false) {
+            self.gl.disable(cap);
+        }
+    }
+
+    pub fn depth_func(&self, func: u32) {
+        if self.state.set_depth_func(func) {
+            self.gl.depth_func(func);
+        }
+    }
+
+    pub fn depth_mask(&self, flag: bool) {
+        if self.state.set_depth_mask(flag) {
+            self.gl.depth_mask(flag);
+        }
+    }
+
+    pub fn blend_func(&self, source: u32, destination: u32) {
+        if self.state.set_blend_func(source, destination) {
+            self.gl.blend_func(source, destination);
+        }
+    }
+
+    pub fn cull_face(&self, mode: u32) {
+        if self.state.set_cull_face(mode) {
+            self.gl.cull_face(mode);
+        }
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        if self.state.set_viewport([x, y, width, height]) {
+            self.gl.viewport(x, y, width, height);
+        }
+    }
+
+    pub fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        if self.state.set_clear_color([red, green, blue, alpha]) {
+            self.gl.clear_color(red, green, blue, alpha);
+        }
+    }
+
+    pub fn clear_depth(&self, depth: f32) {
+        if self.state.set_clear_depth(depth) {
+            self.gl.clear_depth(depth);
+        }
+    }
+
+    pub fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.count(|counters| counters.bytes_uploaded += data.len() as u32);
+        self.gl.buffer_data(target, data, usage);
+    }
+
+    pub fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        self.count(|counters| counters.bytes_uploaded += data.len() as u32);
+        self.gl.buffer_sub_data(target, offset, data);
+    }
+
+    pub fn tex_image_2d(
+        &self,
+        upload: &TextureUpload,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+    ) -> Result<(), Error> {
+        self.count(|counters| counters.bytes_uploaded += pixels.len() as u32);
+        Ok(self.gl.tex_image_2d(upload, width, height, pixels)?)
+    }
+
+    /// To the program in use
+    pub fn upload_uniform(&self, value: &UniformValue, location: UniformLocationId) {
+        self.count(|counters| counters.uniform_uploads += 1);
+        value.upload(self.gl.as_ref(), location);
+    }
+
+    pub fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.count(|counters| counters.add_draw(mode, count, 1));
+        self.gl.draw_arrays(mode, first, count);
+    }
+
+    pub fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        self.count(|counters| counters.add_draw(mode, count, 1));
+        self.gl.draw_elements(mode, count, data_type, offset);
+    }
+
+    /// Requires `Capabilities::instancing`
+    pub fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        self.count(|counters| counters.add_draw(mode, count, instance_count));
+        self.gl
+            .draw_arrays_instanced(mode, first, count, instance_count);
+    }
+
+    /// Requires `Capabilities::instancing`
+    pub fn draw_elements_instanced(
+        &self,
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.count(|counters| counters.add_draw(mode, count, instance_count));
+        self.gl
+            .draw_elements_instanced(mode, count, data_type, offset, instance_count);
+    }
+
+    pub fn create_buffer(&self) -> Result<OwnedBuffer, Error> {
+        let buffer = self
+            .gl
             .create_buffer()
-            .ok_or_else(|| Error::new("Failed to create buffer"))
+            .ok_or_else(|| Error::new("Failed to create buffer"))?;
+        Ok(OwnedBuffer::new(&self.objects, buffer))
     }
 
+    pub fn create_texture(&self) -> Result<OwnedTexture, Error> {
+        let texture = self
+            .gl
+            .create_texture()
+            .ok_or_else(|| Error::new("Failed to create texture"))?;
+        Ok(OwnedTexture::new(&self.objects, texture))
+    }
+
+    /// Requires `Capabilities::vertex_array_objects`
+    pub fn create_vertex_array(&self) -> Result<OwnedVertexArray, Error> {
+        let vertex_array = self
+            .gl
+            .create_vertex_array()
+            .ok_or_else(|| Error::new("Failed to create vertex array"))?;
+        Ok(OwnedVertexArray::new(&self.objects, vertex_array))
+    }
+
+    /// Shaders are deleted once the program is linked,
+    /// the program keeps them alive for as long as it needs them
     pub fn create_program(
         &self,
         vertex_src: &str,
         fragment_src: &str,
-    ) -> Result<WebGlProgram, Error> {
+    ) -> Result<OwnedProgram, Error> {
         let vertex_shader = self.compile_shader(ShaderKind::Vertex, vertex_src)?;
         let fragment_shader = self.compile_shader(ShaderKind::Fragment, fragment_src)?;
-        self.link_program(vertex_shader, fragment_shader)
+        self.link_program(&vertex_shader, &fragment_shader)
     }
 
-    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<WebGlShader, Error> {
+    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<OwnedShader, Error> {
         let gl = &self.gl;
 
+        if is_glsl_es_300(source) && !self.capabilities.glsl_es_300() {
+            return Err(Error::new(&format!(
+                "GLSL ES 3.00 shaders are not supported by {}",
+                self.version()
+            )));
+        }
+
         let shader_type = get_shader_type(kind);
         let shader = gl
             .create_shader(shader_type)
             .ok_or_else(|| Error::new("Unable to create shader object"))?;
+        let shader = OwnedShader::new(&self.objects, shader);
 
-        gl.shader_source(&shader, source);
-        gl.compile_shader(&shader);
+        gl.shader_source(shader.id(), source);
+        gl.compile_shader(shader.id());
 
-        if gl
-            .get_shader_parameter(&shader, WebGlRenderingContext::COMPILE_STATUS)
-            .as_bool()
-            .unwrap_or(false)
-        {
+        if gl.get_shader_compile_status(shader.id()) {
             Ok(shader)
         } else {
-            Err(Error::new(&gl.get_shader_info_log(&shader).unwrap_or_else(
-                || "Unknown error creating shader".to_owned(),
-            )))
+            Err(Error::new(
+                &gl.get_shader_info_log(shader.id())
+                    .unwrap_or_else(|| "Unknown error creating shader".to_owned()),
+            ))
         }
     }
 
     fn link_program(
         &self,
-        vertex_shader: WebGlShader,
-        fragment_shader: WebGlShader,
-    ) -> Result<WebGlProgram, Error> {
+        vertex_shader: &OwnedShader,
+        fragment_shader: &OwnedShader,
+    ) -> Result<OwnedProgram, Error> {
         let gl = &self.gl;
 
         let program = gl
             .create_program()
             .ok_or_else(|| Error::new("Unable to create shader program"))?;
+        let program = OwnedProgram::new(&self.objects, program);
 
-        gl.attach_shader(&program, &vertex_shader);
-        gl.attach_shader(&program, &fragment_shader);
-        gl.link_program(&program);
+        gl.attach_shader(program.id(), vertex_shader.id());
+        gl.attach_shader(program.id(), fragment_shader.id());
+        gl.link_program(program.id());
 
-        if gl
-            .get_program_parameter(&program, WebGlRenderingContext::LINK_STATUS)
-            .as_bool()
-            .unwrap_or(false)
-        {
+        if gl.get_program_link_status(program.id()) {
             Ok(program)
         } else {
             Err(Error::new(
-                &gl.get_program_info_log(&program)
+                &gl.get_program_info_log(program.id())
                     .unwrap_or_else(|| "Unknown error creating shader program".to_owned()),
             ))
         }
     }
 
-    pub fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+    pub fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
         self.gl.get_attrib_location(program, name)
     }
 
     pub fn get_uniform_location(
         &self,
-        program: &WebGlProgram,
+        program: ProgramId,
         name: &str,
-    ) -> Result<WebGlUniformLocation, Error> {
+    ) -> Result<UniformLocationId, Error> {
         self.gl
             .get_uniform_location(program, name)
             .ok_or_else(|| Error::new(&format!("Can't get uniform location for {name}")))
     }
 
-    pub fn use_program(&self, program: &WebGlProgram) {
-        self.gl.use_program(Some(program));
+    pub fn use_program(&self, program: ProgramId) {
+        if self.state.set_program(Some(program)) {
+            self.gl.use_program(Some(program));
+        }
     }
 
     // pub fn clear_program(&self) {
     //     self.gl.use_program(None);
     // }
 }
+
+impl Drop for RenderingContext {
+    fn drop(&mut self) {
+        // Everything created through the context should be gone by now
+        if cfg!(debug_assertions) {
+            for object in self.alive_objects() {
+                console_log!("Leaked GL object: {object}");
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use js_sys::Array;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use web_sys::{Element, ResizeObserver, ResizeObserverEntry};
+
+/// Watches the CSS size of an element. The latest size is kept
+/// until `take_size` picks it up, so the scene can apply it
+/// between frames. Stops observing when dropped
+pub struct SizeObserver {
+    observer: ResizeObserver,
+    _callback: Closure<dyn FnMut(Array)>,
+    size: Rc<Cell<Option<(f64, f64)>>>,
+}
+
+impl SizeObserver {
+    pub fn new(element: &Element) -> Result<Self, JsValue> {
+        let size = Rc::new(Cell::new(None));
+        let callback = {
+            let size = Rc::clone(&size);
+            move |entries: Array| {
+                if let Some(entry) = entries.iter().last() {
+                    let rect = entry.unchecked_into::<ResizeObserverEntry>().content_rect();
+                    size.set(Some((rect.width(), rect.height())));
+                }
+            }
+        };
+        let callback = Closure::wrap(Box::new(callback) as Box<dyn FnMut(Array)>);
+        let observer = ResizeObserver::new(callback.as_ref().unchecked_ref())?;
+        observer.observe(element);
+        Ok(SizeObserver {
+            observer,
+            _callback: callback,
+            size,
+        })
+    }
+
+    /// CSS width and height reported since the previous call
+    pub fn take_size(&self) -> Option<(f64, f64)> {
+        self.size.take()
+    }
+}
+
+impl Drop for SizeObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
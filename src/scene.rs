@@ -1,15 +1,28 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ops::Drop;
 use std::rc::Rc;
 
-use js_sys::Date;
+use nalgebra::{Point3, Vector3};
+use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
-use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use web_sys::{Element, HtmlCanvasElement, Performance, WebGlRenderingContext, Window};
 
-use crate::cube::{Cube, get_geometries};
+use crate::backend::Backend;
+use crate::camera::Camera;
+use crate::cube::{CubeField, get_cube_field};
 use crate::dom_helpers::*;
+use crate::error::Error;
+use crate::event_listener::EventListener;
+use crate::light::{DirectionalLight, Lights, PointLight, SpotLight};
+use crate::orbit_controls::{self, OrbitControls};
 use crate::program::Program;
+use crate::render_stats::{FpsCounter, FrameStats};
 use crate::rendering_context::RenderingContext;
+use crate::resize_observer::SizeObserver;
+use crate::scene_graph::SceneGraph;
+use crate::scene_options::SceneOptions;
+use crate::stats_overlay::StatsOverlay;
+use crate::webgl_backend::WebGlBackend;
 
 type CanvasRef = Rc<RefCell<HtmlCanvasElement>>;
 
@@ -17,48 +30,254 @@ fn canvas_to_ref(canvas: HtmlCanvasElement) -> CanvasRef {
     Rc::new(RefCell::new(canvas))
 }
 
+fn to_vector(values: &[f32]) -> Result<Vector3<f32>, Error> {
+    match values {
+        [x, y, z] => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(Error::new(&format!(
+            "Expected 3 numbers, got {}",
+            values.len()
+        ))),
+    }
+}
+
 #[wasm_bindgen]
 pub struct Scene {
-    canvas: CanvasRef,
-    ctx: RenderingContext,
+    canvas: Option<CanvasRef>,
+    /// Whether the canvas was created by the scene and should be removed with it
+    owns_canvas: bool,
+    clear_color: [f32; 4],
+    /// CSS width and height of the canvas
+    size: (f64, f64),
+    pixel_ratio: f64,
+    /// Reports canvas resizes, `None` for headless scenes
+    size_observer: Option<SizeObserver>,
+    camera: Camera,
+    controls: Rc<RefCell<OrbitControls>>,
+    /// Input listeners on the canvas, empty for headless scenes
+    listeners: Vec<EventListener>,
     program: Program,
-    geometries: Vec<Cube>,
+    graph: SceneGraph,
+    lights: Lights,
+    cubes: CubeField,
     last_update: f64,
+    /// Frame timer, `None` for headless scenes
+    performance: Option<Performance>,
+    /// Time the last `update_state` took
+    update_ms: f64,
+    fps: RefCell<FpsCounter>,
+    /// Of the last drawn frame
+    stats: Cell<FrameStats>,
+    overlay: Option<StatsOverlay>,
+    // Dropped last, after all the GL objects created through it
+    ctx: RenderingContext,
 }
 
 impl Scene {
+    /// Creates a scene without a canvas, drawing into the given backend.
+    /// Useful to run the engine outside of a browser. There is no frame timer,
+    /// so the time is passed to `update_state_at` and frames take 0 ms
+    pub fn headless(backend: Box<dyn Backend>) -> Result<Scene, Error> {
+        Scene::with_context(None, RenderingContext::new(backend), None)
+    }
+
+    fn with_context(
+        canvas: Option<CanvasRef>,
+        ctx: RenderingContext,
+        performance: Option<Performance>,
+    ) -> Result<Scene, Error> {
+        let last_update = performance
+            .as_ref()
+            .map_or(0.0, |performance| performance.now());
+        let vert_src = include_str!("shaders/lit_vert.glsl");
+        let frag_src = include_str!("shaders/lit_frag.glsl");
+        let program = Program::new(&ctx, vert_src, frag_src)?;
+
+        let mut graph = SceneGraph::new();
+        let cubes = get_cube_field(&ctx, &mut graph)?;
+
+        let field_of_view = 45.0 * std::f32::consts::PI / 180.0; // in radians
+        let camera = Camera::perspective(1.0, field_of_view, 0.1, 1000.0);
+
+        let controls = Rc::new(RefCell::new(OrbitControls::from_camera(&camera)));
+
+        let mut scene = Scene {
+            ctx,
+            camera,
+            controls,
+            listeners: vec![],
+            program,
+            graph,
+            lights: Lights::default(),
+            cubes,
+            last_update,
+            canvas,
+            owns_canvas: false,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            size: (0.0, 0.0),
+            pixel_ratio: 1.0,
+            size_observer: None,
+            performance,
+            update_ms: 0.0,
+            fps: RefCell::new(FpsCounter::new()),
+            stats: Cell::new(FrameStats::default()),
+            overlay: None,
+        };
+
+        scene.resize(640.0, 480.0, 1.0);
+        scene.set_initial_state();
+        // Setting up is not part of the first frame
+        scene.ctx.take_counters();
+        scene.ctx.reset_state_stats();
+
+        Ok(scene)
+    }
+
+    /// Applies the latest size reported by the observer,
+    /// along with the current device pixel ratio
+    fn track_size(&mut self) {
+        let Some(observer) = &self.size_observer else {
+            return;
+        };
+        let (width, height) = observer.take_size().unwrap_or(self.size);
+        let pixel_ratio =
+            get_window().map_or(self.pixel_ratio, |window| window.device_pixel_ratio());
+        self.resize(width, height, pixel_ratio);
+    }
+
     pub fn get_ctx(&self) -> &RenderingContext {
         &self.ctx
     }
 
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn get_camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Settings of the orbit controller, e.g. its damping
+    pub fn get_controls(&self) -> &RefCell<OrbitControls> {
+        &self.controls
+    }
+
+    pub fn get_graph(&self) -> &SceneGraph {
+        &self.graph
+    }
+
+    pub fn get_graph_mut(&mut self) -> &mut SceneGraph {
+        &mut self.graph
+    }
+
+    pub fn get_lights(&self) -> &Lights {
+        &self.lights
+    }
+
+    pub fn get_lights_mut(&mut self) -> &mut Lights {
+        &mut self.lights
+    }
+
+    pub fn get_cubes(&self) -> &CubeField {
+        &self.cubes
+    }
+
     pub fn get_program(&self) -> &Program {
         &self.program
     }
 
     fn clear_colors(&self) {
-        let gl = &self.ctx.gl;
+        let ctx = &self.ctx;
 
-        gl.clear_color(0.0, 0.0, 0.0, 1.0);
-        gl.clear_depth(1.0);
-        gl.enable(WebGlRenderingContext::DEPTH_TEST);
-        gl.depth_func(WebGlRenderingContext::LEQUAL);
+        let [red, green, blue, alpha] = self.clear_color;
+        ctx.clear_color(red, green, blue, alpha);
+        ctx.clear_depth(1.0);
+        ctx.enable(WebGlRenderingContext::DEPTH_TEST);
+        ctx.depth_func(WebGlRenderingContext::LEQUAL);
 
-        gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
+        ctx.gl.clear(
+            WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT,
+        );
+    }
+
+    /// Milliseconds, 0 without a frame timer
+    fn now(&self) -> f64 {
+        self.performance
+            .as_ref()
+            .map_or(0.0, |performance| performance.now())
+    }
+
+    /// Collects what the frame cost and starts counting the next one
+    fn finish_frame(&self, start: f64, end: f64) -> Result<(), Error> {
+        let mut fps = self.fps.borrow_mut();
+        fps.tick(start);
+        let stats = FrameStats {
+            update_ms: self.update_ms,
+            draw_ms: end - start,
+            fps: fps.fps(),
+            ..FrameStats::new(self.ctx.take_counters(), self.ctx.state_stats())
+        };
+        self.ctx.reset_state_stats();
+        self.stats.set(stats);
+
+        if let (Some(overlay), Some(canvas)) = (&self.overlay, &self.canvas) {
+            overlay.show(&canvas.borrow(), &stats)?;
+        }
+        Ok(())
     }
 
     pub fn use_program(&self) {
-        self.ctx.use_program(&self.program.compiled);
+        self.ctx.use_program(self.program.compiled.id());
     }
 
-    fn set_initial_state(&mut self) {
-        for geometry in &mut self.geometries {
-            geometry.set_initial_state();
+    /// Creates the rendering context and starts tracking the canvas size and input
+    fn attach(
+        window: &Window,
+        canvas: HtmlCanvasElement,
+        options: &SceneOptions,
+        owns_canvas: bool,
+    ) -> Result<Scene, JsValue> {
+        let style = canvas.style();
+        if let Some(width) = options.width {
+            style.set_property("width", &format!("{width}px"))?;
         }
+        if let Some(height) = options.height {
+            style.set_property("height", &format!("{height}px"))?;
+        }
+
+        let gl = get_context(&canvas, &options.context_attributes())?;
+        let ctx = RenderingContext::new(Box::new(WebGlBackend::new(gl)));
+        console_log!("Rendering with {}", ctx.version());
+
+        let canvas_ref = canvas_to_ref(canvas.clone());
+        let mut scene = Scene::with_context(Some(canvas_ref), ctx, window.performance())?;
+        scene.owns_canvas = owns_canvas;
+        scene.clear_color = options.clear_color;
+        scene.listeners = orbit_controls::attach(window, &canvas, &scene.controls)?;
+        scene.size_observer = Some(SizeObserver::new(&canvas)?);
+        scene.set_stats_overlay(options.stats_overlay)?;
+
+        // The observer reports the laid out size on its own, this one
+        // only matters until then
+        let width = options
+            .width
+            .map_or(canvas.client_width() as f64, f64::from);
+        let height = options
+            .height
+            .map_or(canvas.client_height() as f64, f64::from);
+        scene.resize(width, height, window.device_pixel_ratio());
+        Ok(scene)
+    }
+
+    fn set_initial_state(&mut self) {
+        let eye = Point3::new(0.0, 0.0, 450.0);
+        self.camera.look_at(eye, Point3::origin(), Vector3::y());
+        self.controls.borrow_mut().look_at(eye, Point3::origin());
     }
 }
 
 #[wasm_bindgen]
 impl Scene {
+    /// Appends a bordered 640x480 canvas to the body
     #[wasm_bindgen(constructor)]
     pub fn new() -> Result<Scene, JsValue> {
         let window = get_window()?;
@@ -66,57 +285,415 @@ impl Scene {
         let body = get_body(&document)?;
         let canvas = create_canvas(&document)?;
 
-        canvas.set_width(640);
-        canvas.set_height(480);
         canvas.style().set_property("border", "1px solid black")?;
-
         body.append_child(&canvas)?;
 
-        let gl = get_context(&canvas)?;
-        let ctx = RenderingContext::new(gl);
+        let options = SceneOptions {
+            width: Some(640),
+            height: Some(480),
+            ..SceneOptions::default()
+        };
+        Scene::attach(&window, canvas, &options, true)
+    }
 
-        let vert_src = include_str!("shaders/vert.glsl");
-        let frag_src = include_str!("shaders/frag.glsl");
-        let program = Program::new(&ctx, vert_src, frag_src)?;
+    /// Renders into a canvas which is already on the page.
+    /// The canvas is left in place when the scene is dropped
+    pub fn with_canvas(
+        canvas: HtmlCanvasElement,
+        options: &SceneOptions,
+    ) -> Result<Scene, JsValue> {
+        Scene::attach(&get_window()?, canvas, options, false)
+    }
 
-        let geometries = get_geometries(&ctx)?;
+    /// Looks up the element by id. A canvas is rendered into directly,
+    /// any other element is used as a container
+    pub fn with_element_id(id: &str, options: &SceneOptions) -> Result<Scene, JsValue> {
+        let window = get_window()?;
+        let element = get_element_by_id(&get_document(&window)?, id)?;
+        match element.dyn_into::<HtmlCanvasElement>() {
+            Ok(canvas) => Scene::attach(&window, canvas, options, false),
+            Err(element) => Scene::with_container(&element, options),
+        }
+    }
 
-        let mut scene = Scene {
-            ctx,
-            program,
-            geometries,
-            last_update: Date::now(),
-            canvas: canvas_to_ref(canvas),
-        };
+    /// Appends a new canvas to the container. Unless the options
+    /// give it a size, the canvas fills the container and follows its size
+    pub fn with_container(container: &Element, options: &SceneOptions) -> Result<Scene, JsValue> {
+        let window = get_window()?;
+        let canvas = create_canvas(&get_document(&window)?)?;
 
-        scene.set_initial_state();
+        let style = canvas.style();
+        style.set_property("display", "block")?;
+        style.set_property("width", "100%")?;
+        style.set_property("height", "100%")?;
+        container.append_child(&canvas)?;
 
-        Ok(scene)
+        Scene::attach(&window, canvas, options, true)
+    }
+
+    pub fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.clear_color = [red, green, blue, alpha];
+    }
+
+    /// Either "WebGL 1" or "WebGL 2", depending on what the browser gave us
+    pub fn gl_version(&self) -> String {
+        self.ctx.version().to_string()
+    }
+
+    /// Moves the camera to `eye`, looking towards `target` with y up
+    pub fn look_at(
+        &mut self,
+        eye_x: f32,
+        eye_y: f32,
+        eye_z: f32,
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+    ) {
+        let eye = Point3::new(eye_x, eye_y, eye_z);
+        let target = Point3::new(target_x, target_y, target_z);
+        self.camera.look_at(eye, target, Vector3::y());
+        self.controls.borrow_mut().look_at(eye, target);
+    }
+
+    /// Sets the CSS size of the canvas, scaling its drawing buffer
+    /// by `pixel_ratio`, and updates the viewport and the camera aspect.
+    /// Called automatically when the canvas is resized
+    pub fn resize(&mut self, width: f64, height: f64, pixel_ratio: f64) {
+        if (width, height) == self.size && pixel_ratio == self.pixel_ratio {
+            return;
+        }
+        self.size = (width, height);
+        self.pixel_ratio = pixel_ratio;
+
+        let buffer_width = (width * pixel_ratio).round().max(1.0) as u32;
+        let buffer_height = (height * pixel_ratio).round().max(1.0) as u32;
+        if let Some(canvas) = &self.canvas {
+            let canvas = canvas.borrow();
+            canvas.set_width(buffer_width);
+            canvas.set_height(buffer_height);
+        }
+        self.ctx
+            .viewport(0, 0, buffer_width as i32, buffer_height as i32);
+        if width > 0.0 && height > 0.0 {
+            self.camera.set_aspect((width / height) as f32);
+        }
+    }
+
+    pub fn set_ambient_light(&mut self, red: f32, green: f32, blue: f32) {
+        self.lights.ambient = Vector3::new(red, green, blue);
+    }
+
+    /// Removes every light except the ambient one
+    pub fn clear_lights(&mut self) {
+        self.lights.directional.clear();
+        self.lights.point.clear();
+        self.lights.spot.clear();
+    }
+
+    /// `direction` is the way the light travels
+    pub fn add_directional_light(
+        &mut self,
+        direction: &[f32],
+        color: &[f32],
+    ) -> Result<(), JsValue> {
+        self.lights.directional.push(DirectionalLight {
+            direction: to_vector(direction)?,
+            color: to_vector(color)?,
+        });
+        Ok(())
+    }
+
+    pub fn add_point_light(
+        &mut self,
+        position: &[f32],
+        color: &[f32],
+        range: f32,
+    ) -> Result<(), JsValue> {
+        self.lights.point.push(PointLight {
+            position: to_vector(position)?.into(),
+            color: to_vector(color)?,
+            range,
+        });
+        Ok(())
+    }
+
+    /// Angles are in radians, measured from the direction to the edge of the cone
+    pub fn add_spot_light(
+        &mut self,
+        position: &[f32],
+        direction: &[f32],
+        color: &[f32],
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Result<(), JsValue> {
+        self.lights.spot.push(SpotLight {
+            position: to_vector(position)?.into(),
+            direction: to_vector(direction)?,
+            color: to_vector(color)?,
+            range,
+            inner_angle,
+            outer_angle,
+        });
+        Ok(())
+    }
+
+    /// Advances the animation to the current time of the frame timer
+    pub fn update_state(&mut self) -> Result<(), Error> {
+        self.update_state_at(self.now())
     }
 
-    pub fn update_state(&mut self) {
-        let now = Date::now();
+    /// Advances the animation to `now`, in milliseconds. Takes the
+    /// `requestAnimationFrame` timestamp, or any clock for a headless scene
+    pub fn update_state_at(&mut self, now: f64) -> Result<(), Error> {
+        let start = self.now();
+        self.track_size();
+
         let diff = now - self.last_update;
         self.last_update = now;
 
         let angle = diff as f32 / 1e3;
 
-        for geometry in &mut self.geometries {
-            geometry.update_state(angle);
+        self.cubes.update_state(&mut self.graph, angle)?;
+        self.cubes.write_instances(&self.ctx, &self.graph);
+
+        let mut controls = self.controls.borrow_mut();
+        if controls.update() {
+            controls.apply(&mut self.camera);
         }
+        self.update_ms = self.now() - start;
+        Ok(())
     }
 
-    pub fn draw(&self) {
+    pub fn draw(&self) -> Result<(), Error> {
+        let start = self.now();
         self.clear_colors();
-        for geometry in &self.geometries {
-            geometry.draw(self);
+        self.cubes.draw(self)?;
+        self.finish_frame(start, self.now())?;
+        Ok(())
+    }
+
+    /// Counters and timings of the last drawn frame
+    #[wasm_bindgen(getter)]
+    pub fn stats(&self) -> FrameStats {
+        self.stats.get()
+    }
+
+    /// Shows the frame stats over the canvas
+    pub fn set_stats_overlay(&mut self, visible: bool) -> Result<(), Error> {
+        if !visible {
+            self.overlay = None;
+            return Ok(());
+        }
+        if self.overlay.is_none() {
+            let canvas = self
+                .canvas
+                .as_ref()
+                .ok_or_else(|| Error::new("A headless scene has no canvas to show stats on"))?;
+            self.overlay = Some(StatsOverlay::new(&canvas.borrow())?);
         }
+        Ok(())
     }
 }
 
 impl Drop for Scene {
     fn drop(&mut self) {
-        self.canvas.borrow().remove();
-        // also remove event listeners
+        if let Some(canvas) = &self.canvas
+            && self.owns_canvas
+        {
+            canvas.borrow().remove();
+        }
+        self.listeners.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::Capabilities;
+    use crate::glsl_type;
+    use crate::recording_backend::{Call, RecordingBackend};
+
+    #[test]
+    fn draws_headless_frame() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let mut scene = Scene::headless(Box::new(backend)).unwrap();
+        scene.update_state_at(16.0).unwrap();
+        calls.borrow_mut().clear();
+        scene.draw().unwrap();
+
+        let calls = calls.borrow();
+        let program = scene.get_program();
+        assert!(calls.contains(&Call::UseProgram(Some(program.compiled.id()))));
+
+        let view = program.uniform("uViewMatrix").unwrap().location;
+        let view_matrix = scene.get_camera().view().to_homogeneous();
+        assert!(calls.contains(&Call::UniformMatrixfv {
+            location: view,
+            columns: 4,
+            transpose: false,
+            data: view_matrix.as_slice().to_vec(),
+        }));
+        let shininess = program.uniform("uShininess").unwrap().location;
+        assert!(calls.contains(&Call::Uniformfv {
+            location: shininess,
+            components: 1,
+            data: vec![32.0],
+        }));
+
+        let draws: Vec<&Call> = calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    Call::DrawElements { .. } | Call::DrawElementsInstanced { .. }
+                )
+            })
+            .collect();
+        assert_eq!(draws.len(), 1);
+        let Call::DrawElementsInstanced {
+            mode,
+            count,
+            instance_count,
+            ..
+        } = draws[0]
+        else {
+            panic!("Expected an instanced draw, got {:?}", draws[0]);
+        };
+        assert_eq!(*mode, WebGlRenderingContext::TRIANGLES);
+        assert_eq!(*count, 36);
+        assert_eq!(*instance_count as usize, scene.get_cubes().instance_count());
+        assert_eq!(scene.stats().draw_calls, 1);
+    }
+
+    #[test]
+    fn fits_webgl1_attribute_locations() {
+        let scene = Scene::headless(Box::new(RecordingBackend::new())).unwrap();
+        let slots: i32 = scene
+            .get_program()
+            .attributes
+            .values()
+            .map(|attribute| glsl_type::attribute_slots(attribute.data_type))
+            .sum();
+        // the MAX_VERTEX_ATTRIBS every WebGL1 device has
+        assert!(slots <= 8, "{slots} attribute locations");
+    }
+
+    fn is_attribute_call(call: &Call) -> bool {
+        matches!(
+            call,
+            Call::VertexAttribPointer { .. }
+                | Call::EnableVertexAttribArray(_)
+                | Call::DisableVertexAttribArray(_)
+                | Call::VertexAttribDivisor { .. }
+        )
+    }
+
+    #[test]
+    fn records_instance_matrices_once() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let scene = Scene::headless(Box::new(backend)).unwrap();
+        scene.draw().unwrap();
+        let divisors = calls
+            .borrow()
+            .iter()
+            .filter(|call| matches!(call, Call::VertexAttribDivisor { divisor: 1, .. }))
+            .count();
+        // one per model matrix column
+        assert_eq!(divisors, 4);
+
+        calls.borrow_mut().clear();
+        scene.draw().unwrap();
+        assert!(!calls.borrow().iter().any(is_attribute_call));
+    }
+
+    #[test]
+    fn replays_instance_divisors_without_vertex_arrays() {
+        let capabilities = Capabilities {
+            vertex_array_objects: false,
+            ..Capabilities::webgl2()
+        };
+        let backend = RecordingBackend::with_capabilities(capabilities);
+        let calls = backend.calls();
+        let scene = Scene::headless(Box::new(backend)).unwrap();
+        scene.draw().unwrap();
+        calls.borrow_mut().clear();
+        scene.draw().unwrap();
+
+        let calls = calls.borrow();
+        let divisors: Vec<u32> = calls
+            .iter()
+            .filter_map(|call| match call {
+                Call::VertexAttribDivisor { divisor, .. } => Some(*divisor),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(divisors, [[1; 4], [0; 4]].concat());
+        let draw = calls
+            .iter()
+            .position(|call| matches!(call, Call::DrawElementsInstanced { .. }))
+            .unwrap();
+        assert!(
+            !calls[draw..]
+                .iter()
+                .any(|call| { matches!(call, Call::VertexAttribDivisor { divisor: 1, .. }) })
+        );
+    }
+
+    #[test]
+    fn deletes_every_object_when_dropped() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let scene = Scene::headless(Box::new(backend)).unwrap();
+        scene.draw().unwrap();
+        drop(scene);
+
+        let calls = calls.borrow();
+        let created: usize = calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    Call::CreateBuffer(_)
+                        | Call::CreateShader { .. }
+                        | Call::CreateProgram(_)
+                        | Call::CreateTexture(_)
+                        | Call::CreateVertexArray(_)
+                )
+            })
+            .count();
+        let deleted = calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    Call::DeleteBuffer(_)
+                        | Call::DeleteShader(_)
+                        | Call::DeleteProgram(_)
+                        | Call::DeleteTexture(_)
+                        | Call::DeleteVertexArray(_)
+                )
+            })
+            .count();
+        assert!(created > 0);
+        assert_eq!(created, deleted);
+    }
+
+    #[test]
+    fn draws_one_by_one_without_instancing() {
+        let backend = RecordingBackend::with_capabilities(Capabilities::webgl1());
+        let calls = backend.calls();
+        let scene = Scene::headless(Box::new(backend)).unwrap();
+        scene.draw().unwrap();
+
+        let draws = calls
+            .borrow()
+            .iter()
+            .filter(|call| matches!(call, Call::DrawElements { count: 36, .. }))
+            .count();
+        assert_eq!(draws, scene.get_cubes().instance_count());
     }
 }
@@ -0,0 +1,328 @@
+use std::cell::Cell;
+
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+use crate::error::Error;
+
+/// Handle to a node in a `SceneGraph`. Handles of removed nodes
+/// are never reused, so they can't point to somebody else's node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// Position, rotation and scale relative to the parent node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn from_translation(translation: Vector3<f32>) -> Self {
+        Transform {
+            translation,
+            ..Transform::identity()
+        }
+    }
+
+    /// Scales first, then rotates, then translates
+    pub fn to_homogeneous(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+fn missing(id: NodeId) -> Error {
+    Error::new(&format!("Node {id:?} is not in the scene graph"))
+}
+
+struct Node {
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Valid unless `dirty` is set
+    world: Cell<Matrix4<f32>>,
+    dirty: Cell<bool>,
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// A tree of transforms. World matrices are computed on demand,
+/// and only recomputed after the node or one of its ancestors changes
+#[derive(Default)]
+pub struct SceneGraph {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        SceneGraph::default()
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+
+    fn existing(&self, id: NodeId) -> Result<&Node, Error> {
+        self.node(id).ok_or_else(|| missing(id))
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    /// Adds a node under `parent`, or at the root when `parent` is `None`
+    pub fn add(&mut self, transform: Transform, parent: Option<NodeId>) -> Result<NodeId, Error> {
+        if let Some(parent) = parent {
+            self.existing(parent)?;
+        }
+        let node = Node {
+            transform,
+            parent,
+            children: vec![],
+            world: Cell::new(Matrix4::identity()),
+            dirty: Cell::new(true),
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        if let Some(parent) = parent.and_then(|parent| self.node_mut(parent)) {
+            parent.children.push(id);
+        }
+        Ok(id)
+    }
+
+    /// Removes the node along with all its descendants
+    pub fn remove(&mut self, id: NodeId) -> Result<(), Error> {
+        let parent = self.existing(id)?.parent;
+        self.detach(id, parent);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(id.index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the node under `parent`, or to the root when `parent` is `None`.
+    /// The local transform is kept, so the node moves along with its new parent
+    pub fn reparent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), Error> {
+        let old_parent = self.existing(id)?.parent;
+        if let Some(parent) = parent {
+            self.existing(parent)?;
+            if self.ancestors(parent).any(|ancestor| ancestor == id) {
+                return Err(Error::new(&format!(
+                    "Can't move node {id:?} under its own descendant {parent:?}"
+                )));
+            }
+        }
+
+        self.detach(id, old_parent);
+        if let Some(parent) = parent.and_then(|parent| self.node_mut(parent)) {
+            parent.children.push(id);
+        }
+        if let Some(node) = self.node_mut(id) {
+            node.parent = parent;
+        }
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    fn detach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        if let Some(parent) = parent.and_then(|parent| self.node_mut(parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+    }
+
+    /// The node itself, then its parent, up to the root
+    fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id), |id| self.node(*id).and_then(|node| node.parent))
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id).map_or(&[], |node| &node.children)
+    }
+
+    pub fn transform(&self, id: NodeId) -> Option<&Transform> {
+        self.node(id).map(|node| &node.transform)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) -> Result<(), Error> {
+        let node = self.node_mut(id).ok_or_else(|| missing(id))?;
+        node.transform = transform;
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    /// Changes the local transform in place
+    pub fn update_transform(
+        &mut self,
+        id: NodeId,
+        update: impl FnOnce(&mut Transform),
+    ) -> Result<(), Error> {
+        let node = self.node_mut(id).ok_or_else(|| missing(id))?;
+        update(&mut node.transform);
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    /// Descendants of a dirty node are always dirty,
+    /// so there is no need to go below one
+    fn mark_dirty(&self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.node(id) else {
+                continue;
+            };
+            if node.dirty.replace(true) {
+                continue;
+            }
+            stack.extend(&node.children);
+        }
+    }
+
+    /// Local to world transform, recomputed only for dirty nodes
+    pub fn world_matrix(&self, id: NodeId) -> Option<Matrix4<f32>> {
+        let node = self.node(id)?;
+        if !node.dirty.get() {
+            return Some(node.world.get());
+        }
+        let local = node.transform.to_homogeneous();
+        let world = match node.parent {
+            Some(parent) => self.world_matrix(parent)? * local,
+            None => local,
+        };
+        node.world.set(world);
+        node.dirty.set(false);
+        Some(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32) -> Transform {
+        Transform::from_translation(Vector3::new(x, 0.0, 0.0))
+    }
+
+    fn world_x(graph: &SceneGraph, id: NodeId) -> f32 {
+        graph.world_matrix(id).unwrap()[(0, 3)]
+    }
+
+    #[test]
+    fn stale_handles_miss_reused_slots() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add(translation(1.0), None).unwrap();
+        let child = graph.add(translation(2.0), Some(parent)).unwrap();
+        graph.remove(parent).unwrap();
+        assert!(!graph.contains(parent));
+        assert!(!graph.contains(child));
+
+        let fresh = graph.add(translation(3.0), None).unwrap();
+        assert_ne!(fresh, parent);
+        assert_ne!(fresh, child);
+        assert!(graph.world_matrix(child).is_none());
+        assert!(graph.set_transform(parent, translation(4.0)).is_err());
+        assert!(graph.remove(child).is_err());
+        assert_eq!(world_x(&graph, fresh), 3.0);
+    }
+
+    #[test]
+    fn children_follow_their_parent() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add(translation(1.0), None).unwrap();
+        let child = graph.add(translation(2.0), Some(parent)).unwrap();
+        assert_eq!(world_x(&graph, child), 3.0);
+
+        graph.set_transform(parent, translation(10.0)).unwrap();
+        assert_eq!(world_x(&graph, child), 12.0);
+        graph
+            .update_transform(parent, |transform| transform.scale *= 2.0)
+            .unwrap();
+        assert_eq!(world_x(&graph, child), 14.0);
+
+        // the local transform is kept when moving to the root
+        graph.reparent(child, None).unwrap();
+        assert_eq!(world_x(&graph, child), 2.0);
+        assert!(graph.children(parent).is_empty());
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let mut graph = SceneGraph::new();
+        let root = graph.add(translation(1.0), None).unwrap();
+        let child = graph.add(translation(1.0), Some(root)).unwrap();
+        let grandchild = graph.add(translation(1.0), Some(child)).unwrap();
+
+        assert!(graph.reparent(root, Some(grandchild)).is_err());
+        assert!(graph.reparent(child, Some(child)).is_err());
+        assert_eq!(graph.parent(root), None);
+        assert_eq!(graph.parent(child), Some(root));
+        assert_eq!(graph.children(grandchild), []);
+
+        graph.reparent(grandchild, Some(root)).unwrap();
+        assert_eq!(graph.children(root), [child, grandchild]);
+        assert_eq!(world_x(&graph, grandchild), 2.0);
+    }
+}
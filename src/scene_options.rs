@@ -0,0 +1,82 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlContextAttributes, WebGlPowerPreference};
+
+/// Which GPU the browser should pick on systems with more than one
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerPreference {
+    #[default]
+    Default,
+    LowPower,
+    HighPerformance,
+}
+
+impl From<PowerPreference> for WebGlPowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::Default => WebGlPowerPreference::Default,
+            PowerPreference::LowPower => WebGlPowerPreference::LowPower,
+            PowerPreference::HighPerformance => WebGlPowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// How a scene sets up its canvas and rendering context.
+/// `antialias`, `alpha`, `preserve_drawing_buffer` and `power_preference`
+/// are passed to `getContext` and can't change afterwards
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneOptions {
+    /// CSS width of the canvas. When not set, a canvas created by
+    /// the scene is 640px wide, or fills its container, and an
+    /// existing canvas keeps its own size
+    pub width: Option<u32>,
+    /// CSS height of the canvas, see `width`
+    pub height: Option<u32>,
+    #[wasm_bindgen(skip)]
+    pub clear_color: [f32; 4],
+    pub antialias: bool,
+    pub alpha: bool,
+    pub preserve_drawing_buffer: bool,
+    pub power_preference: PowerPreference,
+    /// Shows the frame stats over the canvas, see `Scene::set_stats_overlay`
+    pub stats_overlay: bool,
+}
+
+impl Default for SceneOptions {
+    fn default() -> Self {
+        SceneOptions {
+            width: None,
+            height: None,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            antialias: true,
+            alpha: true,
+            preserve_drawing_buffer: false,
+            power_preference: PowerPreference::Default,
+            stats_overlay: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl SceneOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SceneOptions {
+        SceneOptions::default()
+    }
+
+    pub fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.clear_color = [red, green, blue, alpha];
+    }
+}
+
+impl SceneOptions {
+    pub fn context_attributes(&self) -> WebGlContextAttributes {
+        let attributes = WebGlContextAttributes::new();
+        attributes.set_antialias(self.antialias);
+        attributes.set_alpha(self.alpha);
+        attributes.set_preserve_drawing_buffer(self.preserve_drawing_buffer);
+        attributes.set_power_preference(self.power_preference.into());
+        attributes
+    }
+}
@@ -1,5 +0,0 @@
-varying lowp vec4 vColor;
-
-void main() {
-  gl_FragColor = vColor;
-}
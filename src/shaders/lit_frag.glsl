@@ -0,0 +1,73 @@
+precision mediump float;
+
+// Lights are in view space, the sizes match `light::MAX_LIGHTS`
+uniform vec3 uAmbientLight;
+
+uniform int uDirectionalLightCount;
+// Towards the light
+uniform vec3 uDirectionalLightDirections[4];
+uniform vec3 uDirectionalLightColors[4];
+
+uniform int uPointLightCount;
+uniform vec3 uPointLightPositions[4];
+uniform vec3 uPointLightColors[4];
+uniform float uPointLightRanges[4];
+
+uniform int uSpotLightCount;
+uniform vec3 uSpotLightPositions[4];
+// Towards the light
+uniform vec3 uSpotLightDirections[4];
+uniform vec3 uSpotLightColors[4];
+// Range, then the cosines of the inner and outer angles
+uniform vec3 uSpotLightParameters[4];
+
+uniform vec3 uSpecularColor;
+uniform float uShininess;
+
+varying highp vec3 vPosition;
+varying highp vec3 vNormal;
+varying lowp vec4 vColor;
+
+// Light reflected towards the camera, which sits at the origin
+vec3 blinnPhong(vec3 normal, vec3 toLight, vec3 toEye, vec3 color) {
+  float diffuse = max(dot(normal, toLight), 0.0);
+  vec3 halfway = normalize(toLight + toEye);
+  float specular = diffuse > 0.0 ? pow(max(dot(normal, halfway), 0.0), uShininess) : 0.0;
+  return color * (vColor.rgb * diffuse + uSpecularColor * specular);
+}
+
+// Smoothly reaches zero at the range
+float attenuation(float distance, float range) {
+  float falloff = clamp(1.0 - (distance * distance) / (range * range), 0.0, 1.0);
+  return falloff * falloff;
+}
+
+void main() {
+  vec3 normal = normalize(vNormal);
+  vec3 toEye = normalize(-vPosition);
+  vec3 light = vColor.rgb * uAmbientLight;
+
+  for (int i = 0; i < 4; i++) {
+    if (i >= uDirectionalLightCount) break;
+    light += blinnPhong(normal, uDirectionalLightDirections[i], toEye, uDirectionalLightColors[i]);
+  }
+
+  for (int i = 0; i < 4; i++) {
+    if (i >= uPointLightCount) break;
+    vec3 offset = uPointLightPositions[i] - vPosition;
+    vec3 color = uPointLightColors[i] * attenuation(length(offset), uPointLightRanges[i]);
+    light += blinnPhong(normal, normalize(offset), toEye, color);
+  }
+
+  for (int i = 0; i < 4; i++) {
+    if (i >= uSpotLightCount) break;
+    vec3 offset = uSpotLightPositions[i] - vPosition;
+    vec3 toLight = normalize(offset);
+    vec3 parameters = uSpotLightParameters[i];
+    float cone = smoothstep(parameters.z, parameters.y, dot(toLight, uSpotLightDirections[i]));
+    vec3 color = uSpotLightColors[i] * cone * attenuation(length(offset), parameters.x);
+    light += blinnPhong(normal, toLight, toEye, color);
+  }
+
+  gl_FragColor = vec4(light, vColor.a);
+}
@@ -0,0 +1,35 @@
+attribute vec4 aVertexPosition;
+attribute vec3 aVertexNormal;
+attribute vec4 aVertexColor;
+attribute mat4 aModelMatrix;
+
+uniform mat4 uViewMatrix;
+uniform mat4 uProjectionMatrix;
+
+varying highp vec3 vPosition;
+varying highp vec3 vNormal;
+varying lowp vec4 vColor;
+
+// Inverse transpose of the upper 3x3 of the model matrix. Its columns are
+// cross products of the model columns over the determinant, of which only
+// the sign matters since the normal is normalized later. Deriving it here
+// keeps the attributes within the 8 locations WebGL1 guarantees
+mat3 normalMatrix(mat4 model) {
+  vec3 x = model[0].xyz;
+  vec3 y = model[1].xyz;
+  vec3 z = model[2].xyz;
+  vec3 yz = cross(y, z);
+  return sign(dot(x, yz)) * mat3(yz, cross(z, x), cross(x, y));
+}
+
+void main() {
+  vec4 position = uViewMatrix * aModelMatrix * aVertexPosition;
+  // The view is a rigid transform, so the normal matrix of the
+  // model-view is the view rotation times the model's normal matrix
+  mat3 viewRotation = mat3(uViewMatrix[0].xyz, uViewMatrix[1].xyz, uViewMatrix[2].xyz);
+
+  gl_Position = uProjectionMatrix * position;
+  vPosition = position.xyz;
+  vNormal = viewRotation * normalMatrix(aModelMatrix) * aVertexNormal;
+  vColor = aVertexColor;
+}
@@ -0,0 +1,575 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use nalgebra::Vector3;
+
+use crate::geometry::Geometry;
+
+/// Collects vertices and triangles, counter clockwise when seen
+/// from the side the normals point to
+#[derive(Default)]
+struct ShapeBuilder {
+    vertices: Vec<f32>,
+    normals: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+impl ShapeBuilder {
+    fn vertex(&mut self, position: Vector3<f32>, normal: Vector3<f32>, uv: [f32; 2]) -> u32 {
+        let index = self.vertices.len() / 3;
+        self.vertices.extend(position.iter());
+        self.normals.extend(normal.normalize().iter());
+        self.uvs.extend(uv);
+        index as u32
+    }
+
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend([a, b, c]);
+    }
+
+    /// Corners in counter clockwise order
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Triangles between rows of a surface of revolution, from the top down.
+    /// Triangles touching a row collapsed into a single point are skipped
+    fn grid(&mut self, rows: &[Vec<u32>], collapsed: impl Fn(usize) -> bool) {
+        for (row, pair) in rows.windows(2).enumerate() {
+            let (top, bottom) = (&pair[0], &pair[1]);
+            for column in 0..top.len() - 1 {
+                let (a, b) = (top[column + 1], top[column]);
+                let (c, d) = (bottom[column], bottom[column + 1]);
+                if !collapsed(row) {
+                    self.triangle(a, b, d);
+                }
+                if !collapsed(row + 1) {
+                    self.triangle(b, c, d);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Geometry {
+        let mut geometry = Geometry::new(self.indices, self.vertices)
+            .with_normals(self.normals)
+            .with_uvs(self.uvs);
+        geometry.compute_tangents();
+        geometry
+    }
+}
+
+/// A point on a sphere of radius 1, `polar` going from 0 at the top
+/// to PI at the bottom and `azimuth` going around the Y axis
+fn sphere_point(polar: f32, azimuth: f32) -> Vector3<f32> {
+    Vector3::new(
+        -azimuth.cos() * polar.sin(),
+        polar.cos(),
+        azimuth.sin() * polar.sin(),
+    )
+}
+
+/// A row of a surface of revolution around the Y axis
+struct Ring {
+    polar: f32,
+    /// Added to the Y coordinate, splits the capsule halves
+    offset: f32,
+    v: f32,
+}
+
+/// Rotates `rings` around the Y axis. The first and the last ring are
+/// expected to be the poles, where triangles collapse
+fn revolve(radius: f32, segments: u32, rings: &[Ring]) -> Geometry {
+    let mut builder = ShapeBuilder::default();
+    let rows: Vec<Vec<u32>> = rings
+        .iter()
+        .map(|ring| {
+            (0..=segments)
+                .map(|segment| {
+                    let u = segment as f32 / segments as f32;
+                    let normal = sphere_point(ring.polar, u * TAU);
+                    let position = normal * radius + Vector3::y() * ring.offset;
+                    builder.vertex(position, normal, [u, ring.v])
+                })
+                .collect()
+        })
+        .collect();
+    let last = rows.len() - 1;
+    builder.grid(&rows, |row| row == 0 || row == last);
+    builder.finish()
+}
+
+/// Sphere made of `segments` slices around the Y axis and
+/// `rings` stacks from pole to pole
+pub fn get_uv_sphere(radius: f32, segments: u32, rings: u32) -> Geometry {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+    let rings: Vec<_> = (0..=rings)
+        .map(|ring| {
+            let v = ring as f32 / rings as f32;
+            Ring {
+                polar: v * PI,
+                offset: 0.0,
+                v: 1.0 - v,
+            }
+        })
+        .collect();
+    revolve(radius, segments, &rings)
+}
+
+/// Sphere made of evenly sized triangles, from an icosahedron
+/// with every triangle split in four `subdivisions` times
+pub fn get_icosphere(radius: f32, subdivisions: u32) -> Geometry {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    #[rustfmt::skip]
+    let corners = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ];
+    let mut points: Vec<Vector3<f32>> = corners
+        .iter()
+        .map(|corner| Vector3::from(*corner).normalize())
+        .collect();
+    #[rustfmt::skip]
+    let mut triangles: Vec<[usize; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut middles = HashMap::new();
+        let mut middle = |a: usize, b: usize| {
+            *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push(((points[a] + points[b]) / 2.0).normalize());
+                points.len() - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (middle(a, b), middle(b, c), middle(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // UVs are spherical, vertices on the seam and at the poles
+    // are duplicated for every U they are seen with
+    let mut builder = ShapeBuilder::default();
+    let mut welded = HashMap::new();
+    for triangle in triangles {
+        let mut us = triangle.map(|point| {
+            let point = points[point];
+            0.5 + point.z.atan2(-point.x) / TAU
+        });
+        let wraps = us.iter().fold(0.0f32, |max, u| max.max(*u))
+            - us.iter().fold(1.0f32, |min, u| min.min(*u))
+            > 0.5;
+        if wraps {
+            us.iter_mut().filter(|u| **u < 0.5).for_each(|u| *u += 1.0);
+        }
+        for corner in 0..3 {
+            if points[triangle[corner]].y.abs() > 1.0 - 1.0e-6 {
+                us[corner] = (us[(corner + 1) % 3] + us[(corner + 2) % 3]) / 2.0;
+            }
+        }
+
+        let corners = [0, 1, 2].map(|corner| {
+            let point = points[triangle[corner]];
+            let u = us[corner];
+            *welded
+                .entry((triangle[corner], u.to_bits()))
+                .or_insert_with(|| {
+                    let v = 0.5 + point.y.asin() / PI;
+                    builder.vertex(point * radius, point, [u, v])
+                })
+        });
+        builder.triangle(corners[0], corners[1], corners[2]);
+    }
+    builder.finish()
+}
+
+/// Flat rectangle on the XZ plane, facing up. U goes along X,
+/// V along -Z, and every side is split into `subdivisions` parts
+pub fn get_plane(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> Geometry {
+    let columns = subdivisions_x.max(1);
+    let rows = subdivisions_z.max(1);
+    let mut builder = ShapeBuilder::default();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+            let position = Vector3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+            builder.vertex(position, Vector3::y(), [u, 1.0 - v]);
+        }
+    }
+    let index = |column: u32, row: u32| row * (columns + 1) + column;
+    for row in 0..rows {
+        for column in 0..columns {
+            builder.quad(
+                index(column, row),
+                index(column, row + 1),
+                index(column + 1, row + 1),
+                index(column + 1, row),
+            );
+        }
+    }
+    builder.finish()
+}
+
+/// Disk on the end of a cylinder or a cone, facing up or down
+fn cap(builder: &mut ShapeBuilder, radius: f32, y: f32, segments: u32, up: bool) {
+    let normal = match up {
+        true => Vector3::y(),
+        false => -Vector3::y(),
+    };
+    let center = builder.vertex(Vector3::new(0.0, y, 0.0), normal, [0.5, 0.5]);
+    let rim: Vec<u32> = (0..=segments)
+        .map(|segment| {
+            let angle = segment as f32 / segments as f32 * TAU;
+            let (sin, cos) = angle.sin_cos();
+            let position = Vector3::new(radius * sin, y, radius * cos);
+            let uv = match up {
+                true => [0.5 + sin / 2.0, 0.5 - cos / 2.0],
+                false => [0.5 + sin / 2.0, 0.5 + cos / 2.0],
+            };
+            builder.vertex(position, normal, uv)
+        })
+        .collect();
+    for pair in rim.windows(2) {
+        match up {
+            true => builder.triangle(center, pair[0], pair[1]),
+            false => builder.triangle(center, pair[1], pair[0]),
+        }
+    }
+}
+
+/// Side of a cylinder whose radius changes from bottom to top, with caps
+fn frustum(bottom_radius: f32, top_radius: f32, height: f32, segments: u32) -> Geometry {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let slope = (bottom_radius - top_radius) / height;
+    let mut builder = ShapeBuilder::default();
+
+    let rows: Vec<Vec<u32>> = [(bottom_radius, -half, 0.0), (top_radius, half, 1.0)]
+        .iter()
+        .map(|&(radius, y, v)| {
+            (0..=segments)
+                .map(|segment| {
+                    let u = segment as f32 / segments as f32;
+                    let (sin, cos) = (u * TAU).sin_cos();
+                    let position = Vector3::new(radius * sin, y, radius * cos);
+                    let normal = Vector3::new(sin, slope, cos);
+                    builder.vertex(position, normal, [u, v])
+                })
+                .collect()
+        })
+        .collect();
+    for segment in 0..segments as usize {
+        let (a, b) = (rows[0][segment], rows[0][segment + 1]);
+        let (c, d) = (rows[1][segment + 1], rows[1][segment]);
+        if bottom_radius > 0.0 {
+            builder.triangle(a, b, c);
+        }
+        if top_radius > 0.0 {
+            builder.triangle(a, c, d);
+        }
+    }
+
+    if bottom_radius > 0.0 {
+        cap(&mut builder, bottom_radius, -half, segments, false);
+    }
+    if top_radius > 0.0 {
+        cap(&mut builder, top_radius, half, segments, true);
+    }
+    builder.finish()
+}
+
+/// Closed cylinder along the Y axis, centered on the origin
+pub fn get_cylinder(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, radius, height, segments)
+}
+
+/// Cone along the Y axis with its tip up, centered on the origin
+pub fn get_cone(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, 0.0, height, segments)
+}
+
+/// Ring lying on the XZ plane. `radius` goes to the center of the tube,
+/// `radial_segments` go around the tube and `tubular_segments` along it
+pub fn get_torus(
+    radius: f32,
+    tube_radius: f32,
+    radial_segments: u32,
+    tubular_segments: u32,
+) -> Geometry {
+    let radial = radial_segments.max(3);
+    let tubular = tubular_segments.max(3);
+    let mut builder = ShapeBuilder::default();
+    for j in 0..=radial {
+        for i in 0..=tubular {
+            let (u, v) = (i as f32 / tubular as f32, j as f32 / radial as f32);
+            let (sin_u, cos_u) = (u * TAU).sin_cos();
+            let (sin_v, cos_v) = (v * TAU).sin_cos();
+            let center = Vector3::new(radius * cos_u, 0.0, -radius * sin_u);
+            let normal = Vector3::new(cos_v * cos_u, sin_v, -cos_v * sin_u);
+            builder.vertex(center + normal * tube_radius, normal, [u, v]);
+        }
+    }
+    let index = |i: u32, j: u32| j * (tubular + 1) + i;
+    for j in 0..radial {
+        for i in 0..tubular {
+            builder.quad(
+                index(i, j),
+                index(i + 1, j),
+                index(i + 1, j + 1),
+                index(i, j + 1),
+            );
+        }
+    }
+    builder.finish()
+}
+
+/// Cylinder of `height` along the Y axis with a half sphere on each end,
+/// `rings` is the number of stacks in each half sphere
+pub fn get_capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Geometry {
+    let segments = segments.max(3);
+    let rings = rings.max(1);
+    let half = height / 2.0;
+    // V follows the distance along the surface from top to bottom
+    let length = PI * radius + height;
+    let quarter = FRAC_PI_2 * radius;
+
+    let halves = [(half, 0.0, 0.0), (-half, FRAC_PI_2, quarter + height)];
+    let mut all = vec![];
+    for (offset, polar, distance) in halves {
+        for ring in 0..=rings {
+            let t = ring as f32 / rings as f32;
+            all.push(Ring {
+                polar: polar + t * FRAC_PI_2,
+                offset,
+                v: 1.0 - (distance + t * quarter) / length,
+            });
+        }
+    }
+    if height == 0.0 {
+        // both halves meet at the equator, which is the same ring twice
+        all.remove(rings as usize + 1);
+    }
+    revolve(radius, segments, &all)
+}
+
+/// Coordinates along one side of a rounded box: `segments` steps around
+/// each rounded edge, nothing in between
+fn rounded_steps(size: f32, radius: f32, segments: u32) -> Vec<f32> {
+    let half = size / 2.0;
+    let mut steps: Vec<f32> = (0..=segments)
+        .map(|step| -half + radius * step as f32 / segments as f32)
+        .chain((0..=segments).map(|step| half - radius + radius * step as f32 / segments as f32))
+        .collect();
+    steps.dedup_by(|a, b| (*a - *b).abs() < 1.0e-6);
+    steps
+}
+
+/// Box centered on the origin with edges and corners rounded by `radius`,
+/// each rounded edge made of `segments` steps
+pub fn get_rounded_box(
+    width: f32,
+    height: f32,
+    depth: f32,
+    radius: f32,
+    segments: u32,
+) -> Geometry {
+    let size = Vector3::new(width, height, depth);
+    let half = size / 2.0;
+    let radius = radius.clamp(0.0, half.min());
+    let segments = segments.max(1);
+    let inner = half - Vector3::repeat(radius);
+    let steps = [0, 1, 2].map(|axis| rounded_steps(size[axis], radius, segments));
+
+    // normal, then the U and V directions, with U x V = normal
+    let faces = [
+        (Vector3::x(), -Vector3::z(), Vector3::y()),
+        (-Vector3::x(), Vector3::z(), Vector3::y()),
+        (Vector3::y(), Vector3::x(), -Vector3::z()),
+        (-Vector3::y(), Vector3::x(), Vector3::z()),
+        (Vector3::z(), Vector3::x(), Vector3::y()),
+        (-Vector3::z(), -Vector3::x(), Vector3::y()),
+    ];
+    let axis = |direction: &Vector3<f32>| direction.iamax();
+
+    let mut builder = ShapeBuilder::default();
+    for (normal, u_direction, v_direction) in faces {
+        let (u_axis, v_axis) = (axis(&u_direction), axis(&v_direction));
+        let (u_steps, v_steps) = (&steps[u_axis], &steps[v_axis]);
+        let face_center = normal.component_mul(&half);
+
+        let mut rows = vec![];
+        for &v_step in v_steps {
+            let mut row = vec![];
+            for &u_step in u_steps {
+                let flat = face_center + u_direction * u_step + v_direction * v_step;
+                // the point on the shrunk box the surface is rounded around
+                let core = flat.zip_map(&inner, |value, limit| value.clamp(-limit, limit));
+                let outward = (flat - core).try_normalize(1.0e-6).unwrap_or(normal);
+                let position = core + outward * radius;
+                let uv = [0.5 + u_step / size[u_axis], 0.5 + v_step / size[v_axis]];
+                row.push(builder.vertex(position, outward, uv));
+            }
+            rows.push(row);
+        }
+        for pair in rows.windows(2) {
+            for u in 0..pair[0].len() - 1 {
+                builder.quad(pair[0][u], pair[0][u + 1], pair[1][u + 1], pair[1][u]);
+            }
+        }
+    }
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(values: &[f32], index: usize) -> Vector3<f32> {
+        Vector3::from_column_slice(&values[index * 3..index * 3 + 3])
+    }
+
+    /// Indices in range, counter clockwise triangles agreeing with
+    /// the normals, unit normals and tangents perpendicular to them
+    fn check(geometry: &Geometry) {
+        let count = geometry.vertex_count();
+        assert!(count > 0);
+        assert_eq!(geometry.normals.len(), count * 3);
+        assert_eq!(geometry.uvs.len(), count * 2);
+        assert_eq!(geometry.tangents.len(), count * 4);
+        assert_eq!(geometry.indices.len() % 3, 0);
+        assert!(
+            geometry
+                .indices
+                .iter()
+                .all(|&index| (index as usize) < count)
+        );
+
+        for triangle in geometry.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+            let position = |index| vector(&geometry.vertices, index);
+            let face = (position(b) - position(a)).cross(&(position(c) - position(a)));
+            assert!(face.norm() > 1.0e-9, "degenerate triangle {triangle:?}");
+            let normals = vector(&geometry.normals, a)
+                + vector(&geometry.normals, b)
+                + vector(&geometry.normals, c);
+            assert!(
+                face.dot(&normals) > 0.0,
+                "triangle {triangle:?} faces inwards"
+            );
+        }
+
+        for vertex in 0..count {
+            let normal = vector(&geometry.normals, vertex);
+            assert!((normal.norm() - 1.0).abs() < 1.0e-5, "normal {normal:?}");
+            let tangent = &geometry.tangents[vertex * 4..vertex * 4 + 4];
+            let direction = Vector3::new(tangent[0], tangent[1], tangent[2]);
+            assert!((direction.norm() - 1.0).abs() < 1.0e-5);
+            assert!(direction.dot(&normal).abs() < 1.0e-4);
+            assert_eq!(tangent[3].abs(), 1.0);
+        }
+    }
+
+    fn distances_from_origin(geometry: &Geometry) -> impl Iterator<Item = f32> + '_ {
+        (0..geometry.vertex_count()).map(|vertex| vector(&geometry.vertices, vertex).norm())
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let sphere = get_uv_sphere(2.0, 16, 8);
+        check(&sphere);
+        assert!(distances_from_origin(&sphere).all(|distance| (distance - 2.0).abs() < 1.0e-5));
+        // poles skip one triangle of every quad
+        assert_eq!(sphere.indices.len(), (16 * 8 * 2 - 2 * 16) * 3);
+    }
+
+    #[test]
+    fn icosphere() {
+        let sphere = get_icosphere(1.5, 3);
+        check(&sphere);
+        assert_eq!(sphere.indices.len(), 20 * 4usize.pow(3) * 3);
+        assert!(distances_from_origin(&sphere).all(|distance| (distance - 1.5).abs() < 1.0e-5));
+    }
+
+    #[test]
+    fn plane() {
+        let plane = get_plane(4.0, 2.0, 4, 2);
+        check(&plane);
+        assert_eq!(plane.vertex_count(), 5 * 3);
+        assert_eq!(plane.indices.len(), 4 * 2 * 6);
+        assert!(
+            plane
+                .normals
+                .chunks(3)
+                .all(|normal| normal == [0.0, 1.0, 0.0])
+        );
+        // U follows X, so the tangents do too
+        assert!(
+            plane
+                .tangents
+                .chunks(4)
+                .all(|tangent| tangent == [1.0, 0.0, 0.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn cylinder() {
+        let cylinder = get_cylinder(1.0, 3.0, 12);
+        check(&cylinder);
+        let heights = cylinder.vertices.chunks(3).map(|position| position[1]);
+        assert!(heights.into_iter().all(|y| y.abs() == 1.5));
+    }
+
+    #[test]
+    fn cone() {
+        let cone = get_cone(1.0, 2.0, 12);
+        check(&cone);
+        // the sides and the bottom, no top cap
+        assert_eq!(cone.indices.len(), (12 + 12) * 3);
+    }
+
+    #[test]
+    fn torus() {
+        let torus = get_torus(2.0, 0.5, 8, 16);
+        check(&torus);
+        for vertex in 0..torus.vertex_count() {
+            let position = vector(&torus.vertices, vertex);
+            let center = Vector3::new(position.x, 0.0, position.z).normalize() * 2.0;
+            assert!(((position - center).norm() - 0.5).abs() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn capsule() {
+        let capsule = get_capsule(0.5, 2.0, 12, 4);
+        check(&capsule);
+        let top = capsule.vertices.chunks(3).map(|position| position[1]);
+        assert!((top.fold(0.0, f32::max) - 1.5).abs() < 1.0e-5);
+
+        check(&get_capsule(1.0, 0.0, 8, 3));
+    }
+
+    #[test]
+    fn rounded_box() {
+        let rounded = get_rounded_box(2.0, 1.0, 3.0, 0.25, 3);
+        check(&rounded);
+        for position in rounded.vertices.chunks(3) {
+            assert!(position[0].abs() <= 1.0 + 1.0e-5);
+            assert!(position[1].abs() <= 0.5 + 1.0e-5);
+            assert!(position[2].abs() <= 1.5 + 1.0e-5);
+        }
+
+        check(&get_rounded_box(1.0, 1.0, 1.0, 0.0, 2));
+        check(&get_rounded_box(1.0, 1.0, 1.0, 0.5, 4));
+    }
+}
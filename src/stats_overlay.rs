@@ -0,0 +1,56 @@
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, HtmlElement};
+
+use crate::error::Error;
+use crate::render_stats::FrameStats;
+
+const STYLE: [(&str, &str); 9] = [
+    ("position", "absolute"),
+    ("pointer-events", "none"),
+    ("margin", "4px"),
+    ("padding", "4px 6px"),
+    ("background", "rgba(0, 0, 0, 0.6)"),
+    ("color", "white"),
+    ("font", "11px monospace"),
+    ("white-space", "pre"),
+    ("z-index", "1"),
+];
+
+/// Frame stats shown over the top left corner of the canvas.
+/// The element is placed right after the canvas and removed when dropped
+pub struct StatsOverlay {
+    element: HtmlElement,
+}
+
+impl StatsOverlay {
+    pub fn new(canvas: &HtmlCanvasElement) -> Result<Self, Error> {
+        let document = canvas
+            .owner_document()
+            .ok_or_else(|| Error::new("The canvas has no document"))?;
+        let element = document
+            .create_element("div")?
+            .dyn_into::<HtmlElement>()
+            .map_err(|_| Error::new("Can't cast created element to HtmlElement"))?;
+        let style = element.style();
+        for (property, value) in STYLE {
+            style.set_property(property, value)?;
+        }
+        canvas.after_with_node_1(&element)?;
+        Ok(StatsOverlay { element })
+    }
+
+    /// Follows the canvas, in case the layout moved it
+    pub fn show(&self, canvas: &HtmlCanvasElement, stats: &FrameStats) -> Result<(), Error> {
+        let style = self.element.style();
+        style.set_property("left", &format!("{}px", canvas.offset_left()))?;
+        style.set_property("top", &format!("{}px", canvas.offset_top()))?;
+        self.element.set_text_content(Some(&stats.summary()));
+        Ok(())
+    }
+}
+
+impl Drop for StatsOverlay {
+    fn drop(&mut self) {
+        self.element.remove();
+    }
+}
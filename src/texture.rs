@@ -0,0 +1,422 @@
+use web_sys::{WebGl2RenderingContext as Gl2, WebGlRenderingContext as Gl};
+
+use crate::backend::{ImageSource, TextureId, TextureUpload};
+use crate::capabilities::GlVersion;
+use crate::error::Error;
+use crate::gl_object::OwnedTexture;
+use crate::image_decoder::DecodedImage;
+use crate::rendering_context::RenderingContext;
+
+/// `SRGB_ALPHA_EXT` from `EXT_sRGB`
+const SRGB_ALPHA: u32 = 0x8c42;
+
+/// Layout of the pixel data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    /// sRGB encoded color with linear alpha, shaders read it linear.
+    /// Needs `Capabilities::srgb_textures`, and WebGL1 can't mipmap it
+    Srgba8,
+    Rgb8,
+    /// A single channel. Shaders read it from `.r` on WebGL2,
+    /// WebGL1 stores it as luminance and repeats it in `.rgb`
+    R8,
+    /// Needs `Capabilities::float_textures`, and `Capabilities::float_linear`
+    /// for linear filtering. Never mipmapped
+    Rgba32F,
+}
+
+impl TextureFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            TextureFormat::Rgba8 | TextureFormat::Srgba8 => 4,
+            TextureFormat::Rgb8 => 3,
+            TextureFormat::R8 => 1,
+            TextureFormat::Rgba32F => 16,
+        }
+    }
+
+    fn upload(self, version: GlVersion) -> TextureUpload {
+        let webgl2 = version == GlVersion::WebGl2;
+        let (internal_format, format, data_type) = match self {
+            TextureFormat::Rgba8 if webgl2 => (Gl2::RGBA8, Gl::RGBA, Gl::UNSIGNED_BYTE),
+            TextureFormat::Srgba8 if webgl2 => (Gl2::SRGB8_ALPHA8, Gl::RGBA, Gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 if webgl2 => (Gl2::RGB8, Gl::RGB, Gl::UNSIGNED_BYTE),
+            TextureFormat::R8 if webgl2 => (Gl2::R8, Gl2::RED, Gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba32F if webgl2 => (Gl2::RGBA32F, Gl::RGBA, Gl::FLOAT),
+            TextureFormat::Rgba8 => (Gl::RGBA, Gl::RGBA, Gl::UNSIGNED_BYTE),
+            TextureFormat::Srgba8 => (SRGB_ALPHA, SRGB_ALPHA, Gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 => (Gl::RGB, Gl::RGB, Gl::UNSIGNED_BYTE),
+            TextureFormat::R8 => (Gl::LUMINANCE, Gl::LUMINANCE, Gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba32F => (Gl::RGBA, Gl::RGBA, Gl::FLOAT),
+        };
+        TextureUpload {
+            target: Gl::TEXTURE_2D,
+            level: 0,
+            internal_format: internal_format as i32,
+            format,
+            data_type,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    ClampToEdge,
+    MirroredRepeat,
+}
+
+impl Wrap {
+    fn gl_enum(self) -> u32 {
+        match self {
+            Wrap::Repeat => Gl::REPEAT,
+            Wrap::ClampToEdge => Gl::CLAMP_TO_EDGE,
+            Wrap::MirroredRepeat => Gl::MIRRORED_REPEAT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// How the texture is sampled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Filter between mipmap levels. Mipmaps are generated on upload
+    /// when it is set, `None` samples the full size image only
+    pub mipmap_filter: Option<Filter>,
+}
+
+impl Default for SamplerOptions {
+    /// Repeating, with trilinear filtering
+    fn default() -> Self {
+        SamplerOptions {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: Some(Filter::Linear),
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Clamped, without mipmaps, fits images of any size on WebGL1
+    pub fn clamped(filter: Filter) -> Self {
+        SamplerOptions {
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            min_filter: filter,
+            mag_filter: filter,
+            mipmap_filter: None,
+        }
+    }
+
+    fn min_filter_enum(&self) -> u32 {
+        use Filter::*;
+        match (self.min_filter, self.mipmap_filter) {
+            (Nearest, None) => Gl::NEAREST,
+            (Linear, None) => Gl::LINEAR,
+            (Nearest, Some(Nearest)) => Gl::NEAREST_MIPMAP_NEAREST,
+            (Linear, Some(Nearest)) => Gl::LINEAR_MIPMAP_NEAREST,
+            (Nearest, Some(Linear)) => Gl::NEAREST_MIPMAP_LINEAR,
+            (Linear, Some(Linear)) => Gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn mag_filter_enum(&self) -> u32 {
+        match self.mag_filter {
+            Filter::Nearest => Gl::NEAREST,
+            Filter::Linear => Gl::LINEAR,
+        }
+    }
+}
+
+/// A 2D texture living on the graphics card
+pub struct Texture2D {
+    texture: OwnedTexture,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    options: SamplerOptions,
+}
+
+impl Texture2D {
+    /// `pixels` are tightly packed rows, starting from the first one in memory
+    pub fn from_pixels(
+        ctx: &RenderingContext,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        pixels: &[u8],
+        options: SamplerOptions,
+    ) -> Result<Self, Error> {
+        let expected = width as usize * height as usize * format.bytes_per_pixel();
+        if pixels.len() != expected {
+            return Err(Error::new(&format!(
+                "A {width}x{height} {format:?} texture takes {expected} bytes, got {}",
+                pixels.len()
+            )));
+        }
+
+        let texture = Texture2D::create(ctx, width, height, format, options)?;
+        ctx.with_texture(Gl::TEXTURE_2D, texture.id(), || {
+            ctx.gl.pixel_storei(Gl::UNPACK_ALIGNMENT, 1);
+            let upload = format.upload(ctx.version());
+            ctx.tex_image_2d(&upload, width as i32, height as i32, pixels)?;
+            texture.finish_upload(ctx);
+            Ok::<_, Error>(())
+        })?;
+        Ok(texture)
+    }
+
+    /// Four floats per pixel, as `TextureFormat::Rgba32F`.
+    /// Use `SamplerOptions::clamped(Filter::Nearest)` unless the
+    /// float filtering capabilities say otherwise
+    pub fn from_floats(
+        ctx: &RenderingContext,
+        width: u32,
+        height: u32,
+        pixels: &[f32],
+        options: SamplerOptions,
+    ) -> Result<Self, Error> {
+        let bytes: Vec<u8> = pixels
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        Texture2D::from_pixels(ctx, width, height, TextureFormat::Rgba32F, &bytes, options)
+    }
+
+    /// Uploads an image decoded by `image_decoder`.
+    /// Flipping and premultiplication are already done while decoding
+    pub fn from_decoded(
+        ctx: &RenderingContext,
+        image: &DecodedImage,
+        options: SamplerOptions,
+    ) -> Result<Self, Error> {
+        Texture2D::from_pixels(
+            ctx,
+            image.width,
+            image.height,
+            image.format,
+            &image.pixels,
+            options,
+        )
+    }
+
+    /// The image should be fully loaded, its natural size is used
+    pub fn from_image(
+        ctx: &RenderingContext,
+        source: ImageSource,
+        options: SamplerOptions,
+    ) -> Result<Self, Error> {
+        let (width, height) = source.size();
+        let format = TextureFormat::Rgba8;
+
+        let texture = Texture2D::create(ctx, width, height, format, options)?;
+        ctx.with_texture(Gl::TEXTURE_2D, texture.id(), || {
+            let upload = format.upload(ctx.version());
+            ctx.gl.tex_image_2d_from_source(&upload, source)?;
+            texture.finish_upload(ctx);
+            Ok::<_, Error>(())
+        })?;
+        Ok(texture)
+    }
+
+    /// Creates the texture without binding it
+    fn create(
+        ctx: &RenderingContext,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        options: SamplerOptions,
+    ) -> Result<Self, Error> {
+        if format == TextureFormat::Rgba32F && !ctx.capabilities().float_textures {
+            return Err(Error::new("Float textures are not supported"));
+        }
+        if format == TextureFormat::Srgba8 && !ctx.capabilities().srgb_textures {
+            return Err(Error::new("sRGB textures are not supported"));
+        }
+        let texture = Texture2D {
+            texture: ctx.create_texture()?,
+            width,
+            height,
+            format,
+            options,
+        };
+        texture.check_options(ctx, &options)?;
+        Ok(texture)
+    }
+
+    /// Expects the texture to be bound
+    fn finish_upload(&self, ctx: &RenderingContext) {
+        self.apply_options(ctx);
+        if self.options.mipmap_filter.is_some() {
+            ctx.gl.generate_mipmap(Gl::TEXTURE_2D);
+        }
+    }
+
+    /// WebGL1 can only repeat and mipmap textures with power of two sides,
+    /// float textures are never mipmapped and only filtered linearly
+    /// with `Capabilities::float_linear`
+    fn check_options(&self, ctx: &RenderingContext, options: &SamplerOptions) -> Result<(), Error> {
+        let power_of_two = self.width.is_power_of_two() && self.height.is_power_of_two();
+        let repeats = options.wrap_s != Wrap::ClampToEdge || options.wrap_t != Wrap::ClampToEdge;
+        let mipmaps = options.mipmap_filter.is_some();
+        if ctx.version() == GlVersion::WebGl1 && !power_of_two && (repeats || mipmaps) {
+            return Err(Error::new(&format!(
+                "A {}x{} texture needs clamped wrapping and no mipmaps on WebGL1",
+                self.width, self.height
+            )));
+        }
+        if ctx.version() == GlVersion::WebGl1 && self.format == TextureFormat::Srgba8 && mipmaps {
+            return Err(Error::new("sRGB textures can't have mipmaps on WebGL1"));
+        }
+        if self.format == TextureFormat::Rgba32F {
+            if mipmaps {
+                return Err(Error::new("Float textures can't have mipmaps"));
+            }
+            let linear =
+                options.min_filter == Filter::Linear || options.mag_filter == Filter::Linear;
+            if linear && !ctx.capabilities().float_linear {
+                return Err(Error::new(
+                    "Linear filtering of float textures is not supported",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_options(&self, ctx: &RenderingContext) {
+        let options = &self.options;
+        let parameters = [
+            (Gl::TEXTURE_WRAP_S, options.wrap_s.gl_enum()),
+            (Gl::TEXTURE_WRAP_T, options.wrap_t.gl_enum()),
+            (Gl::TEXTURE_MIN_FILTER, options.min_filter_enum()),
+            (Gl::TEXTURE_MAG_FILTER, options.mag_filter_enum()),
+        ];
+        for (pname, param) in parameters {
+            ctx.gl.tex_parameteri(Gl::TEXTURE_2D, pname, param as i32);
+        }
+    }
+
+    /// Mipmaps are generated if the new options use them
+    pub fn set_options(
+        &mut self,
+        ctx: &RenderingContext,
+        options: SamplerOptions,
+    ) -> Result<(), Error> {
+        self.check_options(ctx, &options)?;
+        self.options = options;
+        ctx.with_texture(Gl::TEXTURE_2D, self.id(), || self.finish_upload(ctx));
+        Ok(())
+    }
+
+    /// Binds the texture to a texture unit, counting from 0.
+    /// The sampler uniform should be set to the same unit,
+    /// see `Program::set_texture`
+    pub fn bind(&self, ctx: &RenderingContext, unit: u32) {
+        ctx.active_texture(unit);
+        ctx.bind_texture(Gl::TEXTURE_2D, Some(self.id()));
+    }
+
+    pub fn id(&self) -> TextureId {
+        self.texture.id()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn options(&self) -> &SamplerOptions {
+        &self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::Capabilities;
+    use crate::recording_backend::{Call, RecordingBackend};
+
+    #[test]
+    fn uploads_keep_the_callers_binding() {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        let ctx = RenderingContext::new(Box::new(backend));
+        let options = SamplerOptions::clamped(Filter::Nearest);
+        let bound = Texture2D::from_pixels(&ctx, 1, 1, TextureFormat::R8, &[0], options).unwrap();
+        bound.bind(&ctx, 3);
+
+        let mut texture =
+            Texture2D::from_pixels(&ctx, 2, 1, TextureFormat::R8, &[0, 255], options).unwrap();
+        texture
+            .set_options(&ctx, SamplerOptions::default())
+            .unwrap();
+        // restored on unit 3, which stayed active
+        assert_eq!(
+            calls.borrow().last(),
+            Some(&Call::BindTexture {
+                target: Gl::TEXTURE_2D,
+                texture: Some(bound.id()),
+            })
+        );
+
+        // a dropped texture is unbound rather than restored
+        let deleted = bound.id();
+        drop(bound);
+        texture.set_options(&ctx, options).unwrap();
+        let calls = calls.borrow();
+        let deleted_at = calls
+            .iter()
+            .position(|call| *call == Call::DeleteTexture(deleted))
+            .unwrap();
+        assert!(!calls[deleted_at..].iter().any(|call| matches!(
+            call,
+            Call::BindTexture { texture: Some(id), .. } if *id == deleted
+        )));
+        assert_eq!(
+            calls.last(),
+            Some(&Call::BindTexture {
+                target: Gl::TEXTURE_2D,
+                texture: None,
+            })
+        );
+    }
+
+    #[test]
+    fn checks_float_filtering() {
+        let ctx = RenderingContext::new(Box::new(RecordingBackend::new()));
+        let pixels = [0.5; 4];
+        let error = Texture2D::from_floats(&ctx, 1, 1, &pixels, SamplerOptions::default());
+        assert!(error.is_err());
+        let linear = SamplerOptions::clamped(Filter::Linear);
+        assert!(Texture2D::from_floats(&ctx, 1, 1, &pixels, linear).is_err());
+        let nearest = SamplerOptions::clamped(Filter::Nearest);
+        assert!(Texture2D::from_floats(&ctx, 1, 1, &pixels, nearest).is_ok());
+
+        let capabilities = Capabilities {
+            float_linear: true,
+            ..Capabilities::webgl2()
+        };
+        let ctx =
+            RenderingContext::new(Box::new(RecordingBackend::with_capabilities(capabilities)));
+        assert!(Texture2D::from_floats(&ctx, 1, 1, &pixels, linear).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+use nalgebra::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use web_sys::WebGlRenderingContext as Gl;
+
+use crate::backend::{Backend, UniformLocationId};
+
+/// A value for a uniform of a GLSL ES shader. Unsigned integers and
+/// non-square matrices are not covered, see `glsl_type::is_settable_uniform`.
+/// Matrices are column-major, as nalgebra stores them.
+/// Samplers take the index of the texture unit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Int(i32),
+    IVec2([i32; 2]),
+    IVec3([i32; 3]),
+    IVec4([i32; 4]),
+    Bool(bool),
+    BVec2([bool; 2]),
+    BVec3([bool; 3]),
+    BVec4([bool; 4]),
+    Mat2([f32; 4]),
+    Mat3([f32; 9]),
+    Mat4([f32; 16]),
+    Sampler(i32),
+    FloatArray(Vec<f32>),
+    Vec2Array(Vec<[f32; 2]>),
+    Vec3Array(Vec<[f32; 3]>),
+    Vec4Array(Vec<[f32; 4]>),
+    IntArray(Vec<i32>),
+    IVec2Array(Vec<[i32; 2]>),
+    IVec3Array(Vec<[i32; 3]>),
+    IVec4Array(Vec<[i32; 4]>),
+    BoolArray(Vec<bool>),
+    BVec2Array(Vec<[bool; 2]>),
+    BVec3Array(Vec<[bool; 3]>),
+    BVec4Array(Vec<[bool; 4]>),
+    Mat2Array(Vec<[f32; 4]>),
+    Mat3Array(Vec<[f32; 9]>),
+    Mat4Array(Vec<[f32; 16]>),
+    SamplerArray(Vec<i32>),
+}
+
+/// How the value is sent to the graphics card
+enum UniformData<'a> {
+    Floats(u32, &'a [f32]),
+    Ints(u32, Vec<i32>),
+    Matrices(u32, &'a [f32]),
+}
+
+fn bools_to_ints(values: &[bool]) -> Vec<i32> {
+    values.iter().map(|value| *value as i32).collect()
+}
+
+impl UniformValue {
+    /// GL type enum of a single element, e.g. `FLOAT_VEC3` for both
+    /// `Vec3` and `Vec3Array`. Samplers report `SAMPLER_2D`, though they
+    /// can be set on a uniform of any sampler type
+    pub fn data_type(&self) -> u32 {
+        use UniformValue::*;
+        match self {
+            Float(_) | FloatArray(_) => Gl::FLOAT,
+            Vec2(_) | Vec2Array(_) => Gl::FLOAT_VEC2,
+            Vec3(_) | Vec3Array(_) => Gl::FLOAT_VEC3,
+            Vec4(_) | Vec4Array(_) => Gl::FLOAT_VEC4,
+            Int(_) | IntArray(_) => Gl::INT,
+            IVec2(_) | IVec2Array(_) => Gl::INT_VEC2,
+            IVec3(_) | IVec3Array(_) => Gl::INT_VEC3,
+            IVec4(_) | IVec4Array(_) => Gl::INT_VEC4,
+            Bool(_) | BoolArray(_) => Gl::BOOL,
+            BVec2(_) | BVec2Array(_) => Gl::BOOL_VEC2,
+            BVec3(_) | BVec3Array(_) => Gl::BOOL_VEC3,
+            BVec4(_) | BVec4Array(_) => Gl::BOOL_VEC4,
+            Mat2(_) | Mat2Array(_) => Gl::FLOAT_MAT2,
+            Mat3(_) | Mat3Array(_) => Gl::FLOAT_MAT3,
+            Mat4(_) | Mat4Array(_) => Gl::FLOAT_MAT4,
+            Sampler(_) | SamplerArray(_) => Gl::SAMPLER_2D,
+        }
+    }
+
+    pub fn is_sampler(&self) -> bool {
+        matches!(
+            self,
+            UniformValue::Sampler(_) | UniformValue::SamplerArray(_)
+        )
+    }
+
+    /// Number of array elements, 1 for plain values
+    pub fn len(&self) -> usize {
+        use UniformValue::*;
+        match self {
+            FloatArray(values) => values.len(),
+            Vec2Array(values) => values.len(),
+            Vec3Array(values) => values.len(),
+            Vec4Array(values) => values.len(),
+            IntArray(values) => values.len(),
+            IVec2Array(values) => values.len(),
+            IVec3Array(values) => values.len(),
+            IVec4Array(values) => values.len(),
+            BoolArray(values) => values.len(),
+            BVec2Array(values) => values.len(),
+            BVec3Array(values) => values.len(),
+            BVec4Array(values) => values.len(),
+            Mat2Array(values) => values.len(),
+            Mat3Array(values) => values.len(),
+            Mat4Array(values) => values.len(),
+            SamplerArray(values) => values.len(),
+            _ => 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn data(&self) -> UniformData<'_> {
+        use UniformData::*;
+        match self {
+            UniformValue::Float(value) => Floats(1, std::slice::from_ref(value)),
+            UniformValue::Vec2(value) => Floats(2, value),
+            UniformValue::Vec3(value) => Floats(3, value),
+            UniformValue::Vec4(value) => Floats(4, value),
+            UniformValue::Int(value) | UniformValue::Sampler(value) => Ints(1, vec![*value]),
+            UniformValue::IVec2(value) => Ints(2, value.to_vec()),
+            UniformValue::IVec3(value) => Ints(3, value.to_vec()),
+            UniformValue::IVec4(value) => Ints(4, value.to_vec()),
+            UniformValue::Bool(value) => Ints(1, vec![*value as i32]),
+            UniformValue::BVec2(value) => Ints(2, bools_to_ints(value)),
+            UniformValue::BVec3(value) => Ints(3, bools_to_ints(value)),
+            UniformValue::BVec4(value) => Ints(4, bools_to_ints(value)),
+            UniformValue::Mat2(value) => Matrices(2, value),
+            UniformValue::Mat3(value) => Matrices(3, value),
+            UniformValue::Mat4(value) => Matrices(4, value),
+            UniformValue::FloatArray(values) => Floats(1, values),
+            UniformValue::Vec2Array(values) => Floats(2, values.as_flattened()),
+            UniformValue::Vec3Array(values) => Floats(3, values.as_flattened()),
+            UniformValue::Vec4Array(values) => Floats(4, values.as_flattened()),
+            UniformValue::IntArray(values) | UniformValue::SamplerArray(values) => {
+                Ints(1, values.clone())
+            }
+            UniformValue::IVec2Array(values) => Ints(2, values.as_flattened().to_vec()),
+            UniformValue::IVec3Array(values) => Ints(3, values.as_flattened().to_vec()),
+            UniformValue::IVec4Array(values) => Ints(4, values.as_flattened().to_vec()),
+            UniformValue::BoolArray(values) => Ints(1, bools_to_ints(values)),
+            UniformValue::BVec2Array(values) => Ints(2, bools_to_ints(values.as_flattened())),
+            UniformValue::BVec3Array(values) => Ints(3, bools_to_ints(values.as_flattened())),
+            UniformValue::BVec4Array(values) => Ints(4, bools_to_ints(values.as_flattened())),
+            UniformValue::Mat2Array(values) => Matrices(2, values.as_flattened()),
+            UniformValue::Mat3Array(values) => Matrices(3, values.as_flattened()),
+            UniformValue::Mat4Array(values) => Matrices(4, values.as_flattened()),
+        }
+    }
+
+    /// Sends the value to the program currently in use
+    pub fn upload(&self, gl: &dyn Backend, location: UniformLocationId) {
+        match self.data() {
+            UniformData::Floats(components, data) => gl.uniform_fv(location, components, data),
+            UniformData::Ints(components, data) => gl.uniform_iv(location, components, &data),
+            UniformData::Matrices(columns, data) => {
+                gl.uniform_matrix_fv(location, columns, false, data)
+            }
+        }
+    }
+}
+
+impl From<f32> for UniformValue {
+    fn from(value: f32) -> Self {
+        UniformValue::Float(value)
+    }
+}
+
+impl From<i32> for UniformValue {
+    fn from(value: i32) -> Self {
+        UniformValue::Int(value)
+    }
+}
+
+impl From<bool> for UniformValue {
+    fn from(value: bool) -> Self {
+        UniformValue::Bool(value)
+    }
+}
+
+impl From<Vector2<f32>> for UniformValue {
+    fn from(value: Vector2<f32>) -> Self {
+        UniformValue::Vec2([value.x, value.y])
+    }
+}
+
+impl From<Vector3<f32>> for UniformValue {
+    fn from(value: Vector3<f32>) -> Self {
+        UniformValue::Vec3([value.x, value.y, value.z])
+    }
+}
+
+impl From<Vector4<f32>> for UniformValue {
+    fn from(value: Vector4<f32>) -> Self {
+        UniformValue::Vec4([value.x, value.y, value.z, value.w])
+    }
+}
+
+impl From<Matrix2<f32>> for UniformValue {
+    fn from(value: Matrix2<f32>) -> Self {
+        let mut data = [0.0; 4];
+        data.copy_from_slice(value.as_slice());
+        UniformValue::Mat2(data)
+    }
+}
+
+impl From<Matrix3<f32>> for UniformValue {
+    fn from(value: Matrix3<f32>) -> Self {
+        let mut data = [0.0; 9];
+        data.copy_from_slice(value.as_slice());
+        UniformValue::Mat3(data)
+    }
+}
+
+impl From<Matrix4<f32>> for UniformValue {
+    fn from(value: Matrix4<f32>) -> Self {
+        let mut data = [0.0; 16];
+        data.copy_from_slice(value.as_slice());
+        UniformValue::Mat4(data)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::backend::BufferId;
+use crate::error::Error;
+use crate::gl_object::OwnedVertexArray;
+use crate::rendering_context::{BufferKind, RenderingContext};
+
+/// Where an enabled attribute reads its data from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttributeBinding {
+    pub location: u32,
+    pub buffer: BufferId,
+    /// From 1 to 4
+    pub components: i32,
+    pub data_type: u32,
+    pub normalized: bool,
+    pub stride: i32,
+    pub offset: i32,
+    /// 0 advances once per vertex, otherwise once every `divisor` instances.
+    /// Needs `Capabilities::instancing` when not 0
+    pub divisor: u32,
+}
+
+impl AttributeBinding {
+    /// Points the attribute at its buffer and enables it,
+    /// leaves the buffer bound to `ARRAY_BUFFER`
+    pub fn apply(&self, ctx: &RenderingContext) {
+        ctx.bind_array_buffer(self.buffer);
+        ctx.vertex_attrib_pointer(
+            self.location,
+            self.components,
+            self.data_type,
+            self.normalized,
+            self.stride,
+            self.offset,
+        );
+        ctx.enable_vertex_attrib_array(self.location);
+        if self.divisor != 0 {
+            ctx.vertex_attrib_divisor(self.location, self.divisor);
+        }
+    }
+}
+
+/// Attribute bindings and an index buffer, set up once and bound with a
+/// single call. With `Capabilities::vertex_array_objects` they are recorded
+/// in a vertex array object, otherwise `bind` replays them call by call
+pub struct VertexArray {
+    /// `None` when emulated
+    native: Option<OwnedVertexArray>,
+    bindings: Vec<AttributeBinding>,
+    indices: Option<BufferId>,
+}
+
+impl VertexArray {
+    pub fn new(
+        ctx: &RenderingContext,
+        bindings: Vec<AttributeBinding>,
+        indices: Option<BufferId>,
+    ) -> Result<Self, Error> {
+        let native = match ctx.capabilities().vertex_array_objects {
+            true => Some(ctx.create_vertex_array()?),
+            false => None,
+        };
+        let vertex_array = VertexArray {
+            native,
+            bindings,
+            indices,
+        };
+        if let Some(native) = &vertex_array.native {
+            ctx.bind_vertex_array(Some(native.id()));
+            vertex_array.replay(ctx);
+            ctx.bind_vertex_array(None);
+        }
+        Ok(vertex_array)
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.native.is_some()
+    }
+
+    pub fn bindings(&self) -> &[AttributeBinding] {
+        &self.bindings
+    }
+
+    pub fn bind(&self, ctx: &RenderingContext) {
+        match &self.native {
+            Some(native) => ctx.bind_vertex_array(Some(native.id())),
+            None => self.replay(ctx),
+        }
+    }
+
+    /// Goes back to the default vertex array, or disables the attributes
+    /// `bind` enabled and resets their divisors when emulated
+    pub fn unbind(&self, ctx: &RenderingContext) {
+        match &self.native {
+            Some(_) => ctx.bind_vertex_array(None),
+            None => {
+                for binding in &self.bindings {
+                    ctx.disable_vertex_attrib_array(binding.location);
+                    if binding.divisor != 0 {
+                        ctx.vertex_attrib_divisor(binding.location, 0);
+                    }
+                }
+            }
+        }
+    }
+
+    fn replay(&self, ctx: &RenderingContext) {
+        for binding in &self.bindings {
+            binding.apply(ctx);
+        }
+        if let Some(indices) = self.indices {
+            ctx.bind_buffer(BufferKind::ElementArrayBuffer, indices);
+        }
+    }
+}
@@ -0,0 +1,530 @@
+use std::fmt;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::buffer_storage::{BufferStorage, BufferUsage};
+use crate::error::Error;
+use crate::glsl_type;
+use crate::pod::{Pod, as_bytes};
+use crate::program::Program;
+use crate::rendering_context::{BufferKind, RenderingContext};
+use crate::vertex_array::AttributeBinding;
+
+/// WebGL rejects larger strides
+const MAX_STRIDE: i32 = 255;
+
+/// How every component of an attribute is stored in the buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    F32,
+    I8,
+    U8,
+    I16,
+    U16,
+}
+
+impl ComponentType {
+    pub fn gl_type(self) -> u32 {
+        match self {
+            ComponentType::F32 => WebGlRenderingContext::FLOAT,
+            ComponentType::I8 => WebGlRenderingContext::BYTE,
+            ComponentType::U8 => WebGlRenderingContext::UNSIGNED_BYTE,
+            ComponentType::I16 => WebGlRenderingContext::SHORT,
+            ComponentType::U16 => WebGlRenderingContext::UNSIGNED_SHORT,
+        }
+    }
+
+    /// Bytes per component
+    pub fn size(self) -> i32 {
+        match self {
+            ComponentType::F32 => 4,
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+        }
+    }
+}
+
+/// One named attribute inside a vertex
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayoutAttribute {
+    pub name: String,
+    /// From 1 to 4
+    pub components: i32,
+    pub component_type: ComponentType,
+    /// Maps integers to [0, 1], or [-1, 1] when signed,
+    /// instead of converting them to floats as they are
+    pub normalized: bool,
+    /// Bytes from the start of the vertex
+    pub offset: i32,
+}
+
+impl LayoutAttribute {
+    /// Bytes the attribute takes in every vertex
+    pub fn size(&self) -> i32 {
+        self.components * self.component_type.size()
+    }
+
+    fn end(&self) -> i32 {
+        self.offset + self.size()
+    }
+}
+
+/// Why a layout can't describe the vertices, or doesn't fit the program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    NoAttributes,
+    DuplicateName(String),
+    InvalidComponents {
+        name: String,
+        components: i32,
+    },
+    /// The offset is not a multiple of the component size
+    Misaligned {
+        name: String,
+        offset: i32,
+    },
+    Overlap {
+        first: String,
+        second: String,
+    },
+    StrideTooLarge(i32),
+    /// The vertex type doesn't line up with the stride
+    VertexSize {
+        vertex_size: usize,
+        stride: i32,
+    },
+    /// The data doesn't hold a whole number of vertices
+    DataSize {
+        bytes: usize,
+        stride: i32,
+    },
+    /// The shader declares a type the layout can't feed
+    ShaderType {
+        name: String,
+        components: i32,
+        declared: &'static str,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::NoAttributes => write!(f, "Vertex layout has no attributes"),
+            LayoutError::DuplicateName(name) => {
+                write!(f, "Vertex layout has more than one {name} attribute")
+            }
+            LayoutError::InvalidComponents { name, components } => write!(
+                f,
+                "Attribute {name} has {components} components, expected 1 to 4"
+            ),
+            LayoutError::Misaligned { name, offset } => write!(
+                f,
+                "Attribute {name} at offset {offset} is not aligned to its component size"
+            ),
+            LayoutError::Overlap { first, second } => {
+                write!(f, "Attributes {first} and {second} overlap")
+            }
+            LayoutError::StrideTooLarge(stride) => write!(
+                f,
+                "Vertex stride of {stride} bytes is above the limit of {MAX_STRIDE}"
+            ),
+            LayoutError::VertexSize {
+                vertex_size,
+                stride,
+            } => write!(
+                f,
+                "Vertex data of {vertex_size} bytes per element doesn't match the layout stride of {stride} bytes"
+            ),
+            LayoutError::DataSize { bytes, stride } => write!(
+                f,
+                "{bytes} bytes of vertex data are not a multiple of the layout stride of {stride} bytes"
+            ),
+            LayoutError::ShaderType {
+                name,
+                components,
+                declared,
+            } => write!(
+                f,
+                "Attribute {name} has {components} components in the layout, \
+                 but the shader declares it as {declared}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl From<LayoutError> for Error {
+    fn from(error: LayoutError) -> Self {
+        Error::new(&error.to_string())
+    }
+}
+
+/// Where every named attribute sits inside a vertex. The stride is the end
+/// of the last attribute, rounded up to 4 bytes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexLayout {
+    attributes: Vec<LayoutAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends floats right after the previous attribute
+    pub fn floats(self, name: &str, components: i32) -> Self {
+        self.packed(name, components, ComponentType::F32, false)
+    }
+
+    /// Appends an attribute right after the previous one, aligned to its component size
+    pub fn packed(
+        self,
+        name: &str,
+        components: i32,
+        component_type: ComponentType,
+        normalized: bool,
+    ) -> Self {
+        let end = self.attributes.iter().map(LayoutAttribute::end).max();
+        let alignment = component_type.size();
+        let offset = align(end.unwrap_or(0), alignment);
+        self.with_attribute(LayoutAttribute {
+            name: name.to_owned(),
+            components,
+            component_type,
+            normalized,
+            offset,
+        })
+    }
+
+    /// Adds an attribute at its own offset, e.g. to match the fields of a `#[repr(C)]` struct
+    pub fn with_attribute(mut self, attribute: LayoutAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub fn attributes(&self) -> &[LayoutAttribute] {
+        &self.attributes
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&LayoutAttribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    /// Bytes from one vertex to the next
+    pub fn stride(&self) -> i32 {
+        let end = self.attributes.iter().map(LayoutAttribute::end).max();
+        align(end.unwrap_or(0), 4)
+    }
+
+    /// Checks component counts, alignment, overlaps, names and the stride limit
+    pub fn validate(&self) -> Result<(), LayoutError> {
+        if self.attributes.is_empty() {
+            return Err(LayoutError::NoAttributes);
+        }
+        for (index, attribute) in self.attributes.iter().enumerate() {
+            if !(1..=4).contains(&attribute.components) {
+                return Err(LayoutError::InvalidComponents {
+                    name: attribute.name.clone(),
+                    components: attribute.components,
+                });
+            }
+            if attribute.offset < 0 || attribute.offset % attribute.component_type.size() != 0 {
+                return Err(LayoutError::Misaligned {
+                    name: attribute.name.clone(),
+                    offset: attribute.offset,
+                });
+            }
+            for other in &self.attributes[..index] {
+                if other.name == attribute.name {
+                    return Err(LayoutError::DuplicateName(attribute.name.clone()));
+                }
+                if attribute.offset < other.end() && other.offset < attribute.end() {
+                    return Err(LayoutError::Overlap {
+                        first: other.name.clone(),
+                        second: attribute.name.clone(),
+                    });
+                }
+            }
+        }
+        let stride = self.stride();
+        if stride > MAX_STRIDE {
+            return Err(LayoutError::StrideTooLarge(stride));
+        }
+        Ok(())
+    }
+
+    /// Checks the attributes the program reads against their declarations.
+    /// The shader may read more components than the layout provides,
+    /// missing ones default to 0 and `w` to 1. Layout components beyond
+    /// the declared ones are rejected on purpose: GL would ignore them,
+    /// which usually means the layout was written for another shader
+    pub fn check_program(&self, program: &Program) -> Result<(), LayoutError> {
+        for attribute in &self.attributes {
+            let Some(active) = program.attribute(&attribute.name) else {
+                continue;
+            };
+            let declared = glsl_type::float_components(active.data_type);
+            if declared.is_none_or(|declared| attribute.components > declared) {
+                return Err(LayoutError::ShaderType {
+                    name: attribute.name.clone(),
+                    components: attribute.components,
+                    declared: glsl_type::name(active.data_type),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of whole vertices in `data`, whose elements
+    /// are either whole vertices or evenly divide one
+    pub fn vertex_count<T: Pod>(&self, data: &[T]) -> Result<usize, LayoutError> {
+        self.validate()?;
+        let stride = self.stride();
+        let vertex_size = std::mem::size_of::<T>();
+        if vertex_size == 0 || !(stride as usize).is_multiple_of(vertex_size) {
+            return Err(LayoutError::VertexSize {
+                vertex_size,
+                stride,
+            });
+        }
+        let bytes = std::mem::size_of_val(data);
+        if !bytes.is_multiple_of(stride as usize) {
+            return Err(LayoutError::DataSize { bytes, stride });
+        }
+        Ok(bytes / stride as usize)
+    }
+}
+
+fn align(offset: i32, alignment: i32) -> i32 {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+/// Vertices of any `Pod` type in a single buffer, one
+/// after another, with their attributes described by a layout
+pub struct VertexBuffer {
+    layout: VertexLayout,
+    vertex_count: usize,
+    buffer: BufferStorage<u8>,
+}
+
+impl VertexBuffer {
+    pub fn new<T: Pod>(
+        ctx: &RenderingContext,
+        layout: VertexLayout,
+        data: &[T],
+    ) -> Result<Self, Error> {
+        Self::with_usage(ctx, layout, data, BufferUsage::Static)
+    }
+
+    /// Use `Dynamic` or `Stream` for vertices changed with `write`
+    pub fn with_usage<T: Pod>(
+        ctx: &RenderingContext,
+        layout: VertexLayout,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, Error> {
+        let vertex_count = layout.vertex_count(data)?;
+        let bytes = as_bytes(data).to_vec();
+        let mut buffer = BufferStorage::with_usage(ctx, BufferKind::ArrayBuffer, bytes, usage)?;
+        buffer.write_to_graphics_card(ctx);
+        Ok(VertexBuffer {
+            layout,
+            vertex_count,
+            buffer,
+        })
+    }
+
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    /// The bytes of every vertex, in order
+    pub fn data(&self) -> &[u8] {
+        self.buffer.data()
+    }
+
+    /// Overwrites the vertices from `first_vertex`, adding more when they run
+    /// past the end. They are sent to the graphics card by `flush`
+    pub fn write<T: Pod>(&mut self, first_vertex: usize, vertices: &[T]) -> Result<(), Error> {
+        let count = self.layout.vertex_count(vertices)?;
+        let stride = self.layout.stride() as usize;
+        self.buffer.write(first_vertex * stride, as_bytes(vertices));
+        self.vertex_count = self.vertex_count.max(first_vertex + count);
+        Ok(())
+    }
+
+    /// Drops the vertices from `vertex_count` on, keeping their storage
+    pub fn truncate(&mut self, vertex_count: usize) {
+        self.buffer
+            .truncate(vertex_count * self.layout.stride() as usize);
+        self.vertex_count = self.vertex_count.min(vertex_count);
+    }
+
+    /// Sends the vertices changed by `write`. The buffer grows when
+    /// needed and keeps its id, so recorded vertex arrays stay valid
+    pub fn flush(&mut self, ctx: &RenderingContext) {
+        self.buffer.flush(ctx);
+    }
+
+    /// Where every attribute the program reads comes from, after checking
+    /// the layout against the shader declarations
+    pub fn bindings(&self, program: &Program) -> Result<Vec<AttributeBinding>, Error> {
+        self.layout.check_program(program)?;
+        let stride = self.layout.stride();
+        let bindings = self.layout.attributes.iter().filter_map(|attribute| {
+            let active = program.attribute(&attribute.name)?;
+            Some(AttributeBinding {
+                location: active.location,
+                buffer: self.buffer.buffer_id(),
+                components: attribute.components,
+                data_type: attribute.component_type.gl_type(),
+                normalized: attribute.normalized,
+                stride,
+                offset: attribute.offset,
+                divisor: 0,
+            })
+        });
+        Ok(bindings.collect())
+    }
+
+    /// Points every attribute the program reads at the buffer and enables it
+    pub fn bind(&self, ctx: &RenderingContext, program: &Program) -> Result<(), Error> {
+        for binding in self.bindings(program)? {
+            binding.apply(ctx);
+        }
+        Ok(())
+    }
+
+    /// Disables the attributes enabled by `bind`
+    pub fn unbind(&self, ctx: &RenderingContext, program: &Program) {
+        for attribute in &self.layout.attributes {
+            if let Some(active) = program.attribute(&attribute.name) {
+                ctx.disable_vertex_attrib_array(active.location);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_backend::RecordingBackend;
+
+    fn program(ctx: &RenderingContext, attributes: &str) -> Program {
+        let vertex = format!("{attributes}\nvoid main() {{}}");
+        Program::new(ctx, &vertex, "void main() {}").unwrap()
+    }
+
+    #[test]
+    fn packs_attributes_and_computes_stride() {
+        let layout = VertexLayout::new()
+            .floats("aPosition", 3)
+            .packed("aColor", 4, ComponentType::U8, true)
+            .packed("aWeight", 1, ComponentType::U16, true);
+        let offsets: Vec<_> = layout.attributes().iter().map(|a| a.offset).collect();
+        assert_eq!(offsets, [0, 12, 16]);
+        assert_eq!(layout.stride(), 20);
+        assert_eq!(layout.validate(), Ok(()));
+
+        let floats = [0.0f32; 10];
+        assert_eq!(layout.vertex_count(&floats), Ok(2));
+        assert_eq!(
+            layout.vertex_count(&[0.0f32; 7]),
+            Err(LayoutError::DataSize {
+                bytes: 28,
+                stride: 20
+            })
+        );
+        assert_eq!(
+            layout.vertex_count(&[[0.0f32; 6]]),
+            Err(LayoutError::VertexSize {
+                vertex_size: 24,
+                stride: 20
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_broken_layouts() {
+        let overlapping =
+            VertexLayout::new()
+                .floats("aPosition", 3)
+                .with_attribute(LayoutAttribute {
+                    name: "aNormal".into(),
+                    components: 3,
+                    component_type: ComponentType::F32,
+                    normalized: false,
+                    offset: 8,
+                });
+        assert_eq!(
+            overlapping.validate(),
+            Err(LayoutError::Overlap {
+                first: "aPosition".into(),
+                second: "aNormal".into()
+            })
+        );
+
+        let duplicate = VertexLayout::new().floats("aUv", 2).floats("aUv", 2);
+        assert_eq!(
+            duplicate.validate(),
+            Err(LayoutError::DuplicateName("aUv".into()))
+        );
+        let wide = VertexLayout::new().floats("aWide", 5);
+        assert!(matches!(
+            wide.validate(),
+            Err(LayoutError::InvalidComponents { .. })
+        ));
+        assert_eq!(
+            VertexLayout::new().validate(),
+            Err(LayoutError::NoAttributes)
+        );
+    }
+
+    #[test]
+    fn accepts_shaders_reading_more_components() {
+        let ctx = RenderingContext::new(Box::new(RecordingBackend::new()));
+        let layout = VertexLayout::new()
+            .floats("aPosition", 3)
+            .floats("aColor", 4);
+
+        // aPosition.w defaults to 1, the unused aColor is skipped
+        let reads_position = program(&ctx, "attribute vec4 aPosition;");
+        assert_eq!(layout.check_program(&reads_position), Ok(()));
+        let exact = program(&ctx, "attribute vec3 aPosition;\nattribute vec4 aColor;");
+        assert_eq!(layout.check_program(&exact), Ok(()));
+    }
+
+    #[test]
+    fn rejects_layouts_wider_than_the_shader() {
+        let ctx = RenderingContext::new(Box::new(RecordingBackend::new()));
+        let layout = VertexLayout::new()
+            .floats("aPosition", 3)
+            .floats("aColor", 4);
+
+        let narrow = program(&ctx, "attribute vec3 aPosition;\nattribute vec2 aColor;");
+        assert_eq!(
+            layout.check_program(&narrow),
+            Err(LayoutError::ShaderType {
+                name: "aColor".into(),
+                components: 4,
+                declared: "vec2"
+            })
+        );
+
+        let integer = program(&ctx, "#version 300 es\nin ivec3 aPosition;");
+        assert!(matches!(
+            layout.check_program(&integer),
+            Err(LayoutError::ShaderType {
+                declared: "ivec3",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,613 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use js_sys::{Float32Array, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AngleInstancedArrays, OesVertexArrayObject, WebGl2RenderingContext, WebGlActiveInfo,
+    WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+use crate::backend::{
+    ActiveInfo, Backend, BufferId, ImageSource, ProgramId, ShaderId, TextureId, TextureUpload,
+    UniformLocationId, VertexArrayId,
+};
+use crate::capabilities::Capabilities;
+
+pub enum GlContext {
+    WebGl1(WebGlRenderingContext),
+    WebGl2(WebGl2RenderingContext),
+}
+
+fn to_active_info(info: WebGlActiveInfo) -> ActiveInfo {
+    ActiveInfo {
+        name: info.name(),
+        size: info.size(),
+        data_type: info.type_(),
+    }
+}
+
+/// Runs the same expression against whichever context we have,
+/// web-sys gives both of them identically named methods
+macro_rules! with_gl {
+    ($self:ident, $gl:ident => $body:expr) => {
+        match &$self.gl {
+            GlContext::WebGl1($gl) => $body,
+            GlContext::WebGl2($gl) => $body,
+        }
+    };
+}
+
+fn has_extension(gl: &GlContext, name: &str) -> bool {
+    // getExtension both checks and enables an extension
+    let extension = match gl {
+        GlContext::WebGl1(gl) => gl.get_extension(name),
+        GlContext::WebGl2(gl) => gl.get_extension(name),
+    };
+    matches!(extension, Ok(Some(_)))
+}
+
+fn get_extension<T: JsCast>(gl: &GlContext, name: &str) -> Option<T> {
+    match gl {
+        // extension objects don't have a global constructor to check against,
+        // so they can't be cast with dyn_into
+        GlContext::WebGl1(gl) => gl.get_extension(name).ok()?.map(JsCast::unchecked_into),
+        GlContext::WebGl2(_) => None,
+    }
+}
+
+fn detect_capabilities(
+    gl: &GlContext,
+    instanced_arrays: &Option<AngleInstancedArrays>,
+    vertex_array_object: &Option<OesVertexArrayObject>,
+) -> Capabilities {
+    let float_linear = has_extension(gl, "OES_texture_float_linear");
+    match gl {
+        GlContext::WebGl2(_) => Capabilities {
+            float_linear,
+            ..Capabilities::webgl2()
+        },
+        GlContext::WebGl1(_) => Capabilities {
+            vertex_array_objects: vertex_array_object.is_some(),
+            instancing: instanced_arrays.is_some(),
+            element_index_uint: has_extension(gl, "OES_element_index_uint"),
+            multiple_render_targets: has_extension(gl, "WEBGL_draw_buffers"),
+            float_textures: has_extension(gl, "OES_texture_float"),
+            float_linear,
+            srgb_textures: has_extension(gl, "EXT_sRGB"),
+            ..Capabilities::webgl1()
+        },
+    }
+}
+
+/// Maps engine handles to the JS objects WebGL gave us
+struct Registry<T> {
+    next_id: Cell<u32>,
+    items: RefCell<HashMap<u32, T>>,
+}
+
+impl<T: Clone> Registry<T> {
+    fn new() -> Self {
+        Registry {
+            next_id: Cell::new(1),
+            items: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, item: T) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.items.borrow_mut().insert(id, item);
+        id
+    }
+
+    fn remove(&self, id: u32) -> T {
+        self.items
+            .borrow_mut()
+            .remove(&id)
+            .expect("Unknown WebGl object handle")
+    }
+
+    fn retain(&self, keep: impl Fn(&T) -> bool) {
+        self.items.borrow_mut().retain(|_, item| keep(item));
+    }
+
+    fn get(&self, id: u32) -> T {
+        self.items
+            .borrow()
+            .get(&id)
+            .cloned()
+            .expect("Unknown WebGl object handle")
+    }
+}
+
+pub struct WebGlBackend {
+    gl: GlContext,
+    capabilities: Capabilities,
+    /// Only used with WebGL1, instancing is core in WebGL2
+    instanced_arrays: Option<AngleInstancedArrays>,
+    /// Only used with WebGL1, vertex array objects are core in WebGL2
+    vertex_array_object: Option<OesVertexArrayObject>,
+    buffers: Registry<WebGlBuffer>,
+    shaders: Registry<WebGlShader>,
+    programs: Registry<WebGlProgram>,
+    textures: Registry<WebGlTexture>,
+    vertex_arrays: Registry<WebGlVertexArrayObject>,
+    /// Locations are kept along with the program they belong to
+    uniform_locations: Registry<(u32, WebGlUniformLocation)>,
+}
+
+impl WebGlBackend {
+    pub fn new(gl: GlContext) -> Self {
+        let instanced_arrays = get_extension(&gl, "ANGLE_instanced_arrays");
+        let vertex_array_object = get_extension(&gl, "OES_vertex_array_object");
+        let capabilities = detect_capabilities(&gl, &instanced_arrays, &vertex_array_object);
+        WebGlBackend {
+            gl,
+            capabilities,
+            instanced_arrays,
+            vertex_array_object,
+            buffers: Registry::new(),
+            shaders: Registry::new(),
+            programs: Registry::new(),
+            textures: Registry::new(),
+            vertex_arrays: Registry::new(),
+            uniform_locations: Registry::new(),
+        }
+    }
+
+    fn get_program_parameter_u32(&self, ProgramId(program): ProgramId, pname: u32) -> u32 {
+        with_gl!(self, gl => gl.get_program_parameter(&self.programs.get(program), pname))
+            .as_f64()
+            .unwrap_or(0.0) as u32
+    }
+
+    fn angle_instanced_arrays(&self) -> &AngleInstancedArrays {
+        self.instanced_arrays
+            .as_ref()
+            .expect("ANGLE_instanced_arrays is not available")
+    }
+
+    fn oes_vertex_array_object(&self) -> &OesVertexArrayObject {
+        self.vertex_array_object
+            .as_ref()
+            .expect("OES_vertex_array_object is not available")
+    }
+}
+
+impl Backend for WebGlBackend {
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        let buffer = with_gl!(self, gl => gl.create_buffer())?;
+        Some(BufferId(self.buffers.insert(buffer)))
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        let buffer = buffer.map(|BufferId(id)| self.buffers.get(id));
+        with_gl!(self, gl => gl.bind_buffer(target, buffer.as_ref()));
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        with_gl!(self, gl => gl.buffer_data_with_u8_array(target, data, usage));
+    }
+
+    fn buffer_data_size(&self, target: u32, size: i32, usage: u32) {
+        with_gl!(self, gl => gl.buffer_data_with_i32(target, size, usage));
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        with_gl!(self, gl => gl.buffer_sub_data_with_i32_and_u8_array(target, offset, data));
+    }
+
+    fn delete_buffer(&self, BufferId(buffer): BufferId) {
+        let buffer = self.buffers.remove(buffer);
+        with_gl!(self, gl => gl.delete_buffer(Some(&buffer)));
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        let shader = with_gl!(self, gl => gl.create_shader(shader_type))?;
+        Some(ShaderId(self.shaders.insert(shader)))
+    }
+
+    fn shader_source(&self, ShaderId(shader): ShaderId, source: &str) {
+        with_gl!(self, gl => gl.shader_source(&self.shaders.get(shader), source));
+    }
+
+    fn compile_shader(&self, ShaderId(shader): ShaderId) {
+        with_gl!(self, gl => gl.compile_shader(&self.shaders.get(shader)));
+    }
+
+    fn get_shader_compile_status(&self, ShaderId(shader): ShaderId) -> bool {
+        with_gl!(self, gl => gl.get_shader_parameter(
+            &self.shaders.get(shader),
+            WebGlRenderingContext::COMPILE_STATUS,
+        ))
+        .as_bool()
+        .unwrap_or(false)
+    }
+
+    fn get_shader_info_log(&self, ShaderId(shader): ShaderId) -> Option<String> {
+        with_gl!(self, gl => gl.get_shader_info_log(&self.shaders.get(shader)))
+    }
+
+    fn delete_shader(&self, ShaderId(shader): ShaderId) {
+        let shader = self.shaders.remove(shader);
+        with_gl!(self, gl => gl.delete_shader(Some(&shader)));
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        let program = with_gl!(self, gl => gl.create_program())?;
+        Some(ProgramId(self.programs.insert(program)))
+    }
+
+    fn attach_shader(&self, ProgramId(program): ProgramId, ShaderId(shader): ShaderId) {
+        with_gl!(self, gl => gl.attach_shader(&self.programs.get(program), &self.shaders.get(shader)));
+    }
+
+    fn link_program(&self, ProgramId(program): ProgramId) {
+        with_gl!(self, gl => gl.link_program(&self.programs.get(program)));
+    }
+
+    fn get_program_link_status(&self, ProgramId(program): ProgramId) -> bool {
+        with_gl!(self, gl => gl.get_program_parameter(
+            &self.programs.get(program),
+            WebGlRenderingContext::LINK_STATUS,
+        ))
+        .as_bool()
+        .unwrap_or(false)
+    }
+
+    fn get_program_info_log(&self, ProgramId(program): ProgramId) -> Option<String> {
+        with_gl!(self, gl => gl.get_program_info_log(&self.programs.get(program)))
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        let program = program.map(|ProgramId(id)| self.programs.get(id));
+        with_gl!(self, gl => gl.use_program(program.as_ref()));
+    }
+
+    fn delete_program(&self, ProgramId(program): ProgramId) {
+        self.uniform_locations
+            .retain(|(location_program, _)| *location_program != program);
+        let program = self.programs.remove(program);
+        with_gl!(self, gl => gl.delete_program(Some(&program)));
+    }
+
+    fn get_active_attrib_count(&self, program: ProgramId) -> u32 {
+        self.get_program_parameter_u32(program, WebGlRenderingContext::ACTIVE_ATTRIBUTES)
+    }
+
+    fn get_active_attrib(&self, ProgramId(program): ProgramId, index: u32) -> Option<ActiveInfo> {
+        with_gl!(self, gl => gl.get_active_attrib(&self.programs.get(program), index))
+            .map(to_active_info)
+    }
+
+    fn get_active_uniform_count(&self, program: ProgramId) -> u32 {
+        self.get_program_parameter_u32(program, WebGlRenderingContext::ACTIVE_UNIFORMS)
+    }
+
+    fn get_active_uniform(&self, ProgramId(program): ProgramId, index: u32) -> Option<ActiveInfo> {
+        with_gl!(self, gl => gl.get_active_uniform(&self.programs.get(program), index))
+            .map(to_active_info)
+    }
+
+    fn get_attrib_location(&self, ProgramId(program): ProgramId, name: &str) -> i32 {
+        with_gl!(self, gl => gl.get_attrib_location(&self.programs.get(program), name))
+    }
+
+    fn get_uniform_location(
+        &self,
+        ProgramId(program): ProgramId,
+        name: &str,
+    ) -> Option<UniformLocationId> {
+        let location =
+            with_gl!(self, gl => gl.get_uniform_location(&self.programs.get(program), name))?;
+        Some(UniformLocationId(
+            self.uniform_locations.insert((program, location)),
+        ))
+    }
+
+    fn uniform_fv(
+        &self,
+        UniformLocationId(location): UniformLocationId,
+        components: u32,
+        data: &[f32],
+    ) {
+        let location = Some(&self.uniform_locations.get(location).1);
+        with_gl!(self, gl => match components {
+            1 => gl.uniform1fv_with_f32_array(location, data),
+            2 => gl.uniform2fv_with_f32_array(location, data),
+            3 => gl.uniform3fv_with_f32_array(location, data),
+            _ => gl.uniform4fv_with_f32_array(location, data),
+        });
+    }
+
+    fn uniform_iv(
+        &self,
+        UniformLocationId(location): UniformLocationId,
+        components: u32,
+        data: &[i32],
+    ) {
+        let location = Some(&self.uniform_locations.get(location).1);
+        with_gl!(self, gl => match components {
+            1 => gl.uniform1iv_with_i32_array(location, data),
+            2 => gl.uniform2iv_with_i32_array(location, data),
+            3 => gl.uniform3iv_with_i32_array(location, data),
+            _ => gl.uniform4iv_with_i32_array(location, data),
+        });
+    }
+
+    fn uniform_matrix_fv(
+        &self,
+        UniformLocationId(location): UniformLocationId,
+        columns: u32,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        let location = Some(&self.uniform_locations.get(location).1);
+        with_gl!(self, gl => match columns {
+            2 => gl.uniform_matrix2fv_with_f32_array(location, transpose, data),
+            3 => gl.uniform_matrix3fv_with_f32_array(location, transpose, data),
+            _ => gl.uniform_matrix4fv_with_f32_array(location, transpose, data),
+        });
+    }
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        data_type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        with_gl!(self, gl => gl.vertex_attrib_pointer_with_i32(index, size, data_type, normalized, stride, offset));
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        with_gl!(self, gl => gl.enable_vertex_attrib_array(index));
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        with_gl!(self, gl => gl.disable_vertex_attrib_array(index));
+    }
+
+    fn vertex_attrib4fv(&self, index: u32, values: &[f32]) {
+        with_gl!(self, gl => gl.vertex_attrib4fv_with_f32_array(index, values));
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        match &self.gl {
+            GlContext::WebGl1(_) => self
+                .angle_instanced_arrays()
+                .vertex_attrib_divisor_angle(index, divisor),
+            GlContext::WebGl2(gl) => gl.vertex_attrib_divisor(index, divisor),
+        }
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let vertex_array = match &self.gl {
+            GlContext::WebGl1(_) => self.oes_vertex_array_object().create_vertex_array_oes(),
+            GlContext::WebGl2(gl) => gl.create_vertex_array(),
+        }?;
+        Some(VertexArrayId(self.vertex_arrays.insert(vertex_array)))
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        let vertex_array = vertex_array.map(|VertexArrayId(id)| self.vertex_arrays.get(id));
+        match &self.gl {
+            GlContext::WebGl1(_) => self
+                .oes_vertex_array_object()
+                .bind_vertex_array_oes(vertex_array.as_ref()),
+            GlContext::WebGl2(gl) => gl.bind_vertex_array(vertex_array.as_ref()),
+        }
+    }
+
+    fn delete_vertex_array(&self, VertexArrayId(vertex_array): VertexArrayId) {
+        let vertex_array = self.vertex_arrays.remove(vertex_array);
+        match &self.gl {
+            GlContext::WebGl1(_) => self
+                .oes_vertex_array_object()
+                .delete_vertex_array_oes(Some(&vertex_array)),
+            GlContext::WebGl2(gl) => gl.delete_vertex_array(Some(&vertex_array)),
+        }
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        let texture = with_gl!(self, gl => gl.create_texture())?;
+        Some(TextureId(self.textures.insert(texture)))
+    }
+
+    fn active_texture(&self, unit: u32) {
+        with_gl!(self, gl => gl.active_texture(unit));
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        let texture = texture.map(|TextureId(id)| self.textures.get(id));
+        with_gl!(self, gl => gl.bind_texture(target, texture.as_ref()));
+    }
+
+    fn tex_image_2d(
+        &self,
+        upload: &TextureUpload,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+    ) -> Result<(), JsValue> {
+        // WebGL wants the array type to match the data type
+        let pixels: js_sys::Object = match upload.data_type {
+            WebGlRenderingContext::FLOAT => {
+                Float32Array::new(&Uint8Array::from(pixels).buffer()).into()
+            }
+            _ => Uint8Array::from(pixels).into(),
+        };
+        let TextureUpload {
+            target,
+            level,
+            internal_format,
+            format,
+            data_type,
+        } = *upload;
+        with_gl!(self, gl => gl
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+            target,
+            level,
+            internal_format,
+            width,
+            height,
+            0,
+            format,
+            data_type,
+            Some(&pixels),
+        ))
+    }
+
+    fn tex_image_2d_from_source(
+        &self,
+        upload: &TextureUpload,
+        source: ImageSource,
+    ) -> Result<(), JsValue> {
+        let TextureUpload {
+            target,
+            level,
+            internal_format,
+            format,
+            data_type,
+        } = *upload;
+        // the two contexts name the image element overload differently
+        match (&self.gl, source) {
+            (GlContext::WebGl1(gl), ImageSource::Image(image)) => gl
+                .tex_image_2d_with_u32_and_u32_and_image(
+                    target,
+                    level,
+                    internal_format,
+                    format,
+                    data_type,
+                    image,
+                ),
+            (GlContext::WebGl2(gl), ImageSource::Image(image)) => gl
+                .tex_image_2d_with_u32_and_u32_and_html_image_element(
+                    target,
+                    level,
+                    internal_format,
+                    format,
+                    data_type,
+                    image,
+                ),
+            (_, ImageSource::Bitmap(bitmap)) => with_gl!(self, gl => gl
+            .tex_image_2d_with_u32_and_u32_and_image_bitmap(
+                target,
+                level,
+                internal_format,
+                format,
+                data_type,
+                bitmap,
+            )),
+        }
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        with_gl!(self, gl => gl.tex_parameteri(target, pname, param));
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        with_gl!(self, gl => gl.generate_mipmap(target));
+    }
+
+    fn pixel_storei(&self, pname: u32, param: i32) {
+        with_gl!(self, gl => gl.pixel_storei(pname, param));
+    }
+
+    fn delete_texture(&self, TextureId(texture): TextureId) {
+        let texture = self.textures.remove(texture);
+        with_gl!(self, gl => gl.delete_texture(Some(&texture)));
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        with_gl!(self, gl => gl.viewport(x, y, width, height));
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        with_gl!(self, gl => gl.clear_color(red, green, blue, alpha));
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        with_gl!(self, gl => gl.clear_depth(depth));
+    }
+
+    fn enable(&self, cap: u32) {
+        with_gl!(self, gl => gl.enable(cap));
+    }
+
+    fn disable(&self, cap: u32) {
+        with_gl!(self, gl => gl.disable(cap));
+    }
+
+    fn depth_func(&self, func: u32) {
+        with_gl!(self, gl => gl.depth_func(func));
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        with_gl!(self, gl => gl.depth_mask(flag));
+    }
+
+    fn blend_func(&self, source: u32, destination: u32) {
+        with_gl!(self, gl => gl.blend_func(source, destination));
+    }
+
+    fn cull_face(&self, mode: u32) {
+        with_gl!(self, gl => gl.cull_face(mode));
+    }
+
+    fn clear(&self, mask: u32) {
+        with_gl!(self, gl => gl.clear(mask));
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        with_gl!(self, gl => gl.draw_arrays(mode, first, count));
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        match &self.gl {
+            GlContext::WebGl1(_) => self.angle_instanced_arrays().draw_arrays_instanced_angle(
+                mode,
+                first,
+                count,
+                instance_count,
+            ),
+            GlContext::WebGl2(gl) => gl.draw_arrays_instanced(mode, first, count, instance_count),
+        }
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        with_gl!(self, gl => gl.draw_elements_with_i32(mode, count, data_type, offset));
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        mode: u32,
+        count: i32,
+        data_type: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        match &self.gl {
+            GlContext::WebGl1(_) => self
+                .angle_instanced_arrays()
+                .draw_elements_instanced_angle_with_i32(
+                    mode,
+                    count,
+                    data_type,
+                    offset,
+                    instance_count,
+                ),
+            GlContext::WebGl2(gl) => {
+                gl.draw_elements_instanced_with_i32(mode, count, data_type, offset, instance_count)
+            }
+        }
+    }
+}